@@ -0,0 +1,236 @@
+use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
+use crate::error::Error;
+use crate::settings::EndpointsConfig;
+use crate::utils;
+use crate::utils::cache::{self, CacheHeader};
+use crate::Settings;
+
+use anyhow::{Context, Result};
+use savefile_derive::Savefile;
+use serde::{Deserialize, Serialize};
+
+/// Variables requested from the Open-Meteo Air Quality API, for both current and hourly data.
+///
+/// Pollen is only forecast for Europe, and only during the pollen season; outside of
+/// that the API returns `null` for every pollen variable.
+const FIELDS: [&str; 11] = [
+    "us_aqi",
+    "european_aqi",
+    "pm2_5",
+    "pm10",
+    "ozone",
+    "alder_pollen",
+    "birch_pollen",
+    "grass_pollen",
+    "mugwort_pollen",
+    "olive_pollen",
+    "ragweed_pollen",
+];
+
+#[derive(Default, Serialize, Deserialize, Debug, Savefile)]
+pub struct AirQuality {
+    pub current: AirQualityCurrent,
+    pub hourly: AirQualityHourly,
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default)]
+    pub created_at: u64,
+    /// Why older cached data is shown instead of fresh data, if it is
+    #[serde(skip)]
+    #[savefile_ignore]
+    pub stale_reason: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize, Debug, Savefile)]
+pub struct AirQualityCurrent {
+    pub us_aqi: Option<f64>,
+    pub european_aqi: Option<f64>,
+    pub pm2_5: Option<f64>,
+    pub pm10: Option<f64>,
+    pub ozone: Option<f64>,
+    pub alder_pollen: Option<f64>,
+    pub birch_pollen: Option<f64>,
+    pub grass_pollen: Option<f64>,
+    pub mugwort_pollen: Option<f64>,
+    pub olive_pollen: Option<f64>,
+    pub ragweed_pollen: Option<f64>,
+}
+
+/// Hourly air quality values, with times as Unix timestamps so they can be matched
+/// against any weather provider's hourly forecast regardless of its timezone.
+#[derive(Default, Serialize, Deserialize, Debug, Savefile)]
+pub struct AirQualityHourly {
+    pub time: Vec<i64>,
+    pub us_aqi: Vec<Option<f64>>,
+    pub european_aqi: Vec<Option<f64>>,
+    pub pm2_5: Vec<Option<f64>>,
+    pub pm10: Vec<Option<f64>>,
+    pub ozone: Vec<Option<f64>>,
+    pub alder_pollen: Vec<Option<f64>>,
+    pub birch_pollen: Vec<Option<f64>>,
+    pub grass_pollen: Vec<Option<f64>>,
+    pub mugwort_pollen: Vec<Option<f64>>,
+    pub olive_pollen: Vec<Option<f64>>,
+    pub ragweed_pollen: Vec<Option<f64>>,
+}
+
+impl AirQualityHourly {
+    /// Returns the air quality values for the hour starting at a Unix timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Unix timestamp of the start of the hour
+    ///
+    /// # Returns
+    ///
+    /// Returns the values for that hour, or `None` if the hour is not in the forecast.
+    pub fn at(&self, timestamp: i64) -> Option<AirQualityCurrent> {
+        let i = self.time.iter().position(|&t| t == timestamp)?;
+        let value = |series: &[Option<f64>]| series.get(i).copied().flatten();
+
+        Some(AirQualityCurrent {
+            us_aqi: value(&self.us_aqi),
+            european_aqi: value(&self.european_aqi),
+            pm2_5: value(&self.pm2_5),
+            pm10: value(&self.pm10),
+            ozone: value(&self.ozone),
+            alder_pollen: value(&self.alder_pollen),
+            birch_pollen: value(&self.birch_pollen),
+            grass_pollen: value(&self.grass_pollen),
+            mugwort_pollen: value(&self.mugwort_pollen),
+            olive_pollen: value(&self.olive_pollen),
+            ragweed_pollen: value(&self.ragweed_pollen),
+        })
+    }
+}
+
+impl AirQuality {
+    /// Retrieves air quality and pollen data for the specified coordinates, using cached data if available.
+    ///
+    /// Air quality is modelled hourly, so it is cached for an hour by default in its own
+    /// cache entry, independently of the weather forecast. When it can't be refreshed,
+    /// older cached data is used like the forecast's, with its `stale_reason` set.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate for the location
+    /// * `lon` - Longitude coordinate for the location
+    /// * `s` - Settings containing the location information, endpoints and cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns air quality data on success, or an error if fetching fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there is no recent enough cached data and:
+    /// - The API request fails
+    /// - The response cannot be parsed as JSON
+    pub async fn get_cached(lat: f64, lon: f64, s: Settings) -> Result<Self> {
+        let header = CacheHeader::new("airquality", 0, format!("{lat},{lon}")).with_provider("open-meteo");
+        let now = utils::get_now();
+
+        let aq: AirQuality = cache::load(&s.cache.dir(), &header, &s.location).unwrap_or_default();
+
+        let cached = aq.latitude == lat && aq.longitude == lon && aq.created_at > 0;
+        if cached && now - aq.created_at < s.cache.air_quality_seconds() {
+            return Ok(aq);
+        }
+
+        let mut data = match Self::fetch(lat, lon, &s.endpoints).await {
+            Ok(data) => data,
+            Err(e) if cached && cache::usable_when_stale(aq.created_at, &s) => {
+                return Ok(AirQuality { stale_reason: Some(e.root_cause().to_string()), ..aq });
+            },
+            Err(e) => return Err(e),
+        };
+        data.latitude = lat;
+        data.longitude = lon;
+        data.created_at = now;
+
+        match cache::save(&s.cache.dir(), &header, &s.location, &data) {
+            Ok(_) => {},
+            Err(e) => eprintln!("Unable to save air quality data to disk: {e:#?}"),
+        }
+
+        Ok(data)
+    }
+
+    /// Fetches fresh air quality and pollen data from the Open-Meteo Air Quality API.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate for the location
+    /// * `lon` - Longitude coordinate for the location
+    /// * `endpoints` - Endpoint settings from the configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns parsed air quality data on success, or an error if the request fails.
+    async fn fetch(lat: f64, lon: f64, endpoints: &EndpointsConfig) -> Result<Self> {
+        let fields = FIELDS.join(",");
+        let lat_str = lat.to_string();
+        let lon_str = lon.to_string();
+
+        let params: Vec<(&str, &str)> = vec![
+            ("latitude", lat_str.as_str()),
+            ("longitude", lon_str.as_str()),
+            ("current", fields.as_str()),
+            ("hourly", fields.as_str()),
+            ("timeformat", "unixtime"),
+            ("forecast_days", "2"),
+        ];
+
+        let api_url = endpoints::builder(endpoints, Endpoint::AirQuality, params);
+
+        let body = client::get_with_retry(&api_url, 2)
+            .await
+            .with_context(|| "Unable to fetch air quality data from the Open-Meteo API endpoint")?;
+
+        serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Unable to parse air quality response JSON".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::CacheConfig;
+    use crate::utils::test_server::{self, Response};
+
+    #[tokio::test]
+    async fn test_stale_fallback() {
+        let base_url = test_server::serve_responses(vec![("/v1/air-quality", Response::status(500))]);
+        let settings = Settings {
+            location: "51.0447,-114.0719,test_stale_fallback".to_string(),
+            endpoints: EndpointsConfig {
+                air_quality: Some(format!("{base_url}/v1/air-quality")),
+                ..Default::default()
+            },
+            cache: CacheConfig { dir: Some(test_server::cache_dir()), ..Default::default() },
+            ..Default::default()
+        };
+        let header = CacheHeader::new("airquality", 0, "51.0447,-114.0719").with_provider("open-meteo");
+        let cache_old = |age| {
+            let data = AirQuality {
+                current: AirQualityCurrent { us_aqi: Some(42.0), ..Default::default() },
+                latitude: 51.0447,
+                longitude: -114.0719,
+                created_at: utils::get_now() - age,
+                ..Default::default()
+            };
+            cache::save(&settings.cache.dir(), &header, &settings.location, &data).unwrap();
+        };
+
+        cache_old(7200);
+        let aq = AirQuality::get_cached(51.0447, -114.0719, settings.clone()).await.unwrap();
+        assert_eq!(aq.current.us_aqi, Some(42.0));
+        assert!(aq.stale_reason.is_some());
+
+        cache_old(settings.cache.max_stale_seconds() + 60);
+        assert!(AirQuality::get_cached(51.0447, -114.0719, settings.clone()).await.is_err());
+        let offline = Settings { offline: true, ..settings };
+        assert!(AirQuality::get_cached(51.0447, -114.0719, offline).await.is_ok());
+    }
+}
@@ -0,0 +1,462 @@
+use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
+use crate::api::location::LocationData;
+use crate::error::Error;
+use crate::settings::EndpointsConfig;
+use crate::utils;
+use crate::utils::cache::{self, CacheHeader};
+use crate::Settings;
+
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use roxmltree::{Document, Node};
+use savefile_derive::Savefile;
+use serde::{Deserialize, Serialize};
+
+/// MeteoAlarm publishes one feed per member country, named after the country.
+const METEOALARM_COUNTRIES: [(&str, &str); 38] = [
+    ("AT", "austria"),
+    ("BA", "bosnia-herzegovina"),
+    ("BE", "belgium"),
+    ("BG", "bulgaria"),
+    ("CH", "switzerland"),
+    ("CY", "cyprus"),
+    ("CZ", "czechia"),
+    ("DE", "germany"),
+    ("DK", "denmark"),
+    ("EE", "estonia"),
+    ("ES", "spain"),
+    ("FI", "finland"),
+    ("FR", "france"),
+    ("GB", "united-kingdom"),
+    ("GR", "greece"),
+    ("HR", "croatia"),
+    ("HU", "hungary"),
+    ("IE", "ireland"),
+    ("IL", "israel"),
+    ("IS", "iceland"),
+    ("IT", "italy"),
+    ("LT", "lithuania"),
+    ("LU", "luxembourg"),
+    ("LV", "latvia"),
+    ("MD", "moldova"),
+    ("ME", "montenegro"),
+    ("MK", "republic-of-north-macedonia"),
+    ("MT", "malta"),
+    ("NL", "netherlands"),
+    ("NO", "norway"),
+    ("PL", "poland"),
+    ("PT", "portugal"),
+    ("RO", "romania"),
+    ("RS", "serbia"),
+    ("SE", "sweden"),
+    ("SI", "slovenia"),
+    ("SK", "slovakia"),
+    ("UA", "ukraine"),
+];
+
+/// CAP severity levels, ordered from least to most severe.
+#[derive(Default, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Savefile)]
+pub enum Severity {
+    #[default]
+    Unknown,
+    Minor,
+    Moderate,
+    Severe,
+    Extreme,
+}
+
+/// An official weather warning, watch or advisory affecting a location.
+///
+/// `onset` and `expires` are ISO8601 local times at the issuing office
+/// (e.g. "2025-06-21T20:25"), matching the format used for forecast times.
+#[derive(Default, Serialize, Deserialize, Debug, Clone, Savefile)]
+pub struct Alert {
+    pub event: String,
+    pub headline: String,
+    pub severity: Severity,
+    pub description: String,
+    pub area: String,
+    pub onset: String,
+    pub expires: String,
+    #[serde(skip)]
+    pub expires_at: u64,
+}
+
+#[derive(Default, Serialize, Deserialize, Debug, Savefile)]
+pub struct Alerts {
+    pub alerts: Vec<Alert>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub created_at: u64,
+    /// Why older cached data is shown instead of fresh data, if it is
+    #[serde(skip)]
+    #[savefile_ignore]
+    pub stale_reason: Option<String>,
+}
+
+/// A parsed alert together with the geometry of the area it covers.
+struct AlertEntry {
+    alert: Alert,
+    polygons: Vec<Vec<(f64, f64)>>,
+    circles: Vec<(f64, f64, f64)>,
+}
+
+impl Alerts {
+    /// Retrieves the active alerts for a location, using cached data if available.
+    ///
+    /// Alerts are cached for 5 minutes by default, separately from the forecast, so new
+    /// warnings are picked up quickly. When they can't be refreshed, older cached alerts
+    /// are used like the forecast, with their `stale_reason` set.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The resolved location to find alerts for
+    /// * `s` - Settings containing the location information, endpoints and cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns the active alerts, most severe first, or an error if fetching fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there are no recent enough cached alerts and:
+    /// - The alert feed request fails
+    /// - The feed cannot be parsed as CAP or Atom XML
+    pub async fn get_cached(location: &LocationData, s: Settings) -> Result<Self> {
+        let header = CacheHeader::new("alerts", 0, format!("{},{}", location.latitude, location.longitude));
+        let now = utils::get_now();
+
+        let data: Alerts = cache::load(&s.cache.dir(), &header, &s.location).unwrap_or_default();
+
+        let cached =
+            data.latitude == location.latitude && data.longitude == location.longitude && data.created_at > 0;
+        if cached && now - data.created_at < s.cache.alerts_seconds() {
+            return Ok(data);
+        }
+
+        let alerts = match Self::fetch(location, &s.endpoints).await {
+            Ok(alerts) => alerts,
+            Err(e) if cached && cache::usable_when_stale(data.created_at, &s) => {
+                return Ok(Alerts { stale_reason: Some(e.root_cause().to_string()), ..data });
+            },
+            Err(e) => return Err(e),
+        };
+        let data = Alerts {
+            alerts,
+            latitude: location.latitude,
+            longitude: location.longitude,
+            created_at: now,
+            stale_reason: None,
+        };
+
+        match cache::save(&s.cache.dir(), &header, &s.location, &data) {
+            Ok(_) => {},
+            Err(e) => eprintln!("Unable to save alert data to disk: {e:#?}"),
+        }
+
+        Ok(data)
+    }
+
+    /// Fetches active alerts from the official warning feed for the location's country.
+    ///
+    /// - United States: the National Weather Service alerts feed, filtered to the exact point
+    /// - Europe: the MeteoAlarm country feed, filtered by warning area
+    ///
+    /// Locations in other countries have no alert source and return no alerts.
+    async fn fetch(location: &LocationData, endpoints: &EndpointsConfig) -> Result<Vec<Alert>> {
+        let country = location.country_code.to_uppercase();
+
+        let entries = if country == "US" {
+            let api_url = format!(
+                "{}/alerts/active.atom?point={:.4},{:.4}",
+                endpoints::url(endpoints, Endpoint::Nws),
+                location.latitude,
+                location.longitude
+            );
+            let body = client::get_with_retry(&api_url, 2)
+                .await
+                .with_context(|| "Unable to fetch alerts from the National Weather Service")?;
+
+            // The NWS feed is already filtered to alerts covering the point
+            Self::parse(&body)?
+        } else if let Some((_, feed)) = METEOALARM_COUNTRIES.iter().find(|(code, _)| *code == country) {
+            let api_url =
+                format!("{}/meteoalarm-legacy-atom-{feed}", endpoints::url(endpoints, Endpoint::Meteoalarm));
+            let body = client::get_with_retry(&api_url, 2)
+                .await
+                .with_context(|| "Unable to fetch alerts from MeteoAlarm")?;
+
+            Self::parse(&body)?
+                .into_iter()
+                .filter(|entry| entry.covers(location.latitude, location.longitude, &location.city))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut alerts: Vec<Alert> = entries.into_iter().map(|entry| entry.alert).collect();
+        alerts.sort_by_key(|alert| std::cmp::Reverse(alert.severity));
+
+        Ok(alerts)
+    }
+
+    /// Parses alerts from a CAP document or an Atom feed of CAP entries.
+    ///
+    /// Atom feeds (NWS, MeteoAlarm) carry the CAP fields directly on each entry,
+    /// while a CAP document has one `info` block per language; the English one
+    /// is used when available. Cancellations are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `xml` - The CAP or Atom XML document
+    ///
+    /// # Returns
+    ///
+    /// Returns every alert in the document along with the area it covers.
+    fn parse(xml: &str) -> Result<Vec<AlertEntry>> {
+        let doc =
+            Document::parse(xml).with_context(|| Error::Parse("Unable to parse alert XML".to_string()))?;
+        let root = doc.root_element();
+
+        let entries = match root.tag_name().name() {
+            "feed" => root
+                .children()
+                .filter(|n| n.has_tag_name(("http://www.w3.org/2005/Atom", "entry")))
+                .filter(|entry| !is_cancellation(*entry))
+                .map(|entry| AlertEntry::from_node(entry, text(entry, "title"), text(entry, "summary")))
+                .collect(),
+            "alert" if !is_cancellation(root) => {
+                let infos: Vec<Node> = root.children().filter(|n| is_tag(*n, "info")).collect();
+                let info = infos
+                    .iter()
+                    .find(|info| text(**info, "language").map_or(true, |lang| lang.starts_with("en")))
+                    .or(infos.first());
+
+                info.map(|info| {
+                    let mut entry =
+                        AlertEntry::from_node(*info, text(*info, "headline"), text(*info, "description"));
+                    for area in info.children().filter(|n| is_tag(*n, "area")) {
+                        entry.add_area(area);
+                    }
+                    entry
+                })
+                .into_iter()
+                .collect()
+            },
+            "alert" => Vec::new(),
+            other => return Err(Error::Parse(format!("Unsupported alert document <{other}>")).into()),
+        };
+
+        Ok(entries)
+    }
+}
+
+impl AlertEntry {
+    /// Builds an alert from a node holding CAP fields, either an Atom entry or a CAP `info` block.
+    fn from_node(node: Node, headline: Option<&str>, description: Option<&str>) -> Self {
+        let event = text(node, "event").unwrap_or_default().to_string();
+        let expires = text(node, "expires").and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+
+        let mut entry = AlertEntry {
+            alert: Alert {
+                headline: headline.map(str::to_string).unwrap_or_else(|| event.clone()),
+                event,
+                severity: match text(node, "severity").unwrap_or_default() {
+                    "Extreme" => Severity::Extreme,
+                    "Severe" => Severity::Severe,
+                    "Moderate" => Severity::Moderate,
+                    "Minor" => Severity::Minor,
+                    _ => Severity::Unknown,
+                },
+                description: description.unwrap_or_default().to_string(),
+                area: String::new(),
+                onset: local_time(text(node, "onset").or_else(|| text(node, "effective"))),
+                expires: local_time(text(node, "expires")),
+                expires_at: expires.map(|t| t.timestamp().max(0) as u64).unwrap_or_default(),
+            },
+            polygons: Vec::new(),
+            circles: Vec::new(),
+        };
+
+        // Atom entries carry the area fields directly instead of in an `area` block
+        entry.add_area(node);
+        entry
+    }
+
+    /// Adds the description and geometry of a CAP area to the alert.
+    fn add_area(&mut self, area: Node) {
+        if let Some(description) = text(area, "areaDesc") {
+            if !self.alert.area.is_empty() {
+                self.alert.area.push_str("; ");
+            }
+            self.alert.area.push_str(description);
+        }
+
+        for polygon in area.children().filter(|n| is_tag(*n, "polygon")).filter_map(|n| n.text()) {
+            let points: Vec<(f64, f64)> = polygon.split_whitespace().filter_map(parse_point).collect();
+            if points.len() >= 3 {
+                self.polygons.push(points);
+            }
+        }
+
+        for circle in area.children().filter(|n| is_tag(*n, "circle")).filter_map(|n| n.text()) {
+            if let Some((point, radius)) = circle.trim().split_once(' ') {
+                if let (Some((lat, lon)), Ok(radius)) = (parse_point(point), radius.parse()) {
+                    self.circles.push((lat, lon, radius));
+                }
+            }
+        }
+    }
+
+    /// Checks whether the alert applies to a location.
+    ///
+    /// Alerts with a polygon or circle are matched geometrically. Alerts that only
+    /// name their area are matched when the area description mentions the city.
+    fn covers(&self, lat: f64, lon: f64, city: &str) -> bool {
+        if self.polygons.is_empty() && self.circles.is_empty() {
+            return !city.is_empty() && self.alert.area.to_lowercase().contains(&city.to_lowercase());
+        }
+
+        self.polygons.iter().any(|polygon| point_in_polygon(lat, lon, polygon))
+            || self.circles.iter().any(|&(c_lat, c_lon, radius)| {
+                // Equirectangular approximation, plenty accurate at warning-area scale
+                let x = (lon - c_lon).to_radians() * ((lat + c_lat) / 2.0).to_radians().cos();
+                let y = (lat - c_lat).to_radians();
+                (x * x + y * y).sqrt() * 6371.0 <= radius
+            })
+    }
+}
+
+/// Checks whether a node has the given local tag name, in any namespace.
+fn is_tag(node: Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
+
+/// Returns the trimmed, non-empty text of the first child with the given local tag name.
+fn text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children().find(|n| is_tag(*n, name)).and_then(|n| n.text()).map(str::trim).filter(|t| !t.is_empty())
+}
+
+/// Checks whether a CAP message cancels an earlier alert.
+///
+/// NWS uses the CAP `msgType` name, MeteoAlarm's Atom feed uses `message_type`.
+fn is_cancellation(node: Node) -> bool {
+    text(node, "msgType").or_else(|| text(node, "message_type")) == Some("Cancel")
+}
+
+/// Converts an RFC3339 timestamp into an ISO8601 local time without the offset.
+fn local_time(timestamp: Option<&str>) -> String {
+    timestamp
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.format("%Y-%m-%dT%H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// Parses a CAP "lat,lon" coordinate pair.
+fn parse_point(point: &str) -> Option<(f64, f64)> {
+    let (lat, lon) = point.split_once(',')?;
+    Some((lat.trim().parse().ok()?, lon.trim().parse().ok()?))
+}
+
+/// Ray casting test for whether a point lies inside a polygon of (lat, lon) vertices.
+fn point_in_polygon(lat: f64, lon: f64, polygon: &[(f64, f64)]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+
+    for i in 0..polygon.len() {
+        let (lat_i, lon_i) = polygon[i];
+        let (lat_j, lon_j) = polygon[j];
+
+        if (lat_i > lat) != (lat_j > lat) && lon < (lon_j - lon_i) * (lat - lat_i) / (lat_j - lat_i) + lon_i {
+            inside = !inside;
+        }
+        j = i;
+    }
+
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{CacheConfig, EndpointsConfig};
+    use crate::utils::test_server::{self, Response};
+
+    #[test]
+    fn test_parse_nws_atom() {
+        let entries = Alerts::parse(include_str!("../../fixtures/alerts/nws_active.atom")).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        let alert = &entries[0].alert;
+        assert_eq!(alert.event, "Severe Thunderstorm Warning");
+        assert_eq!(alert.severity, Severity::Severe);
+        assert_eq!(alert.onset, "2025-06-21T20:25");
+        assert_eq!(alert.expires, "2025-06-21T21:15");
+        assert_eq!(alert.expires_at, 1750558500);
+        assert!(alert.headline.starts_with("Severe Thunderstorm Warning issued June 21"));
+        assert!(alert.description.starts_with("At 824 PM CDT"));
+        assert!(entries[0].covers(39.7456, -97.0892, ""));
+        assert!(!entries[0].covers(39.0, -97.0892, ""));
+        assert_eq!(entries[1].alert.severity, Severity::Moderate);
+    }
+
+    #[test]
+    fn test_parse_meteoalarm_atom() {
+        let entries = Alerts::parse(include_str!("../../fixtures/alerts/meteoalarm_germany.atom")).unwrap();
+        let berlin: Vec<&Alert> =
+            entries.iter().filter(|e| e.covers(52.52, 13.405, "Berlin")).map(|e| &e.alert).collect();
+
+        assert_eq!(berlin.len(), 1);
+        assert_eq!(berlin[0].event, "Moderate thunderstorm warning");
+        assert_eq!(berlin[0].onset, "2025-06-21T19:00");
+    }
+
+    #[test]
+    fn test_parse_cap_document() {
+        let entries = Alerts::parse(include_str!("../../fixtures/alerts/cap_alert.xml")).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].alert.headline, "heat warning in effect");
+        assert_eq!(entries[0].alert.area, "City of Edmonton - St. Albert - Sherwood Park");
+        assert!(entries[0].covers(53.55, -113.49, ""));
+        assert!(!entries[0].covers(51.05, -114.07, "Calgary"));
+    }
+
+    #[tokio::test]
+    async fn test_stale_fallback() {
+        let base_url = test_server::serve_responses(vec![("/alerts/active.atom", Response::status(500))]);
+        let location = LocationData {
+            country_code: "US".to_string(),
+            latitude: 39.7456,
+            longitude: -97.0892,
+            ..Default::default()
+        };
+        let settings = Settings {
+            location: "39.7456,-97.0892,test_stale_fallback".to_string(),
+            endpoints: EndpointsConfig { nws: Some(base_url), ..Default::default() },
+            cache: CacheConfig { dir: Some(test_server::cache_dir()), ..Default::default() },
+            ..Default::default()
+        };
+        let header = CacheHeader::new("alerts", 0, "39.7456,-97.0892");
+        let cache_old = |age| {
+            let data = Alerts {
+                alerts: vec![Alert { event: "Heat Advisory".to_string(), ..Default::default() }],
+                latitude: location.latitude,
+                longitude: location.longitude,
+                created_at: utils::get_now() - age,
+                ..Default::default()
+            };
+            cache::save(&settings.cache.dir(), &header, &settings.location, &data).unwrap();
+        };
+
+        cache_old(3600);
+        let alerts = Alerts::get_cached(&location, settings.clone()).await.unwrap();
+        assert_eq!(alerts.alerts[0].event, "Heat Advisory");
+        assert!(alerts.stale_reason.is_some());
+
+        cache_old(settings.cache.max_stale_seconds() + 60);
+        assert!(Alerts::get_cached(&location, settings.clone()).await.is_err());
+        assert!(Alerts::get_cached(&location, Settings { offline: true, ..settings }).await.is_ok());
+    }
+}
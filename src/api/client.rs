@@ -1,37 +1,131 @@
+use crate::error::Error;
+use crate::settings::HttpConfig;
+use crate::utils::urls;
+
 use anyhow::{Context, Result};
-use isahc::config::Configurable;
-use isahc::{HttpClient, HttpClientBuilder, ReadResponseExt};
+use isahc::config::{CaCertificate, Configurable};
+use isahc::http::{StatusCode, Uri};
+use isahc::{AsyncReadResponseExt, HttpClient, HttpClientBuilder};
 use std::sync::OnceLock;
 use std::time::Duration;
 
+const DEFAULT_USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"), " ", env!("CARGO_PKG_HOMEPAGE"));
+
+const DEFAULT_CONNECT_TIMEOUT_SECONDS: u64 = 2;
+const DEFAULT_TIMEOUT_SECONDS: u64 = 5;
+const DEFAULT_MAX_BACKOFF_SECONDS: u64 = 10;
+const DOWNLOAD_TIMEOUT_SECONDS: u64 = 120;
+
 static HTTP_CLIENT: OnceLock<HttpClient> = OnceLock::new();
+static HTTP_CONFIG: OnceLock<HttpConfig> = OnceLock::new();
+static OFFLINE: OnceLock<bool> = OnceLock::new();
+
+/// A failed request, with how long the server asked us to wait before retrying.
+struct Failure {
+    error: anyhow::Error,
+    retry_after: Option<Duration>,
+}
+
+/// Applies the user's HTTP configuration to the shared HTTP client.
+///
+/// Must be called before the first request is made, since the client is only
+/// built once. Subsequent calls are ignored.
+///
+/// # Arguments
+///
+/// * `config` - HTTP settings from the configuration file
+/// * `offline` - Whether to refuse every request, so only cached data is used
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The proxy is not a valid URL
+/// - The CA bundle does not exist
+pub fn configure(config: &HttpConfig, offline: bool) -> Result<()> {
+    if let Some(proxy) = &config.proxy {
+        proxy.parse::<Uri>().with_context(|| Error::Config(format!("Invalid HTTP proxy '{proxy}'")))?;
+    }
+    if let Some(ca_bundle) = &config.ca_bundle {
+        if !std::path::Path::new(ca_bundle).is_file() {
+            return Err(Error::Config(format!("The CA bundle {ca_bundle} does not exist")).into());
+        }
+    }
+
+    let _ = HTTP_CONFIG.set(config.clone());
+    let _ = OFFLINE.set(offline);
+    Ok(())
+}
+
+/// Fails without sending anything when running in offline mode.
+fn ensure_online(url: &str) -> Result<()> {
+    if *OFFLINE.get().unwrap_or(&false) {
+        return Err(Error::Network(format!("Offline mode, not requesting {}", urls::redact(url))).into());
+    }
+    Ok(())
+}
+
+/// Returns the user's HTTP configuration, or the defaults if none was applied.
+fn config() -> &'static HttpConfig {
+    HTTP_CONFIG.get_or_init(HttpConfig::default)
+}
+
+/// Returns the User-Agent sent with every request.
+///
+/// Some providers (e.g. MET Norway) reject requests without an identifying
+/// User-Agent, so a default naming this application is always sent.
+///
+/// # Returns
+///
+/// Returns the configured User-Agent, or the application name, version and homepage.
+pub fn user_agent() -> String {
+    config().user_agent.clone().unwrap_or_else(|| DEFAULT_USER_AGENT.to_string())
+}
 
 /// Returns a shared HTTP client instance configured with appropriate timeouts and connection pooling.
 ///
 /// The client is created once and reused for all HTTP requests to improve performance.
 /// Configuration includes:
-/// - 2 second connection timeout
-/// - 5 second request timeout
+/// - 2 second connection timeout, unless configured
 /// - 15 second TCP keepalive
 /// - Maximum 4 connections per host
+/// - An identifying User-Agent header (see `user_agent`)
+/// - The configured proxy and CA bundle, if any
+///
+/// Without a configured proxy, the standard `HTTPS_PROXY`, `https_proxy`, `http_proxy`,
+/// `ALL_PROXY` and `NO_PROXY` environment variables are honored.
 ///
 /// # Returns
 ///
 /// Returns a reference to the global HTTP client instance.
 pub fn get_client() -> &'static HttpClient {
     HTTP_CLIENT.get_or_init(|| {
-        HttpClientBuilder::new()
-            .connect_timeout(Duration::from_secs(2))
-            .timeout(Duration::from_secs(5))
+        let config = config();
+        let mut builder = HttpClientBuilder::new()
+            .connect_timeout(Duration::from_secs(
+                config.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECONDS),
+            ))
             .tcp_keepalive(Duration::from_secs(15))
             .max_connections_per_host(4)
-            .build()
-            .expect("Unable to create HTTP client")
+            .default_header("User-Agent", user_agent());
+
+        // Proxies were validated by `configure`
+        if let Some(proxy) = config.proxy.as_ref().and_then(|proxy| proxy.parse::<Uri>().ok()) {
+            builder = builder.proxy(proxy);
+        }
+        if let Some(ca_bundle) = &config.ca_bundle {
+            builder = builder.ssl_ca_certificate(CaCertificate::file(ca_bundle));
+        }
+
+        builder.build().expect("Unable to create HTTP client")
     })
 }
 
 /// Performs a GET request to the specified URL and returns the response body as a string.
 ///
+/// The request is given 5 seconds to complete, or the configured `timeout`, and is
+/// cancelled when it runs out, as it is whenever the returned future is dropped.
+///
 /// # Arguments
 ///
 /// * `url` - The URL to send the GET request to
@@ -44,34 +138,145 @@ pub fn get_client() -> &'static HttpClient {
 /// # Errors
 ///
 /// This function will return an error if:
-/// - The HTTP request fails to send
+/// - Running in offline mode
+/// - The HTTP request fails to send or times out
 /// - The response status indicates failure
 /// - The response body cannot be read as text
-pub fn get(url: &str) -> Result<String> {
+pub async fn get(url: &str) -> Result<String> {
+    ensure_online(url)?;
+    send(url).await.map_err(|failure| failure.error)
+}
+
+/// Downloads a file, e.g. the offline city database, and returns its contents.
+///
+/// Downloads are given 2 minutes to complete, rather than the timeout of other requests.
+///
+/// # Arguments
+///
+/// * `url` - The URL of the file
+///
+/// # Returns
+///
+/// Returns the raw response body on success.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - Running in offline mode
+/// - The HTTP request fails to send or times out
+/// - The response status indicates failure
+/// - The response body cannot be read
+pub async fn download(url: &str) -> Result<Vec<u8>> {
+    ensure_online(url)?;
+    let timeout = Duration::from_secs(DOWNLOAD_TIMEOUT_SECONDS);
+
+    let body = async {
+        let mut response = get_client()
+            .get_async(url)
+            .await
+            .with_context(|| Error::Network(format!("Unable to send request to {}", urls::redact(url))))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::HttpStatus { status: status.as_u16(), url: urls::redact(url) }.into());
+        }
+
+        response
+            .bytes()
+            .await
+            .with_context(|| Error::Network(format!("Unable to download {}", urls::redact(url))))
+    };
+
+    tokio::time::timeout(timeout, body).await.unwrap_or_else(|_| {
+        let url = urls::redact(url);
+        Err(Error::Network(format!("Download of {url} timed out after {} seconds", timeout.as_secs())).into())
+    })
+}
+
+/// Sends a GET request with a timeout, noting the `Retry-After` delay of a rate limited
+/// or unavailable server.
+async fn send(url: &str) -> Result<String, Failure> {
+    let timeout = Duration::from_secs(config().timeout.unwrap_or(DEFAULT_TIMEOUT_SECONDS));
+
+    tokio::time::timeout(timeout, request(url)).await.unwrap_or_else(|_| {
+        Err(Failure {
+            error: Error::Network(format!(
+                "Request to {} timed out after {} seconds",
+                urls::redact(url),
+                timeout.as_secs()
+            ))
+            .into(),
+            retry_after: None,
+        })
+    })
+}
+
+async fn request(url: &str) -> Result<String, Failure> {
+    let failure = |error| Failure { error, retry_after: None };
     let client = get_client();
 
-    let mut response = client.get(url).with_context(|| format!("Unable to send request to {url}"))?;
+    let mut response = client
+        .get_async(url)
+        .await
+        .with_context(|| Error::Network(format!("Unable to send request to {}", urls::redact(url))))
+        .map_err(failure)?;
 
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "HTTP request failed with status: {} for URL: {}",
-            response.status(),
-            url
-        ));
+    let status = response.status();
+    if !status.is_success() {
+        let retry_after = match status {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => response
+                .headers()
+                .get("Retry-After")
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_retry_after),
+            _ => None,
+        };
+        return Err(Failure {
+            error: Error::HttpStatus { status: status.as_u16(), url: urls::redact(url) }.into(),
+            retry_after,
+        });
     }
 
-    response.text().with_context(|| format!("Unable to read response body from {url}"))
+    response
+        .text()
+        .await
+        .with_context(|| Error::Network(format!("Unable to read response body from {}", urls::redact(url))))
+        .map_err(failure)
+}
+
+/// Reads a `Retry-After` header, given either as seconds or as an HTTP date.
+///
+/// # Arguments
+///
+/// * `value` - The header value, e.g. "120" or "Wed, 21 Oct 2015 07:28:00 GMT"
+///
+/// # Returns
+///
+/// Returns how long to wait, or `None` if the value can't be parsed.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let seconds = (date.timestamp() - chrono::Utc::now().timestamp()).max(0);
+    Some(Duration::from_secs(seconds as u64))
 }
 
 /// Performs a GET request with exponential backoff retry logic.
 ///
-/// Attempts the request up to `max_retries + 1` times (initial attempt plus retries).
-/// Uses exponential backoff with a base delay of 100ms, doubling on each retry.
+/// Attempts the request up to `max_retries + 1` times (initial attempt plus retries),
+/// or as many times as the configured `retries` allow. Uses exponential backoff with a
+/// base delay of 100ms, doubling on each retry. When a rate limited (429) or unavailable
+/// (503) server sends `Retry-After`, that delay is waited instead. Backoff is capped at the
+/// configured `max_backoff`, 10 seconds by default, and when the server asks to wait longer
+/// than that, the request fails right away rather than retrying early.
 ///
 /// # Arguments
 ///
 /// * `url` - The URL to send the GET request to
-/// * `max_retries` - Maximum number of retry attempts after the initial request
+/// * `max_retries` - Maximum number of retry attempts after the initial request, unless configured
 ///
 /// # Returns
 ///
@@ -80,20 +285,29 @@ pub fn get(url: &str) -> Result<String> {
 ///
 /// # Errors
 ///
-/// This function will return an error if all retry attempts fail. The error
-/// returned will be from the final attempt.
-pub fn get_with_retry(url: &str, max_retries: usize) -> Result<String> {
+/// This function will return an error if running in offline mode, or if all retry
+/// attempts fail. The error returned will be from the final attempt.
+pub async fn get_with_retry(url: &str, max_retries: usize) -> Result<String> {
+    ensure_online(url)?;
+    let config = config();
+    let max_retries = config.retries.unwrap_or(max_retries);
+    let max_backoff = Duration::from_secs(config.max_backoff.unwrap_or(DEFAULT_MAX_BACKOFF_SECONDS));
     let mut last_error = None;
 
     for attempt in 0..=max_retries {
-        match get(url) {
+        match send(url).await {
             Ok(response) => return Ok(response),
-            Err(e) => {
-                last_error = Some(e);
+            Err(Failure { error, retry_after }) => {
+                // Retrying before the server is ready again would only be refused
+                if retry_after.is_some_and(|delay| delay > max_backoff) {
+                    return Err(error);
+                }
+
+                last_error = Some(error);
                 if attempt < max_retries {
-                    // Simple backoff strategy: wait 100ms * 2^attempt
-                    let delay = Duration::from_millis(100 * (2_u64.pow(attempt as u32)));
-                    std::thread::sleep(delay);
+                    // Simple backoff strategy: wait 100ms * 2^attempt, unless told otherwise
+                    let backoff = Duration::from_millis(100 * 2_u64.saturating_pow(attempt as u32));
+                    tokio::time::sleep(retry_after.unwrap_or(backoff).min(max_backoff)).await;
                 }
             },
         }
@@ -101,3 +315,30 @@ pub fn get_with_retry(url: &str, max_retries: usize) -> Result<String> {
 
     Err(last_error.unwrap())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_server::{self, Response};
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_beyond_max_backoff() {
+        let base_url = test_server::serve_responses(vec![(
+            "/limited",
+            Response::status(429).with_header("Retry-After", "120"),
+        )]);
+
+        let started = std::time::Instant::now();
+        let error = get_with_retry(&format!("{base_url}/limited"), 2).await.unwrap_err();
+
+        assert!(matches!(Error::find(&error), Some(Error::HttpStatus { status: 429, .. })));
+        assert!(started.elapsed() < Duration::from_secs(DEFAULT_MAX_BACKOFF_SECONDS));
+    }
+}
@@ -0,0 +1,477 @@
+use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
+use crate::api::weather::*;
+use crate::error::Error;
+use crate::utils;
+use crate::utils::cache::{self, CacheHeader};
+use crate::utils::{conversions, mappings, solar};
+use crate::Settings;
+
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDateTime};
+use roxmltree::{Document, Node};
+use savefile_derive::Savefile;
+use serde::{Deserialize, Serialize};
+
+/// Weather provider backed by Environment and Climate Change Canada citypage forecasts.
+///
+/// ECCC publishes one XML document per forecast site (city) on the MSC Datamart,
+/// containing the latest observation, the official 7-day text forecast in day and
+/// night periods, a 24-hour hourly forecast and today's sunrise and sunset.
+pub struct Eccc;
+
+/// The ECCC citypage forecast site closest to a pair of coordinates.
+///
+/// Resolved from the published site list and cached next to the location cache.
+#[derive(Default, Deserialize, Serialize, Debug, Savefile)]
+pub struct CitySite {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub code: String,
+    pub province: String,
+    pub name: String,
+    pub created_at: u64,
+}
+
+#[derive(Default)]
+struct ForecastDay {
+    high: Option<f64>,
+    low: Option<f64>,
+    icon: Option<i32>,
+    precipitation_chance: i32,
+    precipitation_sum: f64,
+    uv_index: f64,
+    text: Vec<String>,
+}
+
+impl CitySite {
+    /// Resolves coordinates to the nearest ECCC forecast site, using cached data if available.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate
+    /// * `lon` - Longitude coordinate
+    /// * `s` - Settings with the endpoints and cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns the site code and province needed to build the citypage URL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the site list cannot be fetched or parsed.
+    pub async fn get_cached(lat: f64, lon: f64, s: &Settings) -> Result<Self> {
+        let key = format!("{lat},{lon}");
+        let header = CacheHeader::new("citypage", 0, key.as_str()).with_provider("eccc");
+
+        let site: CitySite = cache::load(&s.cache.dir(), &header, &key).unwrap_or_default();
+        if site.latitude == lat && site.longitude == lon && site.created_at > 0 {
+            return Ok(site);
+        }
+
+        let data = Self::fetch(&endpoints::url(&s.endpoints, Endpoint::Eccc), lat, lon).await?;
+
+        match cache::save(&s.cache.dir(), &header, &key, &data) {
+            Ok(_) => {},
+            Err(e) => eprintln!("Unable to save citypage site data to disk: {e:#?}"),
+        }
+
+        Ok(data)
+    }
+
+    /// Downloads the ECCC site list and picks the site closest to the coordinates.
+    async fn fetch(base_url: &str, lat: f64, lon: f64) -> Result<Self> {
+        let api_url = format!("{base_url}/docs/site_list_en.csv");
+
+        let body = client::get_with_retry(&api_url, 2)
+            .await
+            .with_context(|| "Unable to fetch the ECCC site list")?;
+
+        Self::nearest(&body, lat, lon)
+            .map(|mut site| {
+                site.latitude = lat;
+                site.longitude = lon;
+                site.created_at = utils::get_now();
+                site
+            })
+            .ok_or_else(|| {
+                Error::LocationNotFound(format!("No ECCC forecast site found near {lat},{lon}")).into()
+            })
+    }
+
+    /// Finds the closest site in the ECCC site list CSV.
+    ///
+    /// The site list has a title line, then a header naming the columns. Coordinates
+    /// are written with hemisphere suffixes (e.g. "53.55N", "113.49W").
+    fn nearest(csv: &str, lat: f64, lon: f64) -> Option<Self> {
+        let mut lines = csv.lines().skip_while(|line| !line.starts_with("Codes"));
+        let header: Vec<&str> = lines.next()?.split(',').map(str::trim).collect();
+        let column = |name: &str| header.iter().position(|h| *h == name);
+        let (code_col, name_col, prov_col) =
+            (column("Codes")?, column("English Names")?, column("Province Codes")?);
+        let (lat_col, lon_col) = (column("Latitude")?, column("Longitude")?);
+
+        lines
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+                let site_lat = Self::parse_coordinate(fields.get(lat_col)?)?;
+                let site_lon = Self::parse_coordinate(fields.get(lon_col)?)?;
+                let distance = (site_lat - lat).powi(2) + ((site_lon - lon) * lat.to_radians().cos()).powi(2);
+
+                let site = CitySite {
+                    code: fields.get(code_col)?.to_string(),
+                    name: fields.get(name_col)?.to_string(),
+                    province: fields.get(prov_col)?.to_string(),
+                    ..Default::default()
+                };
+                Some((distance, site))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, site)| site)
+    }
+
+    /// Parses a coordinate such as "53.55N" or "113.49W" into signed degrees.
+    fn parse_coordinate(value: &str) -> Option<f64> {
+        let (number, hemisphere) = value.split_at(value.len().checked_sub(1)?);
+        let degrees: f64 = number.parse().ok()?;
+        match hemisphere {
+            "N" | "E" => Some(degrees),
+            "S" | "W" => Some(-degrees),
+            _ => None,
+        }
+    }
+}
+
+impl WeatherProvider for Eccc {
+    /// Fetches fresh weather data from the ECCC citypage weather datamart.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate for the weather location
+    /// * `lon` - Longitude coordinate for the weather location
+    /// * `_timezone` - Unused, Environment Canada reports local times itself
+    /// * `s` - Settings with the endpoints and cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns weather data mapped into the provider-neutral model.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The site list or citypage request fails
+    /// - The XML document cannot be parsed
+    /// - The document contains no forecast
+    async fn fetch(lat: f64, lon: f64, _timezone: &str, s: &Settings) -> Result<Weather> {
+        let base_url = endpoints::url(&s.endpoints, Endpoint::Eccc);
+        let site = CitySite::get_cached(lat, lon, s).await?;
+        Self::fetch_site(&base_url, &site, lat, lon).await
+    }
+}
+
+impl Eccc {
+    /// Fetches and parses the citypage document for a forecast site.
+    async fn fetch_site(base_url: &str, site: &CitySite, lat: f64, lon: f64) -> Result<Weather> {
+        let api_url = format!("{base_url}/xml/{}/{}_e.xml", site.province, site.code);
+
+        let body = client::get_with_retry(&api_url, 2)
+            .await
+            .with_context(|| format!("Unable to fetch the ECCC forecast for {}", site.name))?;
+
+        Self::parse(&body, lat, lon)
+    }
+
+    /// Maps an ECCC citypage XML document into the provider-neutral weather model.
+    ///
+    /// - Current conditions come from the latest observation, falling back to the
+    ///   first hourly forecast when the station has not reported
+    /// - Day and night forecast periods are paired into daily highs and lows, and
+    ///   their text forecasts are kept as the daily narrative
+    /// - Precipitation amounts are only published per period; snowfall in cm is
+    ///   treated as the equivalent mm of water
+    ///
+    /// # Arguments
+    ///
+    /// * `xml` - The citypage XML document
+    /// * `lat` - Latitude coordinate, used for sunrise and sunset after today
+    /// * `lon` - Longitude coordinate, used for sunrise and sunset after today
+    ///
+    /// # Returns
+    ///
+    /// Returns weather data in metric units, or an error if the document is invalid.
+    pub fn parse(xml: &str, lat: f64, lon: f64) -> Result<Weather> {
+        let doc = Document::parse(xml)
+            .with_context(|| Error::Parse("Unable to parse ECCC citypage XML".to_string()))?;
+        let root = doc.root_element();
+
+        let forecast_group = child(root, "forecastGroup")
+            .ok_or_else(|| Error::Parse("ECCC forecast is missing".to_string()))?;
+        let (issued, utc_offset_seconds) = local_timestamp(forecast_group, "forecastIssue")
+            .ok_or_else(|| Error::Parse("ECCC forecast issue time is missing".to_string()))?;
+
+        let mut weather = Weather {
+            timezone: forecast_group
+                .children()
+                .find(|n| n.has_tag_name("dateTime") && n.attribute("zone") != Some("UTC"))
+                .and_then(|n| n.attribute("zone"))
+                .unwrap_or_default()
+                .to_string(),
+            utc_offset_seconds,
+            latitude: lat,
+            longitude: lon,
+            ..Default::default()
+        };
+
+        if let Some(hourly_group) = child(root, "hourlyForecastGroup") {
+            for hour in hourly_group.children().filter(|n| n.has_tag_name("hourlyForecast")) {
+                let Some(utc) = hour
+                    .attribute("dateTimeUTC")
+                    .and_then(|t| NaiveDateTime::parse_from_str(t, "%Y%m%d%H%M").ok())
+                else {
+                    continue;
+                };
+                let local = utc + Duration::seconds(utc_offset_seconds as i64);
+
+                weather.hourly.time.push(local.format("%Y-%m-%dT%H:%M").to_string());
+                weather.hourly.temperature_2m.push(number(hour, &["temperature"]).unwrap_or_default());
+                weather
+                    .hourly
+                    .precipitation_probability
+                    .push(number(hour, &["lop"]).unwrap_or_default() as i32);
+                weather.hourly.precipitation.push(0.0);
+                weather
+                    .hourly
+                    .weather_code
+                    .push(mappings::eccc_icon2meteo_code(number(hour, &["iconCode"]).unwrap_or(-1.0) as i32));
+            }
+        }
+
+        weather.current = Self::current(child(root, "currentConditions"), &weather.hourly);
+        weather.narrative =
+            child(forecast_group, "forecast").and_then(|f| text(f, &["textSummary"])).map(str::to_string);
+
+        // A daytime period starts a new day, a nighttime period completes the current
+        // one, unless the forecast starts with "Tonight"
+        let mut days: Vec<ForecastDay> = Vec::new();
+        for period in forecast_group.children().filter(|n| n.has_tag_name("forecast")) {
+            let temperature = child(period, "temperatures").and_then(|t| child(t, "temperature"));
+            let is_night = temperature.and_then(|t| t.attribute("class")) == Some("low");
+
+            if !is_night || days.is_empty() {
+                days.push(ForecastDay::default());
+            }
+            let day = days.last_mut().unwrap();
+
+            let value = temperature.and_then(|t| t.text()).and_then(|t| t.trim().parse::<f64>().ok());
+            if is_night {
+                day.low = value;
+            } else {
+                day.high = value;
+            }
+
+            let icon = number(period, &["abbreviatedForecast", "iconCode"]).map(|code| code as i32);
+            if day.icon.is_none() || !is_night {
+                day.icon = icon.or(day.icon);
+            }
+            day.precipitation_chance = day
+                .precipitation_chance
+                .max(number(period, &["abbreviatedForecast", "pop"]).unwrap_or_default() as i32);
+            day.precipitation_sum += child(period, "precipitation")
+                .into_iter()
+                .flat_map(|p| p.children().filter(|n| n.has_tag_name("accumulation")))
+                .filter_map(|a| number(a, &["amount"]))
+                .sum::<f64>();
+            day.uv_index = day.uv_index.max(number(period, &["uv", "index"]).unwrap_or_default());
+            if let Some(summary) = text(period, &["textSummary"]) {
+                let name = period.children().find(|n| n.has_tag_name("period"));
+                let name = name.and_then(|n| n.attribute("textForecastName")).unwrap_or_default();
+                day.text.push(format!("{name}: {summary}"));
+            }
+        }
+
+        let rise_set = child(root, "riseSet");
+
+        for (i, day) in days.into_iter().take(7).enumerate() {
+            let date = issued.date() + Duration::days(i as i64);
+            let day_str = date.format("%Y-%m-%d").to_string();
+            let hourly_temps: Vec<f64> = weather
+                .hourly
+                .time
+                .iter()
+                .zip(&weather.hourly.temperature_2m)
+                .filter(|(time, _)| time.starts_with(&day_str))
+                .map(|(_, temp)| *temp)
+                .collect();
+
+            let high = day
+                .high
+                .or_else(|| {
+                    hourly_temps
+                        .iter()
+                        .copied()
+                        .reduce(f64::max)
+                        .map(|t| t.max(weather.current.temperature_2m))
+                })
+                .unwrap_or(weather.current.temperature_2m);
+            let low = day.low.or_else(|| hourly_temps.iter().copied().reduce(f64::min)).unwrap_or(high);
+
+            let (sunrise, sunset) = match (i, rise_set) {
+                (0, Some(rs)) => match (local_timestamp(rs, "sunrise"), local_timestamp(rs, "sunset")) {
+                    (Some((rise, _)), Some((set, _))) => {
+                        (rise.format("%Y-%m-%dT%H:%M").to_string(), set.format("%Y-%m-%dT%H:%M").to_string())
+                    },
+                    _ => solar::sunrise_sunset(lat, lon, date, utc_offset_seconds),
+                },
+                _ => solar::sunrise_sunset(lat, lon, date, utc_offset_seconds),
+            };
+
+            weather.daily.time.push(day_str);
+            weather.daily.weather_code.push(mappings::eccc_icon2meteo_code(day.icon.unwrap_or(-1)));
+            weather.daily.sunrise.push(sunrise);
+            weather.daily.sunset.push(sunset);
+            weather.daily.uv_index_max.push(day.uv_index);
+            weather.daily.precipitation_sum.push(day.precipitation_sum);
+            weather.daily.precipitation_hours.push(0.0);
+            weather.daily.precipitation_probability_max.push(day.precipitation_chance);
+            weather.daily.temperature_2m_max.push(high.max(low));
+            weather.daily.temperature_2m_min.push(low.min(high));
+            weather.daily.narrative.push(day.text.join(" "));
+        }
+
+        if weather.daily.time.is_empty() {
+            return Err(Error::Parse("ECCC citypage contains no forecast periods".to_string()).into());
+        }
+
+        Ok(weather)
+    }
+
+    /// Builds current conditions from the latest observation, or the first hourly forecast.
+    fn current(conditions: Option<Node>, hourly: &Hourly) -> Current {
+        let observed = conditions.and_then(|c| number(c, &["temperature"]).map(|t| (c, t)));
+
+        let Some((conditions, temperature)) = observed else {
+            return Current {
+                apparent_temperature: hourly.temperature_2m.first().copied().unwrap_or_default(),
+                interval: 3600,
+                temperature_2m: hourly.temperature_2m.first().copied().unwrap_or_default(),
+                weather_code: hourly.weather_code.first().copied().unwrap_or(-1),
+                ..Default::default()
+            };
+        };
+
+        let humidity = number(conditions, &["relativeHumidity"]).unwrap_or_default();
+        let wind_speed = number(conditions, &["wind", "speed"]).unwrap_or_default();
+        let feels_like = number(conditions, &["humidex"])
+            .or_else(|| number(conditions, &["windChill"]))
+            .unwrap_or_else(|| conversions::apparent_temperature(temperature, humidity, wind_speed));
+
+        Current {
+            apparent_temperature: feels_like,
+            interval: 3600,
+            precipitation: 0.0,
+            pressure_msl: number(conditions, &["pressure"]).map(|kpa| kpa * 10.0).unwrap_or_default(),
+            relative_humidity_2m: humidity.round() as i32,
+            temperature_2m: temperature,
+            weather_code: mappings::eccc_icon2meteo_code(
+                number(conditions, &["iconCode"]).unwrap_or(-1.0) as i32
+            ),
+            wind_direction_10m: number(conditions, &["wind", "bearing"]).unwrap_or_default().round() as i32,
+            wind_speed_10m: wind_speed,
+            wind_gusts_10m: number(conditions, &["wind", "gust"]).unwrap_or(wind_speed),
+        }
+    }
+}
+
+/// Returns the first child element with the given tag name.
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+/// Returns the trimmed, non-empty text of the element at the given child path.
+fn text<'a>(node: Node<'a, '_>, path: &[&str]) -> Option<&'a str> {
+    path.iter()
+        .try_fold(node, |n, name| child(n, name))
+        .and_then(|n| n.text())
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+/// Returns the numeric value of the element at the given child path.
+fn number(node: Node, path: &[&str]) -> Option<f64> {
+    text(node, path).and_then(|t| t.parse().ok())
+}
+
+/// Finds a named `dateTime` element in local time and returns it with its UTC offset in seconds.
+///
+/// ECCC writes every timestamp twice, once in UTC and once in the site's local zone.
+fn local_timestamp(node: Node, name: &str) -> Option<(NaiveDateTime, i32)> {
+    let date_time = node.children().find(|n| {
+        n.has_tag_name("dateTime") && n.attribute("name") == Some(name) && n.attribute("zone") != Some("UTC")
+    })?;
+    let offset_hours: f64 = date_time.attribute("UTCOffset")?.parse().ok()?;
+    let timestamp = NaiveDateTime::parse_from_str(text(date_time, &["timeStamp"])?, "%Y%m%d%H%M%S").ok()?;
+
+    Some((timestamp, (offset_hours * 3600.0) as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_server;
+
+    const SITE_LIST: &str = include_str!("../../fixtures/eccc/site_list_en.csv");
+    const CITYPAGE: &str = include_str!("../../fixtures/eccc/s0000045_e.xml");
+
+    #[test]
+    fn test_nearest_site() {
+        let site = CitySite::nearest(SITE_LIST, 53.5, -113.5).unwrap();
+        assert_eq!(site.code, "s0000045");
+        assert_eq!(site.province, "AB");
+
+        let site = CitySite::nearest(SITE_LIST, 51.0, -114.0).unwrap();
+        assert_eq!(site.name, "Calgary");
+    }
+
+    #[test]
+    fn test_parse_citypage() {
+        let weather = Eccc::parse(CITYPAGE, 53.55, -113.49).unwrap();
+
+        assert_eq!(weather.utc_offset_seconds, -6 * 3600);
+        assert_eq!(weather.current.temperature_2m, 17.6);
+        assert_eq!(weather.current.pressure_msl, 1007.0);
+        assert_eq!(weather.current.wind_gusts_10m, 26.0);
+        assert_eq!(weather.current.weather_code, 3);
+
+        assert_eq!(weather.hourly.time[0], "2025-06-21T16:00");
+        assert_eq!(weather.hourly.precipitation_probability[1], 40);
+
+        // "Tonight" becomes today with only a low, Sunday pairs day and night,
+        // and Monday has no night period yet
+        assert_eq!(weather.daily.time, vec!["2025-06-21", "2025-06-22", "2025-06-23"]);
+        assert_eq!(weather.daily.temperature_2m_min, vec![9.0, 11.0, 19.0]);
+        assert_eq!(weather.daily.temperature_2m_max, vec![17.6, 22.0, 19.0]);
+        assert_eq!(weather.daily.weather_code, vec![80, 2, 95]);
+        assert_eq!(weather.daily.precipitation_sum, vec![2.0, 0.0, 10.0]);
+        assert_eq!(weather.daily.precipitation_probability_max, vec![40, 0, 70]);
+        assert_eq!(weather.daily.sunrise[0], "2025-06-21T05:03");
+        assert!(weather.daily.narrative[1].starts_with("Sunday: A mix of sun and cloud."));
+        assert_eq!(
+            weather.narrative.as_deref(),
+            Some("Cloudy. 40 percent chance of showers this evening. Low 9.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_local_server() {
+        let base_url = test_server::serve(vec![
+            ("/docs/site_list_en.csv", SITE_LIST.to_string()),
+            ("/xml/AB/s0000045_e.xml", CITYPAGE.to_string()),
+        ]);
+
+        let site = CitySite::fetch(&base_url, 53.5, -113.5).await.unwrap();
+        let weather = Eccc::fetch_site(&base_url, &site, 53.5, -113.5).await.unwrap();
+
+        assert_eq!(weather.daily.time.len(), 3);
+        assert_eq!(weather.current.relative_humidity_2m, 57);
+    }
+}
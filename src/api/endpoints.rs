@@ -0,0 +1,221 @@
+use crate::error::Error;
+use crate::settings::EndpointsConfig;
+use crate::utils;
+
+use anyhow::{Context, Result};
+use url::Url;
+
+/// The remote services data is fetched from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endpoint {
+    Forecast,
+    Archive,
+    AirQuality,
+    Geocoding,
+    ReverseGeocoding,
+    MetNo,
+    Nws,
+    Eccc,
+    Meteoalarm,
+    IpInfo,
+    IpApi,
+    GeoNames,
+}
+
+impl Endpoint {
+    /// Returns the public URL of the service, used unless another is configured.
+    ///
+    /// # Returns
+    ///
+    /// Returns the default base URL, e.g. "https://api.open-meteo.com/v1/forecast".
+    pub fn default_url(&self) -> &'static str {
+        match self {
+            Endpoint::Forecast => "https://api.open-meteo.com/v1/forecast",
+            Endpoint::Archive => "https://archive-api.open-meteo.com/v1/archive",
+            Endpoint::AirQuality => "https://air-quality-api.open-meteo.com/v1/air-quality",
+            Endpoint::Geocoding => "https://geocoding-api.open-meteo.com/v1/search",
+            Endpoint::ReverseGeocoding => "https://nominatim.openstreetmap.org/reverse",
+            Endpoint::MetNo => "https://api.met.no/weatherapi/locationforecast/2.0/complete",
+            Endpoint::Nws => "https://api.weather.gov",
+            Endpoint::Eccc => "https://dd.weather.gc.ca/citypage_weather",
+            Endpoint::Meteoalarm => "https://feeds.meteoalarm.org/feeds",
+            Endpoint::IpInfo => "https://ipinfo.io/json",
+            // ip-api only offers HTTPS on paid plans
+            Endpoint::IpApi => "http://ip-api.com/json",
+            Endpoint::GeoNames => "https://download.geonames.org/export/dump",
+        }
+    }
+
+    /// Returns the configured URL of the service, if any.
+    fn configured<'a>(&self, config: &'a EndpointsConfig) -> Option<&'a String> {
+        match self {
+            Endpoint::Forecast => config.forecast.as_ref(),
+            Endpoint::Archive => config.archive.as_ref(),
+            Endpoint::AirQuality => config.air_quality.as_ref(),
+            Endpoint::Geocoding => config.geocoding.as_ref(),
+            Endpoint::ReverseGeocoding => config.reverse_geocoding.as_ref(),
+            Endpoint::MetNo => config.met_no.as_ref(),
+            Endpoint::Nws => config.nws.as_ref(),
+            Endpoint::Eccc => config.eccc.as_ref(),
+            Endpoint::Meteoalarm => config.meteoalarm.as_ref(),
+            Endpoint::IpInfo => config.ipinfo.as_ref(),
+            Endpoint::IpApi => config.ip_api.as_ref(),
+            Endpoint::GeoNames => config.geonames.as_ref(),
+        }
+    }
+
+    /// Checks whether the service is an Open-Meteo API, which takes the `apikey` parameter.
+    fn is_open_meteo(&self) -> bool {
+        matches!(self, Endpoint::Forecast | Endpoint::Archive | Endpoint::AirQuality | Endpoint::Geocoding)
+    }
+
+    /// Returns every endpoint, for validating the configuration.
+    fn all() -> [Endpoint; 12] {
+        [
+            Endpoint::Forecast,
+            Endpoint::Archive,
+            Endpoint::AirQuality,
+            Endpoint::Geocoding,
+            Endpoint::ReverseGeocoding,
+            Endpoint::MetNo,
+            Endpoint::Nws,
+            Endpoint::Eccc,
+            Endpoint::Meteoalarm,
+            Endpoint::IpInfo,
+            Endpoint::IpApi,
+            Endpoint::GeoNames,
+        ]
+    }
+}
+
+/// Checks the user's endpoint configuration, e.g. for a self-hosted Open-Meteo.
+///
+/// # Arguments
+///
+/// * `config` - Endpoint settings from the configuration file
+///
+/// # Errors
+///
+/// This function will return an error if a configured endpoint is not a valid URL.
+pub fn validate(config: &EndpointsConfig) -> Result<()> {
+    for endpoint in Endpoint::all() {
+        if let Some(url) = endpoint.configured(config) {
+            Url::parse(url).with_context(|| Error::Config(format!("Invalid endpoint URL '{url}'")))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the base URL of a service, as configured or its public default.
+///
+/// # Arguments
+///
+/// * `config` - Endpoint settings from the configuration file
+/// * `endpoint` - The service to look up
+///
+/// # Returns
+///
+/// Returns the base URL without a trailing slash.
+pub fn url(config: &EndpointsConfig, endpoint: Endpoint) -> String {
+    endpoint.configured(config).map_or(endpoint.default_url(), |url| url.trim_end_matches('/')).to_string()
+}
+
+/// Builds a request URL for a service with query parameters.
+///
+/// The configured API key is added to requests to Open-Meteo, for its commercial
+/// `customer-` endpoints.
+///
+/// # Arguments
+///
+/// * `config` - Endpoint settings from the configuration file
+/// * `endpoint` - The service to send the request to
+/// * `params` - Vector of (key, value) tuples to add as query parameters
+///
+/// # Returns
+///
+/// Returns a complete URL string with query parameters appended.
+pub fn builder<'a>(
+    config: &'a EndpointsConfig,
+    endpoint: Endpoint,
+    mut params: Vec<(&'a str, &'a str)>,
+) -> String {
+    if let Some(api_key) = config.api_key.as_deref().filter(|_| endpoint.is_open_meteo()) {
+        params.push(("apikey", api_key));
+    }
+
+    utils::urls::builder(&url(config, endpoint), params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::openmeteo::OpenMeteo;
+    use crate::api::weather::{Weather, WeatherProvider};
+    use crate::error::ErrorReport;
+    use crate::utils::test_server;
+    use crate::Settings;
+
+    #[test]
+    fn test_builder() {
+        let config = EndpointsConfig::default();
+        assert_eq!(
+            builder(&config, Endpoint::Forecast, vec![("latitude", "51.05")]),
+            "https://api.open-meteo.com/v1/forecast?latitude=51.05"
+        );
+
+        let config = EndpointsConfig {
+            forecast: Some("https://customer-api.open-meteo.com/v1/forecast".to_string()),
+            nws: Some("http://127.0.0.1:8080/nws/".to_string()),
+            api_key: Some("secret".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            builder(&config, Endpoint::Forecast, vec![("latitude", "51.05")]),
+            "https://customer-api.open-meteo.com/v1/forecast?latitude=51.05&apikey=secret"
+        );
+        assert_eq!(
+            builder(&config, Endpoint::MetNo, vec![("lat", "60")]),
+            format!("{}?lat=60", Endpoint::MetNo.default_url())
+        );
+        assert_eq!(url(&config, Endpoint::Nws), "http://127.0.0.1:8080/nws");
+    }
+
+    #[tokio::test]
+    async fn test_configured_endpoint() {
+        let forecast = Weather { timezone: "America/Edmonton".to_string(), ..Default::default() };
+        let base_url = test_server::serve(vec![("/v1/forecast", serde_json::to_string(&forecast).unwrap())]);
+        let endpoints =
+            EndpointsConfig { forecast: Some(format!("{base_url}/v1/forecast/")), ..Default::default() };
+
+        validate(&endpoints).unwrap();
+        assert_eq!(url(&endpoints, Endpoint::Forecast), format!("{base_url}/v1/forecast"));
+
+        let settings = Settings { endpoints, ..Default::default() };
+        let weather = OpenMeteo::fetch(51.05, -114.07, "", &settings).await.unwrap();
+        assert_eq!(weather.timezone, "America/Edmonton");
+
+        assert!(validate(&EndpointsConfig { forecast: Some("not a url".to_string()), ..Default::default() })
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_api_key_redacted() {
+        let base_url =
+            test_server::serve_responses(vec![("/v1/forecast", test_server::Response::status(403))]);
+        let endpoints = EndpointsConfig {
+            forecast: Some(format!("{base_url}/v1/forecast")),
+            api_key: Some("secret".to_string()),
+            ..Default::default()
+        };
+
+        let error = OpenMeteo::fetch(51.05, -114.07, "", &Settings { endpoints, ..Default::default() })
+            .await
+            .unwrap_err();
+        let report = ErrorReport::new(&error);
+
+        assert!(matches!(Error::find(&error), Some(Error::HttpStatus { status: 403, .. })));
+        assert!(report.message.contains("apikey=***"));
+        assert!(!report.message.contains("secret"));
+    }
+}
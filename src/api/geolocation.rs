@@ -1,34 +1,260 @@
 use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
 use crate::api::location::*;
+use crate::error::Error;
+use crate::settings::EndpointsConfig;
 use crate::utils;
+use crate::Settings;
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
+/// A city's population must be this many times the next match's for the city to be
+/// picked without asking, e.g. Portland, Oregon over Portland, Maine.
+const DOMINANT_POPULATION_RATIO: u64 = 5;
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct GeoLocation {
+    #[serde(default)]
     pub results: Vec<Results>,
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
 pub struct Results {
     pub name: String,
     pub country_code: String,
     pub latitude: f64,
     pub longitude: f64,
+    pub admin1: Option<String>,
+    pub admin2: Option<String>,
+    pub population: Option<u64>,
+    pub timezone: Option<String>,
+}
+
+/// Error returned when a location name matches several places and no region was given.
+///
+/// Each candidate comes with a location string specific enough to pick it, so callers
+/// can ask the user to choose one and look it up again.
+#[derive(Debug, Clone)]
+pub struct AmbiguousLocation {
+    pub query: String,
+    pub candidates: Vec<(String, Results)>,
+}
+
+impl std::fmt::Display for AmbiguousLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' matches several places, use 'City, Region, CountryCode' to pick one:", self.query)?;
+        for (location, result) in &self.candidates {
+            write!(f, "\n  {}", result.describe(location))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AmbiguousLocation {}
+
+impl From<Results> for LocationData {
+    fn from(result: Results) -> Self {
+        let mut location_data = LocationData {
+            location: format!("{}, {}", result.name, result.country_code),
+            city: result.name,
+            country_code: result.country_code,
+            latitude: result.latitude,
+            longitude: result.longitude,
+            created_at: utils::get_now(),
+            timezone: result.timezone.unwrap_or_default(),
+            ..Default::default()
+        };
+
+        // Normalize the location data for consistent formatting
+        location_data.normalize();
+
+        location_data
+    }
+}
+
+impl Results {
+    /// Describes a match for a list of candidates, with its county and population.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The location string that selects this match
+    ///
+    /// # Returns
+    ///
+    /// Returns a description such as "Springfield, Illinois, US (Sangamon County, pop. 114394)".
+    pub fn describe(&self, location: &str) -> String {
+        let details: Vec<String> = [
+            self.admin2.clone().filter(|admin2| !location.contains(admin2.as_str())),
+            self.population.map(|population| format!("pop. {population}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        if details.is_empty() {
+            location.to_string()
+        } else {
+            format!("{location} ({})", details.join(", "))
+        }
+    }
+
+    /// Checks whether a region given by the user names this match's state, province or county.
+    fn in_region(&self, region: &str) -> bool {
+        [&self.admin1, &self.admin2].into_iter().flatten().any(|admin| admin.eq_ignore_ascii_case(region))
+    }
+}
+
+impl GeoLocation {
+    /// Looks up a place by name, narrowed down by region and country.
+    ///
+    /// Only exact name matches are considered when there are any, most populous first.
+    /// Without a region, several matches of similar size are reported as ambiguous
+    /// rather than picking one at random.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The city or location name to search for
+    /// * `region` - Optional state, province or county to narrow down the search
+    /// * `country_code` - The country code to narrow down the search
+    /// * `endpoints` - Endpoint settings from the configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns `LocationData` containing the location details and coordinates.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The API request fails
+    /// - The JSON response cannot be parsed
+    /// - No results are found for the specified location
+    /// - Several places match and no region was given, as an `AmbiguousLocation`
+    pub async fn resolve(
+        name: &str,
+        region: Option<&str>,
+        country_code: &str,
+        endpoints: &EndpointsConfig,
+    ) -> Result<LocationData> {
+        let params = vec![
+            ("name", name),
+            ("countryCode", country_code),
+            ("count", "10"),
+            ("language", "en"),
+            ("format", "json"),
+        ];
+        let api_url = endpoints::builder(endpoints, Endpoint::Geocoding, params);
+
+        let body = client::get_with_retry(&api_url, 2)
+            .await
+            .with_context(|| format!("Unable to fetch location data for {name}, {country_code}"))?;
+
+        let loc: GeoLocation = serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Failed to parse location response JSON".to_string()))?;
+
+        let query = match region {
+            Some(region) => format!("{name}, {region}, {country_code}"),
+            None => format!("{name}, {country_code}"),
+        };
+        Self::select(loc.results, name, region, &query).map(LocationData::from)
+    }
+
+    /// Picks the place the user meant from the geocoding results.
+    ///
+    /// # Arguments
+    ///
+    /// * `results` - Geocoding results, in the API's order
+    /// * `name` - The name that was searched for
+    /// * `region` - Optional state, province or county given by the user
+    /// * `query` - The location as the user gave it, for error messages
+    ///
+    /// # Returns
+    ///
+    /// Returns the chosen result, or an error if there is none or the choice is ambiguous.
+    pub(crate) fn select(
+        results: Vec<Results>,
+        name: &str,
+        region: Option<&str>,
+        query: &str,
+    ) -> Result<Results> {
+        let has_exact = results.iter().any(|result| result.name.eq_ignore_ascii_case(name));
+        let mut candidates: Vec<Results> = results
+            .into_iter()
+            .filter(|result| !has_exact || result.name.eq_ignore_ascii_case(name))
+            .filter(|result| region.map_or(true, |region| result.in_region(region)))
+            .collect();
+
+        if candidates.is_empty() {
+            return Err(Error::LocationNotFound(format!("No location results found for {query}")).into());
+        }
+
+        // Fuzzy matches keep the API's ranking, exact matches are ranked by size
+        if has_exact {
+            candidates.sort_by_key(|result| std::cmp::Reverse(result.population.unwrap_or_default()));
+        }
+
+        let dominant = match (candidates.first(), candidates.get(1)) {
+            (Some(_), None) => true,
+            (Some(first), Some(second)) => {
+                first.population.unwrap_or_default()
+                    >= second.population.unwrap_or_default().saturating_mul(DOMINANT_POPULATION_RATIO).max(1)
+            },
+            _ => false,
+        };
+
+        if region.is_some() || !has_exact || dominant {
+            return Ok(candidates.swap_remove(0));
+        }
+
+        Err(AmbiguousLocation { query: query.to_string(), candidates: Self::label_candidates(candidates) }
+            .into())
+    }
+
+    /// Gives each candidate a "City, Region, CountryCode" location string that selects it.
+    ///
+    /// The state or province is used as the region, or the county where two candidates
+    /// share a state.
+    fn label_candidates(candidates: Vec<Results>) -> Vec<(String, Results)> {
+        let shared_admin1 = |admin1: &Option<String>| {
+            candidates.iter().filter(|other| admin1.is_some() && other.admin1 == *admin1).count() > 1
+        };
+        let regions: Vec<Option<String>> = candidates
+            .iter()
+            .map(|result| {
+                if shared_admin1(&result.admin1) {
+                    result.admin2.clone().or_else(|| result.admin1.clone())
+                } else {
+                    result.admin1.clone().or_else(|| result.admin2.clone())
+                }
+            })
+            .collect();
+
+        candidates
+            .into_iter()
+            .zip(regions)
+            .map(|(result, region)| {
+                let location = match region {
+                    Some(region) => format!("{}, {region}, {}", result.name, result.country_code),
+                    None => format!("{}, {}", result.name, result.country_code),
+                };
+                (location, result)
+            })
+            .collect()
+    }
 }
 
 impl Location for GeoLocation {
     /// Fetches location data using the Open-Meteo geocoding API.
     ///
-    /// Searches for a location by name and country code, returning the first result.
-    /// Uses the Open-Meteo geocoding API to find coordinates for the specified location.
+    /// Searches for a location by name and country code. See `GeoLocation::resolve`
+    /// for how a result is picked when several places share the name.
     ///
     /// # Arguments
     ///
     /// * `n` - The city or location name to search for
     /// * `c` - The country code to narrow down the search
+    /// * `s` - Settings with the endpoints
     ///
     /// # Returns
     ///
@@ -40,36 +266,63 @@ impl Location for GeoLocation {
     /// - The API request fails
     /// - The JSON response cannot be parsed
     /// - No results are found for the specified location
-    fn fetch(n: &str, c: &str) -> Result<LocationData> {
-        let base_url = "https://geocoding-api.open-meteo.com/v1/search";
-        let params =
-            vec![("name", n), ("countryCode", c), ("count", "10"), ("language", "en"), ("format", "json")];
-        let api_url = utils::urls::builder(base_url, params);
-
-        let body = client::get_with_retry(&api_url, 2)
-            .with_context(|| format!("Unable to fetch location data for {n}, {c}"))?;
+    /// - Several places match, as an `AmbiguousLocation`
+    async fn fetch(n: &str, c: &str, s: &Settings) -> Result<LocationData> {
+        Self::resolve(n, None, c, &s.endpoints).await
+    }
+}
 
-        let loc: GeoLocation =
-            serde_json::from_str(&body).with_context(|| "Failed to parse location response JSON")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if loc.results.is_empty() {
-            return Err(anyhow::anyhow!("No location results found for {}, {}", n, c));
+    fn place(name: &str, admin1: &str, admin2: &str, population: Option<u64>) -> Results {
+        Results {
+            name: name.to_string(),
+            country_code: "US".to_string(),
+            latitude: 0.0,
+            longitude: 0.0,
+            admin1: Some(admin1.to_string()),
+            admin2: Some(admin2.to_string()),
+            population,
+            timezone: None,
         }
+    }
 
-        let result = &loc.results[0];
+    #[test]
+    fn test_select() {
+        let springfields = vec![
+            place("Springfield", "Illinois", "Sangamon", Some(114394)),
+            place("Springfield", "Missouri", "Greene", Some(169176)),
+            place("Springfield", "Oregon", "Lane", Some(62256)),
+            place("Springfield", "Oregon", "Other", None),
+            place("Springfield Gardens", "New York", "Queens", Some(30000)),
+        ];
 
-        let mut location_data = LocationData {
-            city: result.name.to_owned(),
-            country_code: result.country_code.to_owned(),
-            latitude: result.latitude,
-            longitude: result.longitude,
-            location: format!("{}, {}", result.name, result.country_code),
-            created_at: utils::get_now(),
-        };
+        let error =
+            GeoLocation::select(springfields.clone(), "springfield", None, "Springfield, US").unwrap_err();
+        let ambiguous = error.downcast_ref::<AmbiguousLocation>().unwrap();
+        let locations: Vec<&str> =
+            ambiguous.candidates.iter().map(|(location, _)| location.as_str()).collect();
+        assert_eq!(
+            locations,
+            vec![
+                "Springfield, Missouri, US",
+                "Springfield, Illinois, US",
+                "Springfield, Lane, US",
+                "Springfield, Other, US"
+            ]
+        );
 
-        // Normalize the location data for consistent formatting
-        location_data.normalize();
+        let result = GeoLocation::select(springfields.clone(), "Springfield", Some("illinois"), "").unwrap();
+        assert_eq!(result.admin2.as_deref(), Some("Sangamon"));
+        assert!(GeoLocation::select(springfields, "Springfield", Some("Texas"), "").is_err());
 
-        Ok(location_data)
+        let portlands = vec![
+            place("Portland", "Maine", "Cumberland", Some(66215)),
+            place("Portland", "Oregon", "Multnomah", Some(652503)),
+        ];
+        let result = GeoLocation::select(portlands, "Portland", None, "").unwrap();
+        assert_eq!(result.admin1.as_deref(), Some("Oregon"));
     }
 }
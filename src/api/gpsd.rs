@@ -0,0 +1,165 @@
+use crate::api::location::*;
+use crate::error::Error;
+use crate::Settings;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:2947";
+const DEFAULT_TIMEOUT_SECONDS: u64 = 5;
+
+/// Location from a GPS receiver, read through a local gpsd daemon.
+pub struct Gpsd;
+
+/// A gpsd report. Only time-position-velocity (TPV) reports carry a position.
+#[derive(Deserialize, Debug)]
+struct Report {
+    class: String,
+    #[serde(default)]
+    mode: i32,
+    lat: Option<f64>,
+    lon: Option<f64>,
+}
+
+impl Gpsd {
+    /// Finds the current location from gpsd and names it by reverse geocoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Settings with the gpsd address, how long to wait for a fix and how to name the place
+    ///
+    /// # Returns
+    ///
+    /// Returns location data at the receiver's exact position.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if gpsd cannot be reached or has no fix in time.
+    pub async fn locate(s: &Settings) -> Result<LocationData> {
+        let gps = &s.gps;
+        let address = gps.address.as_deref().unwrap_or(DEFAULT_ADDRESS);
+        let timeout = Duration::from_secs(gps.timeout.unwrap_or(DEFAULT_TIMEOUT_SECONDS));
+
+        let (latitude, longitude) = Self::fix(address, timeout).await?;
+
+        let location_data = LocationData::from_coordinates(latitude, longitude, None, s).await;
+
+        Ok(LocationData { source: "gps".to_string(), ..location_data })
+    }
+
+    /// Waits for a 2D or 3D position fix from gpsd.
+    ///
+    /// Speaks the gpsd JSON protocol: enables watching, then reads reports until a
+    /// TPV report with a fix arrives.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The gpsd host and port, e.g. "127.0.0.1:2947"
+    /// * `timeout` - How long to wait in total for a fix
+    ///
+    /// # Returns
+    ///
+    /// Returns the latitude and longitude of the fix.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - gpsd cannot be reached
+    /// - No fix arrives before the timeout
+    pub async fn fix(address: &str, timeout: Duration) -> Result<(f64, f64)> {
+        tokio::time::timeout(timeout, Self::watch(address)).await.unwrap_or_else(|_| {
+            Err(Error::Network(format!("No GPS fix from gpsd within {} seconds", timeout.as_secs())).into())
+        })
+    }
+
+    /// Enables watching and reads reports until a TPV report with a fix arrives.
+    async fn watch(address: &str) -> Result<(f64, f64)> {
+        let mut stream = TcpStream::connect(address)
+            .await
+            .with_context(|| Error::Network(format!("Unable to connect to gpsd at {address}")))?;
+
+        stream
+            .write_all(b"?WATCH={\"enable\":true,\"json\":true};\n")
+            .await
+            .with_context(|| "Unable to send the watch command to gpsd")?;
+
+        let mut lines = BufReader::new(stream).lines();
+        while let Some(line) = lines.next_line().await.with_context(|| "Unable to read from gpsd")? {
+            // Other report classes (VERSION, DEVICES, SKY, ...) are skipped
+            if let Ok(Report { class, mode, lat: Some(lat), lon: Some(lon) }) = serde_json::from_str(&line) {
+                if class == "TPV" && mode >= 2 {
+                    return Ok((lat, lon));
+                }
+            }
+        }
+
+        Err(Error::Network("gpsd closed the connection before a fix".to_string()).into())
+    }
+}
+
+impl Location for Gpsd {
+    /// Fetches location data from a GPS receiver through gpsd on its default port.
+    ///
+    /// # Arguments
+    ///
+    /// * `_` - Unused parameter (name), kept for trait compatibility
+    /// * `_` - Unused parameter (country_code), kept for trait compatibility
+    /// * `s` - Settings with the gpsd address and how to name the place
+    ///
+    /// # Returns
+    ///
+    /// Returns `LocationData` at the receiver's position.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if gpsd cannot be reached or has no fix in time.
+    async fn fetch(_: &str, _: &str, s: &Settings) -> Result<LocationData> {
+        Self::locate(s).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Starts a fake gpsd that sends the given reports to the first client.
+    fn fake_gpsd(reports: &'static [&'static str]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut watch = String::new();
+                let _ = std::io::BufReader::new(&stream).read_line(&mut watch);
+                for report in reports {
+                    let _ = writeln!(stream, "{report}");
+                }
+                // Keep the connection open so the client has to rely on its timeout
+                thread::sleep(Duration::from_secs(2));
+            }
+        });
+
+        address
+    }
+
+    #[tokio::test]
+    async fn test_fix() {
+        let address = fake_gpsd(&[
+            r#"{"class":"VERSION","release":"3.25","proto_major":3,"proto_minor":15}"#,
+            r#"{"class":"TPV","device":"/dev/ttyUSB0","mode":1}"#,
+            r#"{"class":"SKY","device":"/dev/ttyUSB0","satellites":[]}"#,
+            r#"{"class":"TPV","device":"/dev/ttyUSB0","mode":3,"lat":51.0447,"lon":-114.0719,"alt":1045.0}"#,
+        ]);
+        assert_eq!(Gpsd::fix(&address, Duration::from_secs(1)).await.unwrap(), (51.0447, -114.0719));
+
+        let address = fake_gpsd(&[r#"{"class":"TPV","device":"/dev/ttyUSB0","mode":1}"#]);
+        let error = Gpsd::fix(&address, Duration::from_millis(200)).await.unwrap_err();
+        assert!(error.to_string().starts_with("No GPS fix"));
+    }
+}
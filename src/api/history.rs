@@ -0,0 +1,316 @@
+use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
+use crate::api::weather::*;
+use crate::error::Error;
+use crate::settings::EndpointsConfig;
+use crate::utils;
+use crate::utils::cache::{self, CacheHeader};
+use crate::Settings;
+
+use anyhow::{Context, Result};
+use chrono::{Duration, Local, NaiveDate};
+use serde::Deserialize;
+
+/// Reanalysis data in the archive lags real time by a few days.
+const ARCHIVE_DELAY_DAYS: i64 = 5;
+
+/// Response from the Open-Meteo historical weather (archive) API.
+///
+/// Values can be `null` where the reanalysis has gaps, so every series is optional.
+#[derive(Deserialize, Debug)]
+pub struct History {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub timezone: String,
+    pub utc_offset_seconds: i32,
+    pub daily: HistoryDaily,
+    pub hourly: HistoryHourly,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HistoryDaily {
+    pub time: Vec<String>,
+    pub weather_code: Vec<Option<i32>>,
+    pub sunrise: Vec<Option<String>>,
+    pub sunset: Vec<Option<String>>,
+    pub precipitation_sum: Vec<Option<f64>>,
+    pub precipitation_hours: Vec<Option<f64>>,
+    pub temperature_2m_max: Vec<Option<f64>>,
+    pub temperature_2m_min: Vec<Option<f64>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HistoryHourly {
+    pub time: Vec<String>,
+    pub temperature_2m: Vec<Option<f64>>,
+    pub relative_humidity_2m: Vec<Option<i32>>,
+    pub apparent_temperature: Vec<Option<f64>>,
+    pub precipitation: Vec<Option<f64>>,
+    pub weather_code: Vec<Option<i32>>,
+    pub pressure_msl: Vec<Option<f64>>,
+    pub wind_speed_10m: Vec<Option<f64>>,
+    pub wind_direction_10m: Vec<Option<f64>>,
+    pub wind_gusts_10m: Vec<Option<f64>>,
+}
+
+impl History {
+    /// Retrieves past weather for a date range, using cached data if available.
+    ///
+    /// Archive data never changes once published, so responses are cached permanently,
+    /// keyed on the coordinates and the date range.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate for the weather location
+    /// * `lon` - Longitude coordinate for the weather location
+    /// * `start` - First day of the range
+    /// * `end` - Last day of the range (inclusive)
+    /// * `s` - Settings with the endpoints and cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns the past weather mapped into the `Weather` model, where the days of the
+    /// range form the daily series and the conditions at noon on the first day stand in
+    /// for the current conditions.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The range ends before it starts, or includes days not yet in the archive
+    /// - The API request fails
+    /// - The response cannot be parsed as JSON
+    pub async fn get_cached(
+        lat: f64,
+        lon: f64,
+        start: NaiveDate,
+        end: NaiveDate,
+        s: &Settings,
+    ) -> Result<Weather> {
+        Self::validate_range(start, end)?;
+
+        let key = format!("{lat},{lon},{start},{end}");
+        let header = CacheHeader::new("history", 0, key.as_str()).with_provider("open-meteo");
+
+        let wd: Weather = cache::load(&s.cache.dir(), &header, &key).unwrap_or_default();
+        if wd.latitude == lat && wd.longitude == lon && wd.created_at > 0 {
+            return Ok(wd);
+        }
+
+        let mut data = Self::fetch(lat, lon, start, end, &s.endpoints).await?.into_weather()?;
+        data.provider = "open-meteo-archive".to_string();
+        data.latitude = lat;
+        data.longitude = lon;
+        data.created_at = utils::get_now();
+
+        match cache::save(&s.cache.dir(), &header, &key, &data) {
+            Ok(_) => {},
+            Err(e) => eprintln!("Unable to save historical weather data to disk: {e:#?}"),
+        }
+
+        Ok(data)
+    }
+
+    /// Checks that a date range is in order and already available in the archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - First day of the range
+    /// * `end` - Last day of the range (inclusive)
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the range can be fetched, or an error describing why not.
+    pub fn validate_range(start: NaiveDate, end: NaiveDate) -> Result<()> {
+        if end < start {
+            return Err(Error::Config(format!("The end date {end} is before the start date {start}")).into());
+        }
+
+        let latest = Local::now().date_naive() - Duration::days(ARCHIVE_DELAY_DAYS);
+        if end > latest {
+            return Err(Error::Config(format!(
+                "Historical weather is only available up to {latest}, use the forecast for recent days"
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Fetches past weather from the Open-Meteo archive API.
+    async fn fetch(
+        lat: f64,
+        lon: f64,
+        start: NaiveDate,
+        end: NaiveDate,
+        endpoints: &EndpointsConfig,
+    ) -> Result<Self> {
+        let hourly_fields = [
+            "temperature_2m",
+            "relative_humidity_2m",
+            "apparent_temperature",
+            "precipitation",
+            "weather_code",
+            "pressure_msl",
+            "wind_speed_10m",
+            "wind_direction_10m",
+            "wind_gusts_10m",
+        ]
+        .join(",");
+
+        let daily_fields = [
+            "weather_code",
+            "temperature_2m_max",
+            "temperature_2m_min",
+            "sunrise",
+            "sunset",
+            "precipitation_sum",
+            "precipitation_hours",
+        ]
+        .join(",");
+
+        let lat_str = lat.to_string();
+        let lon_str = lon.to_string();
+        let start_str = start.to_string();
+        let end_str = end.to_string();
+
+        let params: Vec<(&str, &str)> = vec![
+            ("latitude", lat_str.as_str()),
+            ("longitude", lon_str.as_str()),
+            ("start_date", start_str.as_str()),
+            ("end_date", end_str.as_str()),
+            ("timezone", "auto"),
+            ("daily", daily_fields.as_str()),
+            ("hourly", hourly_fields.as_str()),
+        ];
+
+        let api_url = endpoints::builder(endpoints, Endpoint::Archive, params);
+
+        let body = client::get_with_retry(&api_url, 2)
+            .await
+            .with_context(|| "Unable to fetch historical weather from the Open-Meteo archive API")?;
+
+        serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Unable to parse historical weather response JSON".to_string()))
+    }
+
+    /// Maps an archive response into the provider-neutral weather model.
+    ///
+    /// The archive has no precipitation probability or UV index, so those are zero,
+    /// and gaps in the reanalysis are filled with zeros.
+    ///
+    /// # Returns
+    ///
+    /// Returns weather data in metric units, or an error if the response has no days.
+    pub fn into_weather(self) -> Result<Weather> {
+        let daily = self.daily;
+        let hourly = self.hourly;
+
+        if daily.time.is_empty() {
+            return Err(
+                Error::Parse("The archive returned no days for the requested range".to_string()).into()
+            );
+        }
+
+        let days = daily.time.len();
+        let value = |series: &[Option<f64>], i: usize| series.get(i).copied().flatten().unwrap_or_default();
+        let code = |series: &[Option<i32>], i: usize| series.get(i).copied().flatten().unwrap_or(-1);
+        let time = |series: &[Option<String>], i: usize, date: &str| {
+            series.get(i).cloned().flatten().unwrap_or_else(|| format!("{date}T00:00"))
+        };
+
+        // Noon on the first day represents the day better than midnight
+        let noon = 12.min(hourly.time.len().saturating_sub(1));
+
+        Ok(Weather {
+            current: Current {
+                apparent_temperature: value(&hourly.apparent_temperature, noon),
+                interval: 3600,
+                precipitation: value(&hourly.precipitation, noon),
+                pressure_msl: value(&hourly.pressure_msl, noon),
+                relative_humidity_2m: hourly
+                    .relative_humidity_2m
+                    .get(noon)
+                    .copied()
+                    .flatten()
+                    .unwrap_or_default(),
+                temperature_2m: value(&hourly.temperature_2m, noon),
+                weather_code: code(&hourly.weather_code, noon),
+                wind_direction_10m: value(&hourly.wind_direction_10m, noon).round() as i32,
+                wind_speed_10m: value(&hourly.wind_speed_10m, noon),
+                wind_gusts_10m: value(&hourly.wind_gusts_10m, noon),
+            },
+            timezone: self.timezone,
+            utc_offset_seconds: self.utc_offset_seconds,
+            daily: Daily {
+                weather_code: (0..days).map(|i| code(&daily.weather_code, i)).collect(),
+                sunrise: daily
+                    .time
+                    .iter()
+                    .enumerate()
+                    .map(|(i, date)| time(&daily.sunrise, i, date))
+                    .collect(),
+                sunset: daily.time.iter().enumerate().map(|(i, date)| time(&daily.sunset, i, date)).collect(),
+                uv_index_max: vec![0.0; days],
+                precipitation_sum: (0..days).map(|i| value(&daily.precipitation_sum, i)).collect(),
+                precipitation_hours: (0..days).map(|i| value(&daily.precipitation_hours, i)).collect(),
+                precipitation_probability_max: vec![0; days],
+                temperature_2m_max: (0..days).map(|i| value(&daily.temperature_2m_max, i)).collect(),
+                temperature_2m_min: (0..days).map(|i| value(&daily.temperature_2m_min, i)).collect(),
+                narrative: Vec::new(),
+                time: daily.time,
+            },
+            hourly: Hourly {
+                temperature_2m: (0..hourly.time.len()).map(|i| value(&hourly.temperature_2m, i)).collect(),
+                precipitation_probability: vec![0; hourly.time.len()],
+                precipitation: (0..hourly.time.len()).map(|i| value(&hourly.precipitation, i)).collect(),
+                weather_code: (0..hourly.time.len()).map(|i| code(&hourly.weather_code, i)).collect(),
+                time: hourly.time,
+            },
+            latitude: self.latitude,
+            longitude: self.longitude,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"{
+        "latitude": 53.55, "longitude": -113.5, "timezone": "America/Edmonton", "utc_offset_seconds": -21600,
+        "daily": {
+            "time": ["2024-07-01"], "weather_code": [61], "sunrise": ["2024-07-01T05:04"],
+            "sunset": ["2024-07-01T22:08"], "precipitation_sum": [4.2], "precipitation_hours": [3.0],
+            "temperature_2m_max": [21.4], "temperature_2m_min": [null]
+        },
+        "hourly": {
+            "time": ["2024-07-01T11:00", "2024-07-01T12:00"],
+            "temperature_2m": [18.1, 19.5], "relative_humidity_2m": [70, 64], "apparent_temperature": [17.0, 18.2],
+            "precipitation": [0.0, 0.6], "weather_code": [3, 61], "pressure_msl": [1008.1, null],
+            "wind_speed_10m": [12.0, 14.4], "wind_direction_10m": [250.0, 262.0], "wind_gusts_10m": [25.2, 30.6]
+        }
+    }"#;
+
+    #[test]
+    fn test_into_weather() {
+        let history: History = serde_json::from_str(FIXTURE).unwrap();
+        let weather = history.into_weather().unwrap();
+
+        assert_eq!(weather.current.temperature_2m, 19.5);
+        assert_eq!(weather.current.weather_code, 61);
+        assert_eq!(weather.current.pressure_msl, 0.0);
+        assert_eq!(weather.daily.temperature_2m_min, vec![0.0]);
+        assert_eq!(weather.daily.precipitation_probability_max, vec![0]);
+        assert_eq!(weather.hourly.precipitation, vec![0.0, 0.6]);
+    }
+
+    #[test]
+    fn test_validate_range() {
+        let day = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+
+        assert!(History::validate_range(day, day).is_ok());
+        assert!(History::validate_range(day, day - Duration::days(1)).is_err());
+        assert!(History::validate_range(day, Local::now().date_naive()).is_err());
+    }
+}
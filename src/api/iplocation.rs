@@ -1,57 +1,171 @@
 use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
+use crate::api::gpsd::Gpsd;
 use crate::api::location::*;
+use crate::error::Error;
+use crate::settings::{EndpointsConfig, IpLocationConfig, IpLocationProvider};
 use crate::utils;
+use crate::Settings;
 
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
-
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct IPLocation {
-    pub city: String,
-    pub country_code: String,
-    pub lat: f64,
-    pub lon: f64,
+use serde_json::Value;
+
+/// IP-based location detection through an ordered chain of providers.
+pub struct IPLocation;
+
+impl IpLocationProvider {
+    /// Returns the provider name for error messages.
+    ///
+    /// # Returns
+    ///
+    /// Returns the provider name, e.g. "ipinfo".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IpLocationProvider::IpInfo => "ipinfo",
+            IpLocationProvider::IpApi => "ip-api",
+            IpLocationProvider::Custom => "custom",
+            IpLocationProvider::Gps => "gps",
+        }
+    }
 }
 
-impl Location for IPLocation {
-    /// Fetches location data based on the client's IP address.
+impl IPLocation {
+    /// Detects the location of the client's public IP address, trying each configured provider in turn.
     ///
-    /// Uses the ip-api.com service to determine the user's location based on their
-    /// public IP address. This is used when no explicit location is provided.
+    /// Providers are tried in the configured order, by default ipinfo over HTTPS and then
+    /// ip-api. A GPS receiver can be listed as a provider too, usually last so it is only
+    /// asked when the IP lookup fails. If they all fail, the configured fallback coordinates
+    /// are used instead.
     ///
     /// # Arguments
     ///
-    /// * `_` - Unused parameter (name), kept for trait compatibility
-    /// * `_` - Unused parameter (country_code), kept for trait compatibility
+    /// * `settings` - Settings with the IP location, GPS and geocoding configuration and the endpoints
     ///
     /// # Returns
     ///
-    /// Returns `LocationData` containing the detected location and coordinates.
+    /// Returns `LocationData` from the first provider that answers, or from the fallback.
     ///
     /// # Errors
     ///
-    /// This function will return an error if:
-    /// - The API request fails
-    /// - The JSON response cannot be parsed
-    /// - Network connectivity issues prevent IP detection
-    fn fetch(_: &str, _: &str) -> Result<LocationData> {
-        let base_url = "http://ip-api.com/json";
-        let api_url = utils::urls::builder(base_url, vec![("fields", "33603794")]);
+    /// Returns an error listing every provider tried and why it failed, if none answered
+    /// and no fallback is configured.
+    pub async fn fetch_chain(settings: &Settings) -> Result<LocationData> {
+        let config = &settings.ip_location;
+        let providers = config
+            .providers
+            .clone()
+            .unwrap_or_else(|| vec![IpLocationProvider::IpInfo, IpLocationProvider::IpApi]);
+
+        let mut failures = Vec::new();
+        let mut offline = true;
+        for provider in &providers {
+            let result = match provider {
+                IpLocationProvider::Gps => Gpsd::locate(settings).await,
+                _ => Self::fetch_provider(provider, config, &settings.endpoints).await,
+            };
+            match result {
+                Ok(location_data) => {
+                    return Ok(LocationData { source: provider.as_str().to_string(), ..location_data })
+                },
+                Err(e) => {
+                    offline &= matches!(Error::find(&e), Some(Error::Network(_)));
+                    failures.push(format!("  {}: {e:#}", provider.as_str()));
+                },
+            }
+        }
+
+        if let Some(fallback) = &config.fallback {
+            return Self::fallback(fallback);
+        }
+
+        let message = format!(
+            "Unable to detect your location from your IP address, tried:\n{}\n\
+             Set a location with --location, or a fallback under ip_location in the configuration file",
+            failures.join("\n")
+        );
+        // Only call it a network failure when no provider could be reached at all
+        Err(if offline { Error::Network(message) } else { Error::LocationNotFound(message) }.into())
+    }
+
+    /// Fetches the location from a single provider.
+    async fn fetch_provider(
+        provider: &IpLocationProvider,
+        config: &IpLocationConfig,
+        endpoints: &EndpointsConfig,
+    ) -> Result<LocationData> {
+        let api_url = match provider {
+            IpLocationProvider::IpInfo => match &config.ipinfo_token {
+                Some(token) => {
+                    endpoints::builder(endpoints, Endpoint::IpInfo, vec![("token", token.as_str())])
+                },
+                None => endpoints::url(endpoints, Endpoint::IpInfo),
+            },
+            IpLocationProvider::IpApi => endpoints::builder(
+                endpoints,
+                Endpoint::IpApi,
+                vec![("fields", "status,message,countryCode,city,lat,lon,timezone")],
+            ),
+            IpLocationProvider::Custom => config
+                .custom_url
+                .clone()
+                .ok_or_else(|| Error::Config("No custom_url is configured".to_string()))?,
+            IpLocationProvider::Gps => {
+                return Err(Error::Config("GPS is not an IP location service".to_string()).into())
+            },
+        };
+
+        let body = client::get_with_retry(&api_url, 1).await.with_context(|| "Request failed")?;
+        let response: Value = serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Unable to parse the response JSON".to_string()))?;
+
+        Self::parse(&response)
+    }
+
+    /// Reads a location from the JSON of any supported provider.
+    ///
+    /// Field names vary between services, so the common spellings are all accepted:
+    /// `latitude`/`lat` and `longitude`/`lon`, or ipinfo's combined `loc`, and
+    /// `country_code`/`countryCode`/`country_iso`, or a two-letter `country`, and
+    /// `timezone`/`time_zone` when the service names one.
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - The provider's JSON response
+    ///
+    /// # Returns
+    ///
+    /// Returns location data on success, or an error if the provider reported a failure
+    /// or the response has no coordinates.
+    pub fn parse(response: &Value) -> Result<LocationData> {
+        if response["status"] == "fail" {
+            let message = response["message"].as_str().unwrap_or("unknown error");
+            return Err(Error::LocationNotFound(format!("The provider reported a failure: {message}")).into());
+        }
 
-        let body =
-            client::get_with_retry(&api_url, 2).with_context(|| "Unable to fetch IP-based location data")?;
+        let string = |keys: &[&str]| keys.iter().find_map(|key| response[*key].as_str().map(str::to_string));
+        let number = |keys: &[&str]| keys.iter().find_map(|key| response[*key].as_f64());
+
+        let (latitude, longitude) = match (number(&["latitude", "lat"]), number(&["longitude", "lon"])) {
+            (Some(latitude), Some(longitude)) => (latitude, longitude),
+            _ => string(&["loc"])
+                .and_then(|loc| LocationData::parse_coordinates(&loc))
+                .map(|(latitude, longitude, _)| (latitude, longitude))
+                .ok_or_else(|| Error::Parse("The response has no coordinates".to_string()))?,
+        };
 
-        let loc: IPLocation =
-            serde_json::from_str(&body).with_context(|| "Unable to parse IP location response JSON")?;
+        let country_code = string(&["country_code", "countryCode", "country_iso"])
+            .or_else(|| string(&["country"]).filter(|country| country.len() == 2))
+            .unwrap_or_default();
 
         let mut location_data = LocationData {
-            city: loc.city.to_owned(),
-            country_code: loc.country_code.to_owned(),
-            latitude: loc.lat,
-            longitude: loc.lon,
+            city: string(&["city"]).unwrap_or_default(),
+            country_code,
+            latitude,
+            longitude,
             location: "".to_string(),
             created_at: utils::get_now(),
+            timezone: string(&["timezone", "time_zone"]).unwrap_or_default(),
+            ..Default::default()
         };
 
         // Normalize the location data for consistent formatting
@@ -59,4 +173,99 @@ impl Location for IPLocation {
 
         Ok(location_data)
     }
+
+    /// Builds location data from the fallback coordinates, without any network access.
+    fn fallback(fallback: &str) -> Result<LocationData> {
+        let (latitude, longitude, label) = LocationData::parse_coordinates(fallback).ok_or_else(|| {
+            Error::Config(format!(
+                "The ip_location fallback '{fallback}' is not in 'latitude,longitude[,label]' format"
+            ))
+        })?;
+
+        Ok(LocationData {
+            city: label.unwrap_or_else(|| format!("{latitude},{longitude}")),
+            latitude,
+            longitude,
+            created_at: utils::get_now(),
+            source: "fallback".to_string(),
+            ..Default::default()
+        })
+    }
+}
+
+impl Location for IPLocation {
+    /// Fetches location data based on the client's IP address.
+    ///
+    /// Uses the default provider chain, see `IPLocation::fetch_chain`. This is used
+    /// when no explicit location is provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `_` - Unused parameter (name), kept for trait compatibility
+    /// * `_` - Unused parameter (country_code), kept for trait compatibility
+    /// * `s` - Settings with the IP location providers and endpoints
+    ///
+    /// # Returns
+    ///
+    /// Returns `LocationData` containing the detected location and coordinates.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if every provider fails, listing why.
+    async fn fetch(_: &str, _: &str, s: &Settings) -> Result<LocationData> {
+        Self::fetch_chain(s).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_server;
+
+    #[test]
+    fn test_parse() {
+        let ipinfo = serde_json::json!({"city": "calgary", "country": "CA", "loc": "51.0501,-114.0853", "timezone": "America/Edmonton"});
+        let location = IPLocation::parse(&ipinfo).unwrap();
+        assert_eq!((location.city.as_str(), location.country_code.as_str()), ("Calgary", "CA"));
+        assert_eq!(location.latitude, 51.0501);
+        assert_eq!(location.timezone, "America/Edmonton");
+
+        let ip_api = serde_json::json!({"status": "success", "countryCode": "DE", "city": "Berlin", "lat": 52.52, "lon": 13.40});
+        assert_eq!(IPLocation::parse(&ip_api).unwrap().country_code, "DE");
+
+        let echoip = serde_json::json!({"country": "Canada", "country_iso": "CA", "latitude": 53.5, "longitude": -113.5});
+        assert_eq!(IPLocation::parse(&echoip).unwrap().country_code, "CA");
+
+        let failure = serde_json::json!({"status": "fail", "message": "reserved range"});
+        assert!(IPLocation::parse(&failure).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_chain() {
+        let base_url = test_server::serve(vec![(
+            "/json",
+            r#"{"city": "Edmonton", "country_code": "CA", "lat": 53.5, "lon": -113.5}"#.to_string(),
+        )]);
+
+        let settings = |ip_location| Settings { ip_location, ..Default::default() };
+
+        let config = IpLocationConfig {
+            providers: Some(vec![IpLocationProvider::Custom]),
+            custom_url: Some(format!("{base_url}/json")),
+            ..Default::default()
+        };
+        assert_eq!(IPLocation::fetch_chain(&settings(config)).await.unwrap().city, "Edmonton");
+
+        let config = IpLocationConfig {
+            providers: Some(vec![IpLocationProvider::Custom]),
+            custom_url: Some(format!("{base_url}/missing")),
+            ..Default::default()
+        };
+        let error = IPLocation::fetch_chain(&settings(config.clone())).await.unwrap_err().to_string();
+        assert!(error.contains("  custom: Request failed"));
+
+        let config = IpLocationConfig { fallback: Some("51.05,-114.07,Office".to_string()), ..config };
+        let location = IPLocation::fetch_chain(&settings(config)).await.unwrap();
+        assert_eq!((location.city.as_str(), location.longitude), ("Office", -114.07));
+    }
 }
@@ -1,15 +1,26 @@
+use crate::settings::{GeocodingConfig, GeocodingMode};
+use crate::utils::cache::CacheHeader;
 use crate::utils::*;
 use crate::Settings;
 
-use crate::api::geolocation;
+use crate::api::geolocation::{self, AmbiguousLocation};
+use crate::api::gpsd::Gpsd;
 use crate::api::iplocation;
+use crate::api::offlinegeocoder::OfflineGeocoder;
+use crate::api::reversegeocoding::ReverseGeocoding;
+use crate::error::Error;
 
 use anyhow::Result;
-use savefile::prelude::*;
 use savefile_derive::Savefile;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
 use stringcase;
 
+/// Location string that reads the position of a GPS receiver through gpsd.
+const GPS_LOCATION: &str = "gps";
+
 /// Trait for different location lookup methods.
 ///
 /// This trait abstracts the location lookup functionality, allowing for different
@@ -21,6 +32,7 @@ pub trait Location {
     ///
     /// * `name` - The city or location name to look up
     /// * `country_code` - The country code (may be empty for IP-based lookup)
+    /// * `s` - Settings with the endpoints and the geocoding, IP location and GPS configuration
     ///
     /// # Returns
     ///
@@ -29,9 +41,17 @@ pub trait Location {
     /// # Errors
     ///
     /// Returns an error if the location cannot be found or API request fails.
-    fn fetch(name: &str, country_code: &str) -> Result<LocationData>;
+    fn fetch(
+        name: &str,
+        country_code: &str,
+        s: &Settings,
+    ) -> impl Future<Output = Result<LocationData>> + Send;
 }
 
+/// Version 1 added where the location came from and the network it was detected on,
+/// version 2 its timezone
+pub const LOCATION_DATA_VERSION: u32 = 2;
+
 #[derive(Default, Deserialize, Serialize, Debug, Savefile)]
 pub struct LocationData {
     pub city: String,
@@ -40,6 +60,15 @@ pub struct LocationData {
     pub longitude: f64,
     pub location: String,
     pub created_at: u64,
+    /// How the location was found, e.g. "geocoding", "gps" or the IP location provider
+    #[savefile_versions = "1.."]
+    pub source: String,
+    /// Fingerprint of the network an automatically detected location was found on
+    #[savefile_versions = "1.."]
+    pub network: String,
+    /// IANA timezone of the location, e.g. "America/Edmonton", or empty if the source doesn't say
+    #[savefile_versions = "2.."]
+    pub timezone: String,
 }
 
 impl LocationData {
@@ -66,8 +95,9 @@ impl LocationData {
     /// Normalizes a location string to consistent "City, COUNTRY" format.
     ///
     /// This helper function takes a location string and returns it in normalized format:
-    /// - City names are converted to CamelCase
+    /// - City and region names are converted to CamelCase
     /// - Country codes are converted to uppercase
+    /// - Coordinates are written without spaces
     ///
     /// # Arguments
     ///
@@ -81,15 +111,107 @@ impl LocationData {
             return location.to_string();
         }
 
+        if Self::is_gps(location) {
+            return GPS_LOCATION.to_string();
+        }
+
+        if let Some((latitude, longitude, label)) = Self::parse_coordinates(location) {
+            return match label {
+                Some(label) => format!("{latitude},{longitude},{label}"),
+                None => format!("{latitude},{longitude}"),
+            };
+        }
+
         let parts: Vec<&str> = location.split(',').collect();
-        if parts.len() == 2 {
-            let city = parts[0].trim();
-            let country = parts[1].trim().to_uppercase();
-            let normalized_city = Self::normalize_city_name(city);
-            format!("{normalized_city}, {country}")
-        } else {
-            location.to_string()
+        match parts.as_slice() {
+            [city, country] => {
+                let normalized_city = Self::normalize_city_name(city.trim());
+                format!("{normalized_city}, {}", country.trim().to_uppercase())
+            },
+            [city, region, country] => {
+                let normalized_city = Self::normalize_city_name(city.trim());
+                let normalized_region = Self::normalize_city_name(region.trim());
+                format!("{normalized_city}, {normalized_region}, {}", country.trim().to_uppercase())
+            },
+            _ => location.to_string(),
+        }
+    }
+
+    /// Checks whether a location asks for the position of a GPS receiver.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The location string
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the location is "gps", in any case.
+    pub fn is_gps(location: &str) -> bool {
+        location.trim().eq_ignore_ascii_case(GPS_LOCATION)
+    }
+
+    /// Resolves a named place from the configuration file to the location it stands for.
+    ///
+    /// A location is looked up in `places` when it is written as "@name", or when it is
+    /// a single word matching a place name. Any other location is returned unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The location string, possibly naming a place
+    /// * `places` - Named places from the configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns the location the place stands for, or the location itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the location is written as "@name" and no such place is defined.
+    pub fn resolve_place(location: &str, places: &HashMap<String, String>) -> Result<String> {
+        let location = location.trim();
+
+        if let Some(name) = location.strip_prefix('@') {
+            return places.get(name).cloned().ok_or_else(|| {
+                Error::Config(format!(
+                    "Unknown place '@{name}', define it under places in the configuration file"
+                ))
+                .into()
+            });
+        }
+
+        if !location.contains(',') {
+            if let Some(place) = places.get(location) {
+                return Ok(place.clone());
+            }
+        }
+
+        Ok(location.to_string())
+    }
+
+    /// Parses a location given as coordinates, with an optional label.
+    ///
+    /// Accepts "latitude,longitude" in decimal degrees, optionally followed by a
+    /// label for display, e.g. "51.05,-114.07" or "51.05,-114.07,Cabin".
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The location string to parse
+    ///
+    /// # Returns
+    ///
+    /// Returns the latitude, longitude and label, or `None` if the string is not
+    /// a pair of valid coordinates.
+    pub fn parse_coordinates(location: &str) -> Option<(f64, f64, Option<String>)> {
+        let mut parts = location.splitn(3, ',');
+        let latitude: f64 = parts.next()?.trim().parse().ok()?;
+        let longitude: f64 = parts.next()?.trim().parse().ok()?;
+        let label = parts.next().map(str::trim).filter(|label| !label.is_empty()).map(str::to_string);
+
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return None;
         }
+
+        Some((latitude, longitude, label))
     }
 
     /// Normalizes the location data by formatting city as CamelCase and country as uppercase.
@@ -128,9 +250,18 @@ impl LocationData {
 
     /// Retrieves location data using cached data if available.
     ///
-    /// Location data is cached for 4 hours (14400 seconds) to reduce API calls.
+    /// Named places from the configuration file are resolved first, see `resolve_place`.
+    /// Location data is cached for 4 hours by default to reduce API calls, or for a minute
+    /// when read from a GPS receiver, which may be moving, see `CacheConfig`. A location detected from
+    /// the IP address is also looked up again as soon as the network changes, so it follows
+    /// a laptop between networks.
     /// If cached data is found for the same location and is still fresh, it will be returned.
-    /// Otherwise, fresh data will be fetched using the appropriate lookup method.
+    /// Otherwise, fresh data will be fetched using the appropriate lookup method. When that
+    /// fails, or in offline mode, the cached location is used regardless of its age.
+    ///
+    /// Looked up coordinates are rounded to 0.1° so nearby lookups share cached weather,
+    /// while coordinates entered by the user or read from GPS are kept at their precision.
+    /// Coordinates that couldn't be named are not cached, so the name is looked up again.
     ///
     /// # Arguments
     ///
@@ -143,29 +274,50 @@ impl LocationData {
     /// # Errors
     ///
     /// This function will return an error if:
+    /// - The location names a place that is not defined
     /// - The location format is invalid (for manual location entry)
     /// - The API request fails
     /// - No location results are found
-    pub fn get_cached(s: Settings) -> Result<Self> {
-        let filename = cache::get_cached_file("location", &s.location);
+    pub async fn get_cached(s: Settings) -> Result<Self> {
+        let l = Self::resolve_place(&s.location, &s.places)?;
         let now = get_now();
 
-        let fd: LocationData = load_file(&filename, 0).unwrap_or_default();
-        let l = s.location.to_owned();
-
         // Normalize the input location for cache comparison
         let normalized_input = Self::normalize_location_string(&l);
 
-        // Cache lifetime is 4 hours (14400 seconds)
-        if fd.location == normalized_input && fd.created_at > 0 && now - fd.created_at < 14400 {
+        let header = CacheHeader::new("location", LOCATION_DATA_VERSION, normalized_input.as_str());
+        let fd: LocationData = cache::load(&s.cache.dir(), &header, &l).unwrap_or_default();
+
+        let is_gps = Self::is_gps(&normalized_input);
+        let lifetime = if is_gps { s.cache.gps_seconds() } else { s.cache.location_seconds() };
+        let network = if normalized_input.is_empty() { network::fingerprint() } else { String::new() };
+        let cached = fd.location == normalized_input && fd.created_at > 0;
+        if cached && ((fd.network == network && now - fd.created_at < lifetime) || s.offline) {
             return Ok(fd);
         }
 
-        let mut data = Self::lookup(l)?;
-        data.latitude = format!("{:.1}", data.latitude).parse().unwrap_or(0.0);
-        data.longitude = format!("{:.1}", data.longitude).parse().unwrap_or(0.0);
+        let mut data = match Self::lookup(l.clone(), &s).await {
+            Ok(data) => data,
+            // Where we were last is still the best guess when the lookup fails, e.g. offline
+            Err(_) if cached => return Ok(fd),
+            Err(e) => return Err(e),
+        };
+        data.network = network;
+        if !normalized_input.is_empty() {
+            // The geocoded name can differ from what was asked for, e.g. with a region
+            data.location = normalized_input.clone();
+        }
+        if !is_gps && Self::parse_coordinates(&normalized_input).is_none() {
+            data.latitude = format!("{:.1}", data.latitude).parse().unwrap_or(0.0);
+            data.longitude = format!("{:.1}", data.longitude).parse().unwrap_or(0.0);
+        }
+
+        // Naming coordinates can fail for a moment, which shouldn't stick for the whole lifetime
+        if data.is_unnamed_coordinates() {
+            return Ok(data);
+        }
 
-        match save_file(&filename, 0, &data) {
+        match cache::save(&s.cache.dir(), &header, &l, &data) {
             Ok(_) => {},
             Err(e) => eprintln!("Unable to save location data to disk: {e:#?}"),
         }
@@ -173,15 +325,27 @@ impl LocationData {
         Ok(data)
     }
 
+    /// Checks whether these are coordinates that couldn't be matched to a place.
+    ///
+    /// That happens when reverse geocoding fails, e.g. offline, but also far from any
+    /// named place, which is looked up again each time as the two can't be told apart.
+    fn is_unnamed_coordinates(&self) -> bool {
+        self.source == "coordinates" && self.country_code.is_empty()
+    }
+
     /// Looks up location data based on the provided location string.
     ///
     /// If the location string is empty, uses IP-based location detection.
-    /// If the location string contains a comma, treats it as "City, CountryCode" format
-    /// and uses geocoding API.
+    /// If the location string is "gps", reads the position of a GPS receiver from gpsd.
+    /// If the location string is a pair of coordinates, reverse geocodes them to a place name.
+    /// Otherwise, treats it as "City, CountryCode" or "City, Region, CountryCode" format
+    /// and looks it up in the offline city database when installed, then the geocoding API.
     ///
     /// # Arguments
     ///
-    /// * `l` - Location string, either empty (for IP lookup) or "City, CountryCode" format
+    /// * `l` - Location string, either empty (for IP lookup), "gps", "latitude,longitude[,label]",
+    ///   "City, CountryCode" or "City, Region, CountryCode" format
+    /// * `s` - Settings with the endpoints and the geocoding, IP location and GPS configuration
     ///
     /// # Returns
     ///
@@ -190,25 +354,191 @@ impl LocationData {
     /// # Errors
     ///
     /// Returns an error if:
-    /// - The location format is invalid (not "City, CountryCode")
+    /// - The location format is invalid (not coordinates or "City, [Region,] CountryCode")
     /// - The geocoding or IP location API request fails
+    /// - gpsd cannot be reached or has no fix in time
     /// - No results are found for the specified location
-    fn lookup(l: String) -> Result<Self> {
-        if !l.is_empty() {
-            let parts: Vec<&str> = l.split(',').collect();
-            if parts.len() == 2 {
-                let name = parts[0].trim();
-                let country_code = parts[1].trim().to_uppercase();
+    /// - Several places match a "City, CountryCode" location, as an `AmbiguousLocation`
+    async fn lookup(l: String, s: &Settings) -> Result<Self> {
+        let geocoding = &s.geocoding;
+        if Self::is_gps(&l) {
+            return Gpsd::locate(s).await;
+        }
 
-                // Normalize the city name to CamelCase for consistency
-                let normalized_name = Self::normalize_city_name(name);
+        if let Some((latitude, longitude, label)) = Self::parse_coordinates(&l) {
+            return Ok(Self::from_coordinates(latitude, longitude, label, s).await);
+        }
+
+        if !l.is_empty() {
+            let parts: Vec<&str> = l.split(',').map(str::trim).collect();
+            // Normalize the city name to CamelCase for consistency
+            let (name, region, country_code) = match parts.as_slice() {
+                [name, country_code] => (Self::normalize_city_name(name), None, country_code.to_uppercase()),
+                [name, region, country_code] => {
+                    (Self::normalize_city_name(name), Some(*region), country_code.to_uppercase())
+                },
+                _ => {
+                    return Err(Error::Config(
+                        "Invalid location format. Use 'City, CountryCode', 'City, Region, CountryCode' or 'latitude,longitude'."
+                            .to_string(),
+                    )
+                    .into())
+                },
+            };
 
-                geolocation::GeoLocation::fetch(&normalized_name, &country_code)
-            } else {
-                Err(anyhow::anyhow!("Invalid location format. Use 'City, CountryCode'."))
+            let mode = geocoding.mode.clone().unwrap_or_default();
+            if let Some(geocoder) = Self::offline_geocoder(geocoding).await? {
+                match geocoder.resolve(&name, region, &country_code) {
+                    Ok(data) => return Ok(LocationData { source: "offline".to_string(), ..data }),
+                    // Only a city missing from the database is worth asking the network about
+                    Err(e) if mode == GeocodingMode::Offline || e.is::<AmbiguousLocation>() => return Err(e),
+                    Err(_) => {},
+                }
             }
+
+            let data = geolocation::GeoLocation::resolve(&name, region, &country_code, &s.endpoints).await?;
+            Ok(LocationData { source: "geocoding".to_string(), ..data })
         } else {
-            iplocation::IPLocation::fetch("", "")
+            iplocation::IPLocation::fetch_chain(s).await
+        }
+    }
+
+    /// Loads the offline city database if the geocoding mode allows it.
+    ///
+    /// # Arguments
+    ///
+    /// * `geocoding` - Geocoding settings from the configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns the offline geocoder, or `None` when geocoding online or when the database
+    /// is not installed in automatic mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in offline mode if the database is missing or cannot be read.
+    async fn offline_geocoder(geocoding: &GeocodingConfig) -> Result<Option<Arc<OfflineGeocoder>>> {
+        match (geocoding.mode.clone().unwrap_or_default(), OfflineGeocoder::database_path(geocoding)) {
+            (GeocodingMode::Online, _) => Ok(None),
+            (GeocodingMode::Auto, Some(path)) => Ok(OfflineGeocoder::load_shared(path).await.ok()),
+            (GeocodingMode::Auto, None) => Ok(None),
+            (GeocodingMode::Offline, Some(path)) => OfflineGeocoder::load_shared(path).await.map(Some),
+            (GeocodingMode::Offline, None) => Err(Error::Config(
+                "Offline geocoding needs the GeoNames cities15000.txt database, run `outside geocoder install`"
+                    .to_string(),
+            )
+            .into()),
+        }
+    }
+
+    /// Builds location data for coordinates entered by the user or read from GPS.
+    ///
+    /// The place name is shown in place of a city: the label if one was given, otherwise
+    /// the nearest named place from reverse geocoding, or the nearest city in the offline
+    /// database. Reverse geocoding is best-effort, so remote sites with no named place
+    /// nearby are shown by their coordinates.
+    ///
+    /// # Arguments
+    ///
+    /// * `latitude` - Latitude in decimal degrees
+    /// * `longitude` - Longitude in decimal degrees
+    /// * `label` - Optional display name given by the user
+    /// * `s` - Settings with the endpoints and the geocoding mode, deciding whether to name
+    ///   the place online or offline
+    ///
+    /// # Returns
+    ///
+    /// Returns location data with the coordinates exactly as given.
+    pub(crate) async fn from_coordinates(
+        latitude: f64,
+        longitude: f64,
+        label: Option<String>,
+        s: &Settings,
+    ) -> Self {
+        // The nearest city also gives the timezone, which reverse geocoding doesn't
+        let nearest_city = Self::offline_geocoder(&s.geocoding)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|geocoder| geocoder.nearest(latitude, longitude));
+        let nearest_place =
+            || nearest_city.as_ref().map(|city| (city.name.clone(), city.country_code.clone()));
+        let (name, country_code) = match s.geocoding.mode.clone().unwrap_or_default() {
+            GeocodingMode::Online => ReverseGeocoding::fetch(latitude, longitude, &s.endpoints).await.ok(),
+            GeocodingMode::Auto => match ReverseGeocoding::fetch(latitude, longitude, &s.endpoints).await {
+                Ok(place) => Some(place),
+                Err(_) => nearest_place(),
+            },
+            GeocodingMode::Offline => nearest_place(),
+        }
+        .unwrap_or_default();
+        let location = match &label {
+            Some(label) => format!("{latitude},{longitude},{label}"),
+            None => format!("{latitude},{longitude}"),
+        };
+
+        LocationData {
+            city: label
+                .or_else(|| Some(name).filter(|name| !name.is_empty()))
+                .unwrap_or_else(|| format!("{latitude},{longitude}")),
+            country_code,
+            latitude,
+            longitude,
+            location,
+            created_at: get_now(),
+            source: "coordinates".to_string(),
+            network: String::new(),
+            timezone: nearest_city.and_then(|city| city.timezone).unwrap_or_default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_coordinates() {
+        assert_eq!(LocationData::parse_coordinates("51.0447,-114.0719"), Some((51.0447, -114.0719, None)));
+        assert_eq!(
+            LocationData::parse_coordinates(" 51.05 , -114.07 , Cabin "),
+            Some((51.05, -114.07, Some("Cabin".to_string())))
+        );
+        assert_eq!(LocationData::parse_coordinates("Calgary, CA"), None);
+        assert_eq!(LocationData::parse_coordinates("95.0,10.0"), None);
+
+        assert_eq!(LocationData::normalize_location_string("51.050, -114.07,Cabin"), "51.05,-114.07,Cabin");
+        assert_eq!(LocationData::normalize_location_string("calgary, ca"), "Calgary, CA");
+        assert_eq!(
+            LocationData::normalize_location_string("springfield, illinois, us"),
+            "Springfield, Illinois, US"
+        );
+    }
+
+    #[test]
+    fn test_is_unnamed_coordinates() {
+        let location = |source: &str, country_code: &str| LocationData {
+            source: source.to_string(),
+            country_code: country_code.to_string(),
+            ..Default::default()
+        };
+
+        assert!(location("coordinates", "").is_unnamed_coordinates());
+        assert!(!location("coordinates", "CA").is_unnamed_coordinates());
+        assert!(!location("ipinfo", "").is_unnamed_coordinates());
+    }
+
+    #[test]
+    fn test_resolve_place() {
+        let places = HashMap::from([
+            ("home".to_string(), "Calgary, CA".to_string()),
+            ("cabin".to_string(), "51.2,-115.5".to_string()),
+        ]);
+
+        assert_eq!(LocationData::resolve_place("home", &places).unwrap(), "Calgary, CA");
+        assert_eq!(LocationData::resolve_place("@cabin", &places).unwrap(), "51.2,-115.5");
+        assert_eq!(LocationData::resolve_place("Home, US", &places).unwrap(), "Home, US");
+        assert_eq!(LocationData::resolve_place("", &places).unwrap(), "");
+        assert!(LocationData::resolve_place("@office", &places).is_err());
+    }
+}
@@ -0,0 +1,414 @@
+use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
+use crate::api::weather::*;
+use crate::error::Error;
+use crate::utils::{conversions, mappings, solar};
+use crate::Settings;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Weather provider backed by the MET Norway Locationforecast 2.0 API.
+///
+/// MET Norway returns a single timeseries in UTC, hourly for the first couple of
+/// days and 6-hourly afterwards. Both the `compact` and `complete` variants can be
+/// parsed; the `complete` variant is requested since it adds precipitation
+/// probability, wind gusts and UV index.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MetNo {
+    pub properties: Properties,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Properties {
+    pub timeseries: Vec<TimeStep>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TimeStep {
+    pub time: String,
+    pub data: StepData,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StepData {
+    pub instant: Instant,
+    pub next_1_hours: Option<Period>,
+    pub next_6_hours: Option<Period>,
+    pub next_12_hours: Option<Period>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Instant {
+    pub details: InstantDetails,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct InstantDetails {
+    pub air_temperature: f64,
+    pub air_pressure_at_sea_level: f64,
+    pub relative_humidity: f64,
+    pub wind_from_direction: f64,
+    pub wind_speed: f64,
+    pub wind_speed_of_gust: Option<f64>,
+    pub ultraviolet_index_clear_sky: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Period {
+    pub summary: Summary,
+    #[serde(default)]
+    pub details: PeriodDetails,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Summary {
+    pub symbol_code: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct PeriodDetails {
+    pub precipitation_amount: Option<f64>,
+    pub probability_of_precipitation: Option<f64>,
+    pub air_temperature_max: Option<f64>,
+    pub air_temperature_min: Option<f64>,
+}
+
+#[derive(Default)]
+struct DayTotals {
+    weather_code: i32,
+    temperature_max: f64,
+    temperature_min: f64,
+    precipitation_sum: f64,
+    precipitation_hours: f64,
+    precipitation_probability_max: i32,
+    uv_index_max: f64,
+}
+
+impl WeatherProvider for MetNo {
+    /// Fetches fresh weather data from the MET Norway Locationforecast API.
+    ///
+    /// MET Norway only reports UTC times, so the forecast is converted to the
+    /// location's timezone, see `into_weather`. Sunrise and sunset are calculated
+    /// locally since they are not part of the forecast response.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate for the weather location
+    /// * `lon` - Longitude coordinate for the weather location
+    /// * `timezone` - IANA timezone of the location, or empty if unknown
+    /// * `s` - Settings with the endpoints
+    ///
+    /// # Returns
+    ///
+    /// Returns weather data mapped into the provider-neutral model.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The HTTP request fails (MET Norway rejects requests without a User-Agent)
+    /// - The JSON response cannot be parsed
+    /// - The response contains no timeseries data
+    async fn fetch(lat: f64, lon: f64, timezone: &str, s: &Settings) -> Result<Weather> {
+        // MET Norway asks clients to use at most 4 decimals to improve cache hits
+        let lat_str = format!("{lat:.4}");
+        let lon_str = format!("{lon:.4}");
+        let params = vec![("lat", lat_str.as_str()), ("lon", lon_str.as_str())];
+
+        let api_url = endpoints::builder(&s.endpoints, Endpoint::MetNo, params);
+
+        let body = client::get_with_retry(&api_url, 2)
+            .await
+            .with_context(|| "Unable to fetch weather data from the MET Norway API endpoint")?;
+
+        let forecast: MetNo = serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Unable to parse MET Norway response JSON".to_string()))?;
+
+        forecast.into_weather(lat, lon, timezone)
+    }
+}
+
+impl MetNo {
+    /// Maps the MET Norway timeseries into the provider-neutral weather model.
+    ///
+    /// - Current conditions come from the first timestep
+    /// - Hourly data comes from every timestep with a 1-hour summary
+    /// - Daily data is aggregated per local calendar day, using 1-hour periods
+    ///   where available and 6-hour periods afterwards
+    ///
+    /// Times are converted with the rules of the location's timezone, so they stay right
+    /// across daylight saving changes. Locations found without a timezone, e.g. by GPS,
+    /// use this machine's timezone, which they are most likely in.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate, used for sunrise and sunset
+    /// * `lon` - Longitude coordinate, used for sunrise and sunset
+    /// * `timezone` - IANA timezone of the location, e.g. "Europe/Oslo", or empty if unknown
+    ///
+    /// # Returns
+    ///
+    /// Returns weather data in metric units, or an error if the timeseries is empty.
+    pub fn into_weather(self, lat: f64, lon: f64, timezone: &str) -> Result<Weather> {
+        let tz = timezone.parse::<Tz>().ok();
+        let offset_at = |time: DateTime<Utc>| -> FixedOffset {
+            match tz {
+                Some(tz) => tz.offset_from_utc_datetime(&time.naive_utc()).fix(),
+                None => Local.offset_from_utc_datetime(&time.naive_utc()).fix(),
+            }
+        };
+
+        let steps: Vec<(DateTime<Utc>, TimeStep)> = self
+            .properties
+            .timeseries
+            .into_iter()
+            .filter_map(|step| {
+                DateTime::parse_from_rfc3339(&step.time).ok().map(|t| (t.with_timezone(&Utc), step))
+            })
+            .collect();
+
+        let (first_time, first) =
+            steps.first().ok_or_else(|| Error::Parse("MET Norway returned no forecast data".to_string()))?;
+        let offset = offset_at(*first_time);
+
+        let mut weather = Weather {
+            current: Self::current(first),
+            timezone: tz.map_or_else(|| offset.to_string(), |tz| tz.name().to_string()),
+            utc_offset_seconds: offset.local_minus_utc(),
+            latitude: lat,
+            longitude: lon,
+            ..Default::default()
+        };
+
+        let mut days: BTreeMap<NaiveDate, DayTotals> = BTreeMap::new();
+
+        for (i, (time, step)) in steps.iter().enumerate() {
+            let local = time.with_timezone(&offset_at(*time)).naive_local();
+            let details = &step.data.instant.details;
+
+            if let Some(hour) = &step.data.next_1_hours {
+                weather.hourly.time.push(local.format("%Y-%m-%dT%H:%M").to_string());
+                weather.hourly.temperature_2m.push(details.air_temperature);
+                weather
+                    .hourly
+                    .precipitation_probability
+                    .push(hour.details.probability_of_precipitation.unwrap_or(0.0).round() as i32);
+                weather.hourly.precipitation.push(hour.details.precipitation_amount.unwrap_or(0.0));
+                weather
+                    .hourly
+                    .weather_code
+                    .push(mappings::metno_symbol2meteo_code(&hour.summary.symbol_code));
+            }
+
+            // Use the period that matches the spacing to the next timestep so that
+            // precipitation is not counted twice when the timeseries becomes 6-hourly
+            let last_step_hours = if step.data.next_1_hours.is_some() { 1 } else { 6 };
+            let step_hours =
+                steps.get(i + 1).map(|(next, _)| (*next - *time).num_hours()).unwrap_or(last_step_hours);
+            let period = if step_hours <= 1 {
+                step.data.next_1_hours.as_ref()
+            } else {
+                step.data.next_6_hours.as_ref().or(step.data.next_1_hours.as_ref())
+            };
+
+            let day = days.entry(local.date()).or_insert_with(|| DayTotals {
+                weather_code: -1,
+                temperature_max: f64::MIN,
+                temperature_min: f64::MAX,
+                ..Default::default()
+            });
+
+            day.temperature_max = day.temperature_max.max(details.air_temperature);
+            day.temperature_min = day.temperature_min.min(details.air_temperature);
+            day.uv_index_max = day.uv_index_max.max(details.ultraviolet_index_clear_sky.unwrap_or(0.0));
+
+            if let Some(period) = period {
+                let amount = period.details.precipitation_amount.unwrap_or(0.0);
+                day.precipitation_sum += amount;
+                if amount > 0.0 {
+                    day.precipitation_hours += step_hours.max(1) as f64;
+                }
+                day.precipitation_probability_max = day
+                    .precipitation_probability_max
+                    .max(period.details.probability_of_precipitation.unwrap_or(0.0).round() as i32);
+                // WMO codes increase with severity, so the highest code describes the day
+                day.weather_code =
+                    day.weather_code.max(mappings::metno_symbol2meteo_code(&period.summary.symbol_code));
+                if let Some(max) = period.details.air_temperature_max {
+                    day.temperature_max = day.temperature_max.max(max);
+                }
+                if let Some(min) = period.details.air_temperature_min {
+                    day.temperature_min = day.temperature_min.min(min);
+                }
+            }
+        }
+
+        for (date, day) in days.into_iter().take(7) {
+            let noon = date.and_hms_opt(12, 0, 0).unwrap_or_default().and_utc();
+            let (sunrise, sunset) = solar::sunrise_sunset(lat, lon, date, offset_at(noon).local_minus_utc());
+
+            weather.daily.time.push(date.format("%Y-%m-%d").to_string());
+            weather.daily.weather_code.push(day.weather_code);
+            weather.daily.sunrise.push(sunrise);
+            weather.daily.sunset.push(sunset);
+            weather.daily.uv_index_max.push(day.uv_index_max);
+            weather.daily.precipitation_sum.push((day.precipitation_sum * 10.0).round() / 10.0);
+            weather.daily.precipitation_hours.push(day.precipitation_hours);
+            weather.daily.precipitation_probability_max.push(day.precipitation_probability_max);
+            weather.daily.temperature_2m_max.push(day.temperature_max);
+            weather.daily.temperature_2m_min.push(day.temperature_min);
+        }
+
+        Ok(weather)
+    }
+
+    /// Builds current conditions from a single timestep.
+    fn current(step: &TimeStep) -> Current {
+        let details = &step.data.instant.details;
+        let period = step
+            .data
+            .next_1_hours
+            .as_ref()
+            .or(step.data.next_6_hours.as_ref())
+            .or(step.data.next_12_hours.as_ref());
+
+        let wind_speed = details.wind_speed * 3.6;
+        let wind_gusts = details.wind_speed_of_gust.unwrap_or(details.wind_speed) * 3.6;
+
+        Current {
+            apparent_temperature: conversions::apparent_temperature(
+                details.air_temperature,
+                details.relative_humidity,
+                wind_speed,
+            ),
+            interval: 3600,
+            precipitation: period.and_then(|p| p.details.precipitation_amount).unwrap_or(0.0),
+            pressure_msl: details.air_pressure_at_sea_level,
+            relative_humidity_2m: details.relative_humidity.round() as i32,
+            temperature_2m: details.air_temperature,
+            weather_code: period
+                .map(|p| mappings::metno_symbol2meteo_code(&p.summary.symbol_code))
+                .unwrap_or(-1),
+            wind_direction_10m: details.wind_from_direction.round() as i32,
+            wind_speed_10m: (wind_speed * 10.0).round() / 10.0,
+            wind_gusts_10m: (wind_gusts * 10.0).round() / 10.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"{
+        "properties": {
+            "timeseries": [
+                {
+                    "time": "2025-06-21T10:00:00Z",
+                    "data": {
+                        "instant": { "details": {
+                            "air_temperature": 18.5, "air_pressure_at_sea_level": 1012.3,
+                            "relative_humidity": 61.2, "wind_from_direction": 225.0,
+                            "wind_speed": 5.0, "wind_speed_of_gust": 9.0
+                        } },
+                        "next_1_hours": {
+                            "summary": { "symbol_code": "lightrainshowers_day" },
+                            "details": { "precipitation_amount": 0.4, "probability_of_precipitation": 55.0 }
+                        },
+                        "next_6_hours": {
+                            "summary": { "symbol_code": "rain" },
+                            "details": { "precipitation_amount": 3.0 }
+                        }
+                    }
+                },
+                {
+                    "time": "2025-06-21T11:00:00Z",
+                    "data": {
+                        "instant": { "details": { "air_temperature": 20.0, "relative_humidity": 50.0 } },
+                        "next_1_hours": {
+                            "summary": { "symbol_code": "cloudy" },
+                            "details": { "precipitation_amount": 0.0 }
+                        }
+                    }
+                },
+                {
+                    "time": "2025-06-21T17:00:00Z",
+                    "data": {
+                        "instant": { "details": { "air_temperature": 15.0, "relative_humidity": 70.0 } },
+                        "next_6_hours": {
+                            "summary": { "symbol_code": "heavyrainandthunder" },
+                            "details": { "precipitation_amount": 6.0, "probability_of_precipitation": 80.0 }
+                        }
+                    }
+                }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_into_weather() {
+        let forecast: MetNo = serde_json::from_str(FIXTURE).unwrap();
+        let weather = forecast.into_weather(59.91, 10.75, "Europe/Oslo").unwrap();
+
+        assert_eq!(weather.timezone, "Europe/Oslo");
+        assert_eq!(weather.utc_offset_seconds, 7200);
+
+        assert_eq!(weather.current.temperature_2m, 18.5);
+        assert_eq!(weather.current.weather_code, 80);
+        assert_eq!(weather.current.wind_speed_10m, 18.0);
+        assert_eq!(weather.current.wind_gusts_10m, 32.4);
+        assert_eq!(weather.current.relative_humidity_2m, 61);
+
+        assert_eq!(weather.hourly.time, vec!["2025-06-21T12:00", "2025-06-21T13:00"]);
+        assert_eq!(weather.hourly.precipitation_probability, vec![55, 0]);
+
+        assert_eq!(weather.daily.time, vec!["2025-06-21"]);
+        assert_eq!(weather.daily.weather_code, vec![95]);
+        assert_eq!(weather.daily.temperature_2m_max, vec![20.0]);
+        assert_eq!(weather.daily.temperature_2m_min, vec![15.0]);
+        // 0.4mm from the first hour, the 11:00 step has no 6-hour period so its dry
+        // 1-hour period is used, and the final step's 6-hour period adds 6.0mm
+        assert_eq!(weather.daily.precipitation_sum, vec![6.4]);
+        assert_eq!(weather.daily.precipitation_probability_max, vec![80]);
+    }
+
+    #[test]
+    fn test_timezone() {
+        let forecast: MetNo = serde_json::from_str(FIXTURE).unwrap();
+        let weather = forecast.into_weather(47.56, -52.71, "America/St_Johns").unwrap();
+        assert_eq!(weather.utc_offset_seconds, -9000);
+        assert_eq!(weather.hourly.time[0], "2025-06-21T07:30");
+
+        // Clocks in Oslo skip from 02:00 to 03:00 on the last Sunday of March
+        let step = |time: &str| {
+            format!(
+                r#"{{ "time": "{time}", "data": {{
+                    "instant": {{ "details": {{ "air_temperature": 2.0, "relative_humidity": 80.0 }} }},
+                    "next_1_hours": {{ "summary": {{ "symbol_code": "cloudy" }}, "details": {{}} }}
+                }} }}"#
+            )
+        };
+        let json = format!(
+            r#"{{ "properties": {{ "timeseries": [{}, {}] }} }}"#,
+            step("2025-03-30T00:00:00Z"),
+            step("2025-03-30T01:00:00Z")
+        );
+        let forecast: MetNo = serde_json::from_str(&json).unwrap();
+        let weather = forecast.into_weather(59.91, 10.75, "Europe/Oslo").unwrap();
+        assert_eq!(weather.hourly.time, vec!["2025-03-30T01:00", "2025-03-30T03:00"]);
+    }
+
+    #[test]
+    fn test_symbol_mapping() {
+        assert_eq!(mappings::metno_symbol2meteo_code("clearsky_night"), 0);
+        assert_eq!(mappings::metno_symbol2meteo_code("partlycloudy_polartwilight"), 2);
+        assert_eq!(mappings::metno_symbol2meteo_code("heavysnowshowers_day"), 86);
+        assert_eq!(mappings::metno_symbol2meteo_code("lightssleetshowersandthunder_day"), 95);
+        assert_eq!(mappings::metno_symbol2meteo_code("unknown"), -1);
+    }
+}
@@ -1,5 +1,17 @@
+pub mod airquality;
+pub mod alerts;
 pub mod client;
+pub mod eccc;
+pub mod endpoints;
 pub mod geolocation;
+pub mod gpsd;
+pub mod history;
 pub mod iplocation;
 pub mod location;
+pub mod metno;
+pub mod models;
+pub mod nws;
+pub mod offlinegeocoder;
+pub mod openmeteo;
+pub mod reversegeocoding;
 pub mod weather;
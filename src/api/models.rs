@@ -0,0 +1,238 @@
+use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
+use crate::error::Error;
+use crate::settings::{EndpointsConfig, Model};
+use crate::utils;
+use crate::utils::cache::{self, CacheHeader};
+use crate::Settings;
+
+use anyhow::{Context, Result};
+use savefile_derive::Savefile;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Daily forecasts from several weather models, fetched side by side for comparison.
+#[derive(Default, Serialize, Deserialize, Debug, Savefile)]
+pub struct ModelComparison {
+    pub models: Vec<ModelForecast>,
+    pub model_list: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub created_at: u64,
+    /// Why older cached data is shown instead of fresh data, if it is
+    #[serde(skip)]
+    #[savefile_ignore]
+    pub stale_reason: Option<String>,
+}
+
+/// One model's daily forecast, in metric units. Values are `None` beyond the model's range.
+#[derive(Default, Serialize, Deserialize, Debug, Savefile)]
+pub struct ModelForecast {
+    pub model: String,
+    pub time: Vec<String>,
+    pub temperature_2m_max: Vec<Option<f64>>,
+    pub temperature_2m_min: Vec<Option<f64>>,
+    pub precipitation_sum: Vec<Option<f64>>,
+}
+
+/// Open-Meteo returns each variable once per model, suffixed with the model name,
+/// e.g. `temperature_2m_max_ecmwf_ifs025`.
+#[derive(Deserialize, Debug)]
+struct MultiModelResponse {
+    daily: MultiModelDaily,
+}
+
+#[derive(Deserialize, Debug)]
+struct MultiModelDaily {
+    time: Vec<String>,
+    #[serde(flatten)]
+    values: HashMap<String, Vec<Option<f64>>>,
+}
+
+impl ModelComparison {
+    /// Retrieves daily forecasts from several models, using cached data if available.
+    ///
+    /// Model forecasts are cached for 10 minutes by default, set with `cache.models`, and
+    /// the cache is tied to the list of models requested. When they can't be refreshed,
+    /// older cached forecasts are used like the main forecast, with their `stale_reason` set.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate for the weather location
+    /// * `lon` - Longitude coordinate for the weather location
+    /// * `s` - Settings containing the models to compare, the location for caching, the endpoints
+    ///   and the cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns one daily forecast per model on success, or an error if fetching fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there are no recent enough cached forecasts and:
+    /// - The API request fails
+    /// - The response cannot be parsed as JSON
+    pub async fn get_cached(lat: f64, lon: f64, s: Settings) -> Result<Self> {
+        let now = utils::get_now();
+        let model_list = s.models.iter().map(Model::as_str).collect::<Vec<_>>().join(",");
+        let header =
+            CacheHeader::new("models", 0, format!("{lat},{lon},{model_list}")).with_provider("open-meteo");
+
+        let mc: ModelComparison = cache::load(&s.cache.dir(), &header, &s.location).unwrap_or_default();
+
+        let cached =
+            mc.latitude == lat && mc.longitude == lon && mc.model_list == model_list && mc.created_at > 0;
+        if cached && now - mc.created_at < s.cache.models_seconds() {
+            return Ok(mc);
+        }
+
+        let models = match Self::fetch(lat, lon, &s.models, &s.endpoints).await {
+            Ok(models) => models,
+            Err(e) if cached && cache::usable_when_stale(mc.created_at, &s) => {
+                return Ok(ModelComparison { stale_reason: Some(e.root_cause().to_string()), ..mc });
+            },
+            Err(e) => return Err(e),
+        };
+        let data = ModelComparison {
+            models,
+            model_list,
+            latitude: lat,
+            longitude: lon,
+            created_at: now,
+            stale_reason: None,
+        };
+
+        match cache::save(&s.cache.dir(), &header, &s.location, &data) {
+            Ok(_) => {},
+            Err(e) => eprintln!("Unable to save model comparison data to disk: {e:#?}"),
+        }
+
+        Ok(data)
+    }
+
+    /// Fetches the daily forecast of each model from the Open-Meteo forecast API in a single request.
+    async fn fetch(
+        lat: f64,
+        lon: f64,
+        models: &[Model],
+        endpoints: &EndpointsConfig,
+    ) -> Result<Vec<ModelForecast>> {
+        let daily_fields = ["temperature_2m_max", "temperature_2m_min", "precipitation_sum"].join(",");
+        let model_names = models.iter().map(Model::api_name).collect::<Vec<_>>().join(",");
+        let lat_str = lat.to_string();
+        let lon_str = lon.to_string();
+
+        let params: Vec<(&str, &str)> = vec![
+            ("latitude", lat_str.as_str()),
+            ("longitude", lon_str.as_str()),
+            ("timezone", "auto"),
+            ("forecast_days", "7"),
+            ("daily", daily_fields.as_str()),
+            ("models", model_names.as_str()),
+        ];
+
+        let api_url = endpoints::builder(endpoints, Endpoint::Forecast, params);
+
+        let body = client::get_with_retry(&api_url, 2)
+            .await
+            .with_context(|| "Unable to fetch model forecasts from the Open-Meteo API endpoint")?;
+
+        let response: MultiModelResponse = serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Unable to parse model forecast response JSON".to_string()))?;
+
+        Ok(Self::split_models(response.daily, models))
+    }
+
+    /// Splits the per-model suffixed series of a multi-model response into one forecast per model.
+    ///
+    /// A request for a single model returns unsuffixed variable names, so those are
+    /// used as a fallback.
+    fn split_models(daily: MultiModelDaily, models: &[Model]) -> Vec<ModelForecast> {
+        let MultiModelDaily { time, mut values } = daily;
+        let mut series = |variable: &str, model: &Model| {
+            values
+                .remove(&format!("{variable}_{}", model.api_name()))
+                .or_else(|| if models.len() == 1 { values.remove(variable) } else { None })
+                .unwrap_or_default()
+        };
+
+        models
+            .iter()
+            .map(|model| ModelForecast {
+                model: model.as_str().to_string(),
+                temperature_2m_max: series("temperature_2m_max", model),
+                temperature_2m_min: series("temperature_2m_min", model),
+                precipitation_sum: series("precipitation_sum", model),
+                time: time.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::CacheConfig;
+    use crate::utils::test_server::{self, Response};
+
+    #[test]
+    fn test_split_models() {
+        let daily: MultiModelDaily = serde_json::from_str(
+            r#"{
+                "time": ["2025-06-21", "2025-06-22"],
+                "temperature_2m_max_ecmwf_ifs025": [21.3, 23.0],
+                "temperature_2m_min_ecmwf_ifs025": [9.1, 10.2],
+                "precipitation_sum_ecmwf_ifs025": [2.1, 0.0],
+                "temperature_2m_max_gfs_seamless": [23.8, null],
+                "temperature_2m_min_gfs_seamless": [10.4, null],
+                "precipitation_sum_gfs_seamless": [0.0, null]
+            }"#,
+        )
+        .unwrap();
+
+        let models = ModelComparison::split_models(daily, &[Model::Ecmwf, Model::Gfs]);
+
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].model, "ecmwf");
+        assert_eq!(models[0].temperature_2m_max, vec![Some(21.3), Some(23.0)]);
+        assert_eq!(models[1].model, "gfs");
+        assert_eq!(models[1].precipitation_sum, vec![Some(0.0), None]);
+    }
+
+    #[tokio::test]
+    async fn test_stale_fallback() {
+        let base_url = test_server::serve_responses(vec![("/v1/forecast", Response::status(500))]);
+        let settings = Settings {
+            location: "51.0447,-114.0719,test_stale_fallback".to_string(),
+            models: vec![Model::Ecmwf, Model::Gfs],
+            endpoints: EndpointsConfig {
+                forecast: Some(format!("{base_url}/v1/forecast")),
+                ..Default::default()
+            },
+            cache: CacheConfig { dir: Some(test_server::cache_dir()), ..Default::default() },
+            ..Default::default()
+        };
+        let header = CacheHeader::new("models", 0, "51.0447,-114.0719,ecmwf,gfs").with_provider("open-meteo");
+        let cache_old = |age| {
+            let data = ModelComparison {
+                models: vec![ModelForecast { model: "ecmwf".to_string(), ..Default::default() }],
+                model_list: "ecmwf,gfs".to_string(),
+                latitude: 51.0447,
+                longitude: -114.0719,
+                created_at: utils::get_now() - age,
+                ..Default::default()
+            };
+            cache::save(&settings.cache.dir(), &header, &settings.location, &data).unwrap();
+        };
+
+        cache_old(3600);
+        let comparison = ModelComparison::get_cached(51.0447, -114.0719, settings.clone()).await.unwrap();
+        assert_eq!(comparison.models[0].model, "ecmwf");
+        assert!(comparison.stale_reason.is_some());
+
+        cache_old(settings.cache.max_stale_seconds() + 60);
+        assert!(ModelComparison::get_cached(51.0447, -114.0719, settings.clone()).await.is_err());
+        let offline = Settings { offline: true, ..settings };
+        assert!(ModelComparison::get_cached(51.0447, -114.0719, offline).await.is_ok());
+    }
+}
@@ -0,0 +1,552 @@
+use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
+use crate::api::weather::*;
+use crate::error::Error;
+use crate::settings::EndpointsConfig;
+use crate::utils;
+use crate::utils::cache::{self, CacheHeader};
+use crate::utils::{conversions, mappings, solar};
+use crate::Settings;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+use savefile_derive::Savefile;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Weather provider backed by the US National Weather Service API (api.weather.gov).
+///
+/// NWS forecasts are issued per 2.5km gridpoint, so coordinates are first resolved
+/// to a gridpoint through the `/points` endpoint (see `GridPoint`). The hourly and
+/// 12-hour forecasts are then combined with the raw gridpoint data, which is the only
+/// place NWS publishes precipitation amounts.
+pub struct Nws;
+
+/// The NWS gridpoint and observation station serving a pair of coordinates.
+///
+/// This mapping practically never changes, so it is cached next to the location cache
+/// and only looked up again when the coordinates change.
+#[derive(Default, Deserialize, Serialize, Debug, Savefile)]
+pub struct GridPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub forecast: String,
+    pub forecast_hourly: String,
+    pub forecast_grid_data: String,
+    pub station: String,
+    pub time_zone: String,
+    pub created_at: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct Points {
+    properties: PointsProperties,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PointsProperties {
+    forecast: String,
+    forecast_hourly: String,
+    forecast_grid_data: String,
+    observation_stations: String,
+    time_zone: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Stations {
+    features: Vec<StationFeature>,
+}
+
+#[derive(Deserialize, Debug)]
+struct StationFeature {
+    properties: StationProperties,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct StationProperties {
+    station_identifier: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Forecast {
+    properties: ForecastProperties,
+}
+
+#[derive(Deserialize, Debug)]
+struct ForecastProperties {
+    periods: Vec<ForecastPeriod>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ForecastPeriod {
+    start_time: String,
+    is_daytime: bool,
+    temperature: f64,
+    #[serde(default)]
+    probability_of_precipitation: QuantitativeValue,
+    #[serde(default)]
+    relative_humidity: QuantitativeValue,
+    #[serde(default)]
+    wind_speed: String,
+    #[serde(default)]
+    wind_direction: String,
+    #[serde(default)]
+    icon: String,
+    #[serde(default)]
+    short_forecast: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct QuantitativeValue {
+    value: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GridData {
+    properties: GridDataProperties,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct GridDataProperties {
+    quantitative_precipitation: GridSeries,
+    wind_gust: GridSeries,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct GridSeries {
+    values: Vec<GridValue>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GridValue {
+    valid_time: String,
+    value: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Observation {
+    properties: ObservationProperties,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ObservationProperties {
+    #[serde(default)]
+    sea_level_pressure: QuantitativeValue,
+    #[serde(default)]
+    barometric_pressure: QuantitativeValue,
+}
+
+impl GridPoint {
+    /// Resolves coordinates to an NWS gridpoint, using cached data if available.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate
+    /// * `lon` - Longitude coordinate
+    /// * `s` - Settings with the endpoints and cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns the gridpoint forecast URLs, nearest observation station and timezone.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the coordinates are outside NWS coverage or the API request fails.
+    pub async fn get_cached(lat: f64, lon: f64, s: &Settings) -> Result<Self> {
+        let key = format!("{lat},{lon}");
+        let header = CacheHeader::new("gridpoint", 0, key.as_str()).with_provider("nws");
+
+        let gp: GridPoint = cache::load(&s.cache.dir(), &header, &key).unwrap_or_default();
+        if gp.latitude == lat && gp.longitude == lon && gp.created_at > 0 {
+            return Ok(gp);
+        }
+
+        let data = Self::fetch(lat, lon, &s.endpoints).await?;
+
+        match cache::save(&s.cache.dir(), &header, &key, &data) {
+            Ok(_) => {},
+            Err(e) => eprintln!("Unable to save gridpoint data to disk: {e:#?}"),
+        }
+
+        Ok(data)
+    }
+
+    /// Looks up the gridpoint and nearest observation station from the NWS API.
+    async fn fetch(lat: f64, lon: f64, endpoints: &EndpointsConfig) -> Result<Self> {
+        // NWS redirects requests with more than 4 decimals
+        let api_url = format!("{}/points/{lat:.4},{lon:.4}", endpoints::url(endpoints, Endpoint::Nws));
+
+        let body = client::get_with_retry(&api_url, 2).await.with_context(|| {
+            format!("Unable to resolve {lat},{lon} to an NWS gridpoint (NWS only covers the United States)")
+        })?;
+        let points: Points = serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Unable to parse NWS points response JSON".to_string()))?;
+
+        let body = client::get_with_retry(&points.properties.observation_stations, 2)
+            .await
+            .with_context(|| "Unable to fetch NWS observation stations")?;
+        let stations: Stations = serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Unable to parse NWS stations response JSON".to_string()))?;
+
+        Ok(GridPoint {
+            latitude: lat,
+            longitude: lon,
+            forecast: points.properties.forecast,
+            forecast_hourly: points.properties.forecast_hourly,
+            forecast_grid_data: points.properties.forecast_grid_data,
+            station: stations
+                .features
+                .into_iter()
+                .next()
+                .map(|f| f.properties.station_identifier)
+                .unwrap_or_default(),
+            time_zone: points.properties.time_zone,
+            created_at: utils::get_now(),
+        })
+    }
+}
+
+impl WeatherProvider for Nws {
+    /// Fetches fresh weather data from the US National Weather Service API.
+    ///
+    /// Combines the hourly forecast (current conditions and hourly data), the
+    /// 12-hour forecast (daily highs, lows and the narrative), the raw gridpoint
+    /// data (precipitation amounts and wind gusts), and the latest observation
+    /// from the nearest station (pressure). Sunrise and sunset are calculated locally.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate for the weather location
+    /// * `lon` - Longitude coordinate for the weather location
+    /// * `_timezone` - Unused, NWS reports local times itself
+    /// * `s` - Settings with the endpoints and cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns weather data mapped into the provider-neutral model.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The coordinates are outside NWS coverage
+    /// - Any of the forecast requests fail
+    /// - The JSON responses cannot be parsed
+    async fn fetch(lat: f64, lon: f64, _timezone: &str, s: &Settings) -> Result<Weather> {
+        let gridpoint = GridPoint::get_cached(lat, lon, s).await?;
+        let si = vec![("units", "si")];
+
+        let body = client::get_with_retry(&utils::urls::builder(&gridpoint.forecast_hourly, si.clone()), 2)
+            .await
+            .with_context(|| "Unable to fetch the NWS hourly forecast")?;
+        let hourly: Forecast = serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Unable to parse NWS hourly forecast JSON".to_string()))?;
+
+        let body = client::get_with_retry(&utils::urls::builder(&gridpoint.forecast, si), 2)
+            .await
+            .with_context(|| "Unable to fetch the NWS 12-hour forecast")?;
+        let periods: Forecast = serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Unable to parse NWS 12-hour forecast JSON".to_string()))?;
+
+        let body = client::get_with_retry(&gridpoint.forecast_grid_data, 2)
+            .await
+            .with_context(|| "Unable to fetch the NWS gridpoint data")?;
+        let grid: GridData = serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Unable to parse NWS gridpoint data JSON".to_string()))?;
+
+        // The latest observation only adds pressure, so a failure here is not fatal
+        let observation_url = format!(
+            "{}/stations/{}/observations/latest",
+            endpoints::url(&s.endpoints, Endpoint::Nws),
+            gridpoint.station
+        );
+        let pressure = client::get(&observation_url)
+            .await
+            .ok()
+            .and_then(|body| serde_json::from_str::<Observation>(&body).ok())
+            .and_then(|o| o.properties.sea_level_pressure.value.or(o.properties.barometric_pressure.value))
+            .map(|pascal| (pascal / 10.0).round() / 10.0)
+            .unwrap_or(0.0);
+
+        let mut weather = Self::into_weather(hourly, periods, grid, lat, lon)?;
+        weather.current.pressure_msl = pressure;
+        weather.timezone = gridpoint.time_zone;
+
+        Ok(weather)
+    }
+}
+
+impl Nws {
+    /// Maps the NWS forecasts into the provider-neutral weather model.
+    fn into_weather(
+        hourly: Forecast,
+        periods: Forecast,
+        grid: GridData,
+        lat: f64,
+        lon: f64,
+    ) -> Result<Weather> {
+        let hours = hourly.properties.periods;
+        let first =
+            hours.first().ok_or_else(|| Error::Parse("NWS returned no hourly forecast data".to_string()))?;
+        let timezone = *DateTime::parse_from_rfc3339(&first.start_time)
+            .with_context(|| format!("Invalid NWS forecast time: {}", first.start_time))?
+            .offset();
+
+        let precipitation = Self::hourly_values(&grid.properties.quantitative_precipitation, timezone, true);
+        let gusts = Self::hourly_values(&grid.properties.wind_gust, timezone, false);
+
+        let mut weather = Weather {
+            timezone: timezone.to_string(),
+            utc_offset_seconds: timezone.local_minus_utc(),
+            latitude: lat,
+            longitude: lon,
+            ..Default::default()
+        };
+
+        for period in &hours {
+            let Ok(start) = DateTime::parse_from_rfc3339(&period.start_time) else { continue };
+            let time = start.with_timezone(&timezone).format("%Y-%m-%dT%H:%M").to_string();
+
+            weather.hourly.precipitation.push(precipitation.get(&time).copied().unwrap_or(0.0));
+            weather.hourly.time.push(time);
+            weather.hourly.temperature_2m.push(period.temperature);
+            weather
+                .hourly
+                .precipitation_probability
+                .push(period.probability_of_precipitation.value.unwrap_or(0.0).round() as i32);
+            weather.hourly.weather_code.push(mappings::nws_icon2meteo_code(&period.icon));
+        }
+
+        let wind_speed = Self::parse_wind_speed(&first.wind_speed);
+        let humidity = first.relative_humidity.value.unwrap_or(0.0);
+        let current_hour = weather.hourly.time.first().cloned().unwrap_or_default();
+
+        weather.current = Current {
+            apparent_temperature: conversions::apparent_temperature(first.temperature, humidity, wind_speed),
+            interval: 3600,
+            precipitation: precipitation.get(&current_hour).copied().unwrap_or(0.0),
+            pressure_msl: 0.0,
+            relative_humidity_2m: humidity.round() as i32,
+            temperature_2m: first.temperature,
+            weather_code: mappings::nws_icon2meteo_code(&first.icon),
+            wind_direction_10m: mappings::compass2degrees(&first.wind_direction),
+            wind_speed_10m: wind_speed,
+            wind_gusts_10m: gusts.get(&current_hour).copied().unwrap_or(wind_speed).max(wind_speed),
+        };
+
+        weather.narrative = periods.properties.periods.first().map(|p| p.short_forecast.clone());
+
+        // Group the 12-hour periods by local date: daytime periods give the high
+        // and nighttime periods the low
+        let mut days: BTreeMap<NaiveDate, Vec<&ForecastPeriod>> = BTreeMap::new();
+        for period in &periods.properties.periods {
+            if let Ok(start) = DateTime::parse_from_rfc3339(&period.start_time) {
+                days.entry(start.with_timezone(&timezone).date_naive()).or_default().push(period);
+            }
+        }
+
+        for (date, day_periods) in days.into_iter().take(7) {
+            let day_str = date.format("%Y-%m-%d").to_string();
+            let hourly_temps: Vec<f64> = weather
+                .hourly
+                .time
+                .iter()
+                .zip(&weather.hourly.temperature_2m)
+                .filter(|(time, _)| time.starts_with(&day_str))
+                .map(|(_, temp)| *temp)
+                .collect();
+
+            let high = day_periods
+                .iter()
+                .find(|p| p.is_daytime)
+                .map(|p| p.temperature)
+                .or_else(|| hourly_temps.iter().copied().reduce(f64::max))
+                .unwrap_or_default();
+            let low = day_periods
+                .iter()
+                .find(|p| !p.is_daytime)
+                .map(|p| p.temperature)
+                .or_else(|| hourly_temps.iter().copied().reduce(f64::min))
+                .unwrap_or_default();
+
+            let day_precipitation: Vec<f64> = precipitation
+                .iter()
+                .filter(|(time, _)| time.starts_with(&day_str))
+                .map(|(_, v)| *v)
+                .collect();
+
+            let (sunrise, sunset) = solar::sunrise_sunset(lat, lon, date, weather.utc_offset_seconds);
+
+            weather.daily.time.push(day_str);
+            weather
+                .daily
+                .weather_code
+                .push(day_periods.iter().map(|p| mappings::nws_icon2meteo_code(&p.icon)).max().unwrap_or(-1));
+            weather.daily.sunrise.push(sunrise);
+            weather.daily.sunset.push(sunset);
+            weather.daily.uv_index_max.push(0.0);
+            weather
+                .daily
+                .precipitation_sum
+                .push((day_precipitation.iter().sum::<f64>() * 10.0).round() / 10.0);
+            weather
+                .daily
+                .precipitation_hours
+                .push(day_precipitation.iter().filter(|v| **v > 0.0).count() as f64);
+            weather.daily.precipitation_probability_max.push(
+                day_periods
+                    .iter()
+                    .map(|p| p.probability_of_precipitation.value.unwrap_or(0.0).round() as i32)
+                    .max()
+                    .unwrap_or(0),
+            );
+            weather.daily.temperature_2m_max.push(high.max(low));
+            weather.daily.temperature_2m_min.push(low.min(high));
+        }
+
+        if weather.daily.time.is_empty() {
+            return Err(Error::Parse("NWS returned no 12-hour forecast data".to_string()).into());
+        }
+
+        Ok(weather)
+    }
+
+    /// Expands a gridpoint series of ISO8601 intervals into hourly local values.
+    ///
+    /// Gridpoint values are valid for intervals such as `2024-07-01T12:00:00+00:00/PT6H`.
+    /// Accumulated values (precipitation) are split evenly across the hours of the
+    /// interval, while instantaneous values (gusts) are repeated for every hour.
+    fn hourly_values(series: &GridSeries, timezone: FixedOffset, accumulated: bool) -> BTreeMap<String, f64> {
+        let mut values = BTreeMap::new();
+
+        for entry in &series.values {
+            let Some((start, duration)) = entry.valid_time.split_once('/') else { continue };
+            let Ok(start) = DateTime::parse_from_rfc3339(start) else { continue };
+            let hours = Self::parse_duration_hours(duration).max(1);
+            let value = entry.value.unwrap_or(0.0);
+            let hourly_value = if accumulated { value / hours as f64 } else { value };
+
+            for hour in 0..hours {
+                let time = (start + Duration::hours(hour)).with_timezone(&timezone);
+                values.insert(time.format("%Y-%m-%dT%H:%M").to_string(), hourly_value);
+            }
+        }
+
+        values
+    }
+
+    /// Parses the hours of an ISO8601 duration such as `PT6H` or `P1DT12H`.
+    fn parse_duration_hours(duration: &str) -> i64 {
+        let mut hours = 0;
+        let mut number = String::new();
+
+        for c in duration.chars() {
+            match c {
+                '0'..='9' => number.push(c),
+                'D' => hours += number.parse::<i64>().unwrap_or(0) * 24,
+                'H' => hours += number.parse::<i64>().unwrap_or(0),
+                _ => {},
+            }
+            if !c.is_ascii_digit() {
+                number.clear();
+            }
+        }
+
+        hours
+    }
+
+    /// Parses an NWS wind speed such as "15 km/h" or "10 to 20 km/h" into the first value.
+    fn parse_wind_speed(wind_speed: &str) -> f64 {
+        wind_speed.split_whitespace().find_map(|part| part.parse::<f64>().ok()).unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::CacheConfig;
+    use crate::utils::test_server::{self, Response};
+
+    #[test]
+    fn test_parse_duration_hours() {
+        assert_eq!(Nws::parse_duration_hours("PT1H"), 1);
+        assert_eq!(Nws::parse_duration_hours("PT6H"), 6);
+        assert_eq!(Nws::parse_duration_hours("P1DT12H"), 36);
+        assert_eq!(Nws::parse_duration_hours("PT30M"), 0);
+    }
+
+    #[test]
+    fn test_hourly_values() {
+        let series = GridSeries {
+            values: vec![GridValue {
+                valid_time: "2025-07-01T12:00:00+00:00/PT3H".to_string(),
+                value: Some(3.0),
+            }],
+        };
+        let timezone = FixedOffset::west_opt(5 * 3600).unwrap();
+
+        let precipitation = Nws::hourly_values(&series, timezone, true);
+        assert_eq!(precipitation.len(), 3);
+        assert_eq!(precipitation.get("2025-07-01T07:00"), Some(&1.0));
+        assert_eq!(precipitation.get("2025-07-01T09:00"), Some(&1.0));
+
+        let gusts = Nws::hourly_values(&series, timezone, false);
+        assert_eq!(gusts.get("2025-07-01T08:00"), Some(&3.0));
+    }
+
+    #[test]
+    fn test_icon_mapping() {
+        assert_eq!(mappings::nws_icon2meteo_code("https://api.weather.gov/icons/land/day/skc?size=small"), 0);
+        assert_eq!(
+            mappings::nws_icon2meteo_code(
+                "https://api.weather.gov/icons/land/night/rain_showers,30/tsra_sct,40"
+            ),
+            95
+        );
+        assert_eq!(mappings::compass2degrees("NNW"), 338);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_local_server() {
+        let base_url = test_server::serve_linked(|base_url| {
+            let fixture = |body: &str| Response::ok(body.replace("{base_url}", base_url));
+            vec![
+                ("/points/38.8894,-77.0352", fixture(include_str!("../../fixtures/nws/points.json"))),
+                ("/gridpoints/LWX/97,71/stations", fixture(include_str!("../../fixtures/nws/stations.json"))),
+                ("/gridpoints/LWX/97,71/forecast", fixture(include_str!("../../fixtures/nws/forecast.json"))),
+                (
+                    "/gridpoints/LWX/97,71/forecast/hourly",
+                    fixture(include_str!("../../fixtures/nws/forecast_hourly.json")),
+                ),
+                ("/gridpoints/LWX/97,71", fixture(include_str!("../../fixtures/nws/gridpoint.json"))),
+                (
+                    "/stations/KDCA/observations/latest",
+                    fixture(include_str!("../../fixtures/nws/observation.json")),
+                ),
+            ]
+        });
+        let settings = Settings {
+            endpoints: EndpointsConfig { nws: Some(base_url), ..Default::default() },
+            cache: CacheConfig { dir: Some(test_server::cache_dir()), ..Default::default() },
+            ..Default::default()
+        };
+
+        let weather = Nws::fetch(38.8894, -77.0352, "", &settings).await.unwrap();
+
+        assert_eq!(weather.timezone, "America/New_York");
+        assert_eq!(weather.current.pressure_msl, 1015.6);
+        assert_eq!(weather.current.temperature_2m, 31.0);
+        assert_eq!(weather.current.wind_gusts_10m, 31.5);
+        assert_eq!(weather.hourly.time, vec!["2025-07-01T14:00", "2025-07-01T15:00"]);
+        assert_eq!(weather.hourly.precipitation, vec![0.5, 0.5]);
+        assert_eq!(weather.daily.temperature_2m_max, vec![33.0]);
+        assert_eq!(weather.daily.temperature_2m_min, vec![23.0]);
+        assert_eq!(weather.narrative.as_deref(), Some("Chance Showers And Thunderstorms"));
+    }
+}
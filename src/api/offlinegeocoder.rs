@@ -0,0 +1,513 @@
+use crate::api::client;
+use crate::api::geolocation::{GeoLocation, Results};
+use crate::api::location::*;
+use crate::error::Error;
+use crate::settings::GeocodingConfig;
+use crate::Settings;
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// City databases already loaded, so each is only read once per run.
+static LOADED: Mutex<Vec<(PathBuf, Arc<OfflineGeocoder>)>> = Mutex::new(Vec::new());
+
+/// File name of the GeoNames cities database looked for when no path is configured.
+const DATABASE_FILE: &str = "cities15000.txt";
+
+/// File name of the GeoNames state and province names, read from beside the cities database.
+const ADMIN1_FILE: &str = "admin1CodesASCII.txt";
+
+/// File name of the GeoNames archive holding the cities database.
+const DATABASE_ARCHIVE: &str = "cities15000.zip";
+
+/// Geocoder backed by a GeoNames city database on disk, for use without connectivity.
+///
+/// Reads the tab-separated GeoNames format, e.g. `cities15000.txt` from
+/// <https://download.geonames.org/export/dump/>, as installed by `outside geocoder install`.
+/// State and province names are read from `admin1CodesASCII.txt` in the same directory if
+/// present; otherwise regions are matched on their GeoNames codes.
+#[derive(Debug, Default)]
+pub struct OfflineGeocoder {
+    cities: Vec<City>,
+}
+
+#[derive(Debug, Clone)]
+struct City {
+    name: String,
+    ascii_name: String,
+    alternate_names: Vec<String>,
+    latitude: f64,
+    longitude: f64,
+    country_code: String,
+    admin1: String,
+    population: u64,
+    timezone: String,
+}
+
+/// How closely a city's names match a query, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Match {
+    Exact,
+    Prefix,
+    Fuzzy,
+}
+
+impl OfflineGeocoder {
+    /// Finds the city database, from the configuration or the default locations.
+    ///
+    /// Without a configured path, `cities15000.txt` is looked for in the data directory
+    /// (e.g. `~/.local/share/outside`) and then beside the executable.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Geocoding settings from the configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns the path of the database, or `None` if it is not installed.
+    pub fn database_path(config: &GeocodingConfig) -> Option<PathBuf> {
+        if let Some(database) = &config.database {
+            return Some(PathBuf::from(database));
+        }
+
+        let data_dir = dirs_next::data_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(DATABASE_FILE));
+        let exe_dir =
+            std::env::current_exe().ok().and_then(|exe| exe.parent().map(|dir| dir.join(DATABASE_FILE)));
+
+        [data_dir, exe_dir].into_iter().flatten().find(|path| path.is_file())
+    }
+
+    /// Returns where `install` puts the city database.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Geocoding settings from the configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns the configured database path, or else `cities15000.txt` in the data
+    /// directory, or `None` if there is no data directory.
+    pub fn install_path(config: &GeocodingConfig) -> Option<PathBuf> {
+        config
+            .database
+            .as_ref()
+            .map(PathBuf::from)
+            .or_else(|| dirs_next::data_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(DATABASE_FILE)))
+    }
+
+    /// Downloads the GeoNames city database and region names, and installs them.
+    ///
+    /// The region names are written beside the database, where `load` looks for them.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - Base URL of the GeoNames export, see `Endpoint::GeoNames`
+    /// * `path` - Where to install the cities file, see `install_path`
+    ///
+    /// # Returns
+    ///
+    /// Returns the geocoder for the installed database.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Downloading either file fails
+    /// - The archive doesn't hold a cities database with any cities
+    /// - The files cannot be written
+    pub async fn install(base_url: &str, path: &Path) -> Result<Self> {
+        let archive = client::download(&format!("{base_url}/{DATABASE_ARCHIVE}")).await?;
+        let cities = Self::extract(&archive)?;
+        let admin1_codes = client::download(&format!("{base_url}/{ADMIN1_FILE}")).await?;
+        let admin1_codes = String::from_utf8_lossy(&admin1_codes);
+
+        let geocoder = Self::parse(&cities, &admin1_codes);
+        if geocoder.is_empty() {
+            return Err(Error::Parse(format!("{DATABASE_ARCHIVE} holds no cities")).into());
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+        }
+        std::fs::write(path, cities).with_context(|| format!("Unable to write {}", path.display()))?;
+        let admin1_path = path.with_file_name(ADMIN1_FILE);
+        std::fs::write(&admin1_path, admin1_codes.as_bytes())
+            .with_context(|| format!("Unable to write {}", admin1_path.display()))?;
+
+        Ok(geocoder)
+    }
+
+    /// Reads the cities database out of the GeoNames zip archive.
+    fn extract(archive: &[u8]) -> Result<String> {
+        let invalid = || Error::Parse(format!("{DATABASE_ARCHIVE} is not a valid GeoNames archive"));
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(archive)).with_context(invalid)?;
+        let mut cities = String::new();
+        zip.by_name(DATABASE_FILE)
+            .with_context(invalid)?
+            .read_to_string(&mut cities)
+            .with_context(invalid)?;
+
+        Ok(cities)
+    }
+
+    /// Returns the number of cities in the database.
+    pub fn len(&self) -> usize {
+        self.cities.len()
+    }
+
+    /// Checks whether the database has no cities.
+    pub fn is_empty(&self) -> bool {
+        self.cities.is_empty()
+    }
+
+    /// Loads the city database, and the region names beside it if present.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the GeoNames cities file
+    ///
+    /// # Returns
+    ///
+    /// Returns the geocoder on success, or an error if the database cannot be read.
+    pub fn load(path: &Path) -> Result<Self> {
+        let cities = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read the city database {}", path.display()))?;
+        let admin1_codes = path
+            .parent()
+            .and_then(|dir| std::fs::read_to_string(dir.join(ADMIN1_FILE)).ok())
+            .unwrap_or_default();
+
+        Ok(Self::parse(&cities, &admin1_codes))
+    }
+
+    /// Loads the city database like `load`, but only once per run and without blocking
+    /// the async runtime.
+    ///
+    /// The database is tens of megabytes, so it is read and parsed on a blocking thread,
+    /// and shared by every later lookup.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the GeoNames cities file
+    ///
+    /// # Returns
+    ///
+    /// Returns the shared geocoder on success, or an error if the database cannot be read.
+    pub async fn load_shared(path: PathBuf) -> Result<Arc<Self>> {
+        let loaded = |path: &Path| {
+            LOADED
+                .lock()
+                .ok()?
+                .iter()
+                .find(|(loaded, _)| loaded == path)
+                .map(|(_, geocoder)| geocoder.clone())
+        };
+        if let Some(geocoder) = loaded(&path) {
+            return Ok(geocoder);
+        }
+
+        let load_path = path.clone();
+        let geocoder = Arc::new(
+            tokio::task::spawn_blocking(move || Self::load(&load_path))
+                .await
+                .context("Loading the city database was interrupted")??,
+        );
+
+        if let Ok(mut loaded) = LOADED.lock() {
+            loaded.push((path, geocoder.clone()));
+        }
+        Ok(geocoder)
+    }
+
+    /// Parses the GeoNames cities and admin1 code files, skipping malformed lines.
+    pub fn parse(cities: &str, admin1_codes: &str) -> Self {
+        let regions: HashMap<&str, &str> = admin1_codes
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                Some((fields.next()?, fields.next()?))
+            })
+            .collect();
+
+        let cities = cities
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                if fields.len() < 15 {
+                    return None;
+                }
+
+                let country_code = fields[8].to_string();
+                let admin1 = regions
+                    .get(format!("{country_code}.{}", fields[10]).as_str())
+                    .map_or_else(|| fields[10].to_string(), |name| name.to_string());
+
+                Some(City {
+                    name: fields[1].to_string(),
+                    ascii_name: fields[2].to_string(),
+                    alternate_names: fields[3]
+                        .split(',')
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_lowercase)
+                        .collect(),
+                    latitude: fields[4].parse().ok()?,
+                    longitude: fields[5].parse().ok()?,
+                    country_code,
+                    admin1,
+                    population: fields[14].parse().unwrap_or_default(),
+                    timezone: fields.get(17).unwrap_or(&"").to_string(),
+                })
+            })
+            .collect();
+
+        OfflineGeocoder { cities }
+    }
+
+    /// Searches the database for cities matching a name.
+    ///
+    /// Exact matches on the name or any alternate name are preferred, then cities whose
+    /// name starts with the query, then names within a couple of typos of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The city name to search for
+    /// * `country_code` - The country code to narrow down the search, or empty for any country
+    ///
+    /// # Returns
+    ///
+    /// Returns the matches of the best kind found, most populous first.
+    pub fn search(&self, name: &str, country_code: &str) -> Vec<Results> {
+        let query = name.trim().to_lowercase();
+
+        let mut matches: Vec<(Match, &City)> = self
+            .cities
+            .iter()
+            .filter(|city| country_code.is_empty() || city.country_code.eq_ignore_ascii_case(country_code))
+            .filter_map(|city| city.matches(&query).map(|quality| (quality, city)))
+            .collect();
+
+        let best = matches.iter().map(|(quality, _)| *quality).min();
+        matches.retain(|(quality, _)| Some(*quality) == best);
+        matches.sort_by_key(|(_, city)| std::cmp::Reverse(city.population));
+
+        matches.into_iter().map(|(_, city)| city.to_result()).collect()
+    }
+
+    /// Looks up a place by name, narrowed down by region and country.
+    ///
+    /// Ambiguous names are handled like the online geocoder, see `GeoLocation::resolve`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The city name to search for
+    /// * `region` - Optional state or province to narrow down the search
+    /// * `country_code` - The country code to narrow down the search
+    ///
+    /// # Returns
+    ///
+    /// Returns `LocationData` containing the location details and coordinates.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - No city in the database matches
+    /// - Several cities match and no region was given, as an `AmbiguousLocation`
+    pub fn resolve(&self, name: &str, region: Option<&str>, country_code: &str) -> Result<LocationData> {
+        let query = match region {
+            Some(region) => format!("{name}, {region}, {country_code}"),
+            None => format!("{name}, {country_code}"),
+        };
+
+        GeoLocation::select(self.search(name, country_code), name, region, &query).map(LocationData::from)
+    }
+
+    /// Finds the city nearest to a point, for naming coordinates without a network.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude in decimal degrees
+    /// * `lon` - Longitude in decimal degrees
+    ///
+    /// # Returns
+    ///
+    /// Returns the city, or `None` if the database is empty.
+    pub fn nearest(&self, lat: f64, lon: f64) -> Option<Results> {
+        // An equirectangular approximation is plenty to rank nearby cities
+        let distance = |city: &City| {
+            let x = (city.longitude - lon).to_radians() * ((city.latitude + lat) / 2.0).to_radians().cos();
+            let y = (city.latitude - lat).to_radians();
+            x * x + y * y
+        };
+
+        self.cities.iter().min_by(|a, b| distance(a).total_cmp(&distance(b))).map(City::to_result)
+    }
+}
+
+impl City {
+    /// Checks how well this city's names match a lowercase query.
+    fn matches(&self, query: &str) -> Option<Match> {
+        let name = self.name.to_lowercase();
+        let ascii_name = self.ascii_name.to_lowercase();
+        let names = || [&name, &ascii_name].into_iter().chain(&self.alternate_names);
+
+        if names().any(|candidate| candidate == query) {
+            Some(Match::Exact)
+        } else if query.chars().count() >= 3 && names().any(|candidate| candidate.starts_with(query)) {
+            Some(Match::Prefix)
+        } else {
+            // Allow one typo in short names and two in longer ones
+            let allowed = if query.chars().count() <= 5 { 1 } else { 2 };
+            [&name, &ascii_name]
+                .into_iter()
+                .any(|candidate| edit_distance(candidate, query) <= allowed)
+                .then_some(Match::Fuzzy)
+        }
+    }
+
+    fn to_result(&self) -> Results {
+        Results {
+            name: self.name.clone(),
+            country_code: self.country_code.clone(),
+            latitude: self.latitude,
+            longitude: self.longitude,
+            admin1: Some(self.admin1.clone()).filter(|admin1| !admin1.is_empty()),
+            admin2: None,
+            population: Some(self.population).filter(|population| *population > 0),
+            timezone: Some(self.timezone.clone()).filter(|timezone| !timezone.is_empty()),
+        }
+    }
+}
+
+impl Location for OfflineGeocoder {
+    /// Fetches location data from the offline city database in its default location.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The city name to search for
+    /// * `c` - The country code to narrow down the search
+    /// * `s` - Settings with the path of the city database
+    ///
+    /// # Returns
+    ///
+    /// Returns `LocationData` containing the location details and coordinates.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The city database is not installed or cannot be read
+    /// - No city in the database matches
+    /// - Several cities match, as an `AmbiguousLocation`
+    async fn fetch(n: &str, c: &str, s: &Settings) -> Result<LocationData> {
+        let path = Self::database_path(&s.geocoding).ok_or_else(|| {
+            Error::Config(format!(
+                "The offline city database {DATABASE_FILE} is not installed, run `outside geocoder install`"
+            ))
+        })?;
+
+        Self::load_shared(path).await?.resolve(n, None, c)
+    }
+}
+
+/// Counts the single-character insertions, deletions and substitutions between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::geolocation::AmbiguousLocation;
+    use crate::utils::test_server::{self, Response};
+
+    const CITIES: &str = include_str!("../../fixtures/geonames/cities.txt");
+    const ADMIN1_CODES: &str = include_str!("../../fixtures/geonames/admin1CodesASCII.txt");
+
+    #[test]
+    fn test_search() {
+        let geocoder = OfflineGeocoder::parse(CITIES, ADMIN1_CODES);
+
+        let results = geocoder.search("köln", "DE");
+        assert_eq!(results[0].name, "Cologne");
+        assert_eq!(results[0].admin1.as_deref(), Some("North Rhine-Westphalia"));
+
+        assert_eq!(geocoder.search("Calg", "").len(), 1);
+        assert_eq!(geocoder.search("Edmonten", "CA")[0].name, "Edmonton");
+        assert!(geocoder.search("Calgary", "US").is_empty());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let geocoder = OfflineGeocoder::parse(CITIES, ADMIN1_CODES);
+
+        let error = geocoder.resolve("Springfield", None, "US").unwrap_err();
+        assert_eq!(error.downcast_ref::<AmbiguousLocation>().unwrap().candidates.len(), 3);
+
+        let location = geocoder.resolve("Springfield", Some("Illinois"), "US").unwrap();
+        assert_eq!((location.latitude, location.longitude), (39.80172, -89.64371));
+
+        let location = geocoder.resolve("Portland", None, "US").unwrap();
+        assert_eq!(location.location, "Portland, US");
+        assert_eq!(location.latitude, 45.52345);
+
+        let nearest = geocoder.nearest(51.2, -114.5).unwrap();
+        assert_eq!((nearest.name.as_str(), nearest.country_code.as_str()), ("Calgary", "CA"));
+        assert_eq!(nearest.timezone.as_deref(), Some("America/Edmonton"));
+    }
+
+    #[tokio::test]
+    async fn test_load_shared() {
+        let path = std::env::temp_dir().join(format!(
+            "{}-cities-{}.txt",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        std::fs::write(&path, CITIES).unwrap();
+
+        let first = OfflineGeocoder::load_shared(path.clone()).await.unwrap();
+        let second = OfflineGeocoder::load_shared(path.clone()).await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(OfflineGeocoder::load_shared(path.with_extension("missing")).await.is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_install() {
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        archive.start_file(DATABASE_FILE, zip::write::SimpleFileOptions::default()).unwrap();
+        std::io::Write::write_all(&mut archive, CITIES.as_bytes()).unwrap();
+        let archive = archive.finish().unwrap().into_inner();
+
+        let base_url = test_server::serve_responses(vec![
+            ("/cities15000.zip", Response::ok(archive)),
+            ("/admin1CodesASCII.txt", Response::ok(ADMIN1_CODES)),
+        ]);
+        let dir =
+            std::env::temp_dir().join(format!("{}-geocoder-{}", env!("CARGO_PKG_NAME"), std::process::id()));
+        let path = dir.join(DATABASE_FILE);
+
+        let installed = OfflineGeocoder::install(&base_url, &path).await.unwrap();
+        assert_eq!(installed.len(), OfflineGeocoder::parse(CITIES, ADMIN1_CODES).len());
+
+        let loaded = OfflineGeocoder::load(&path).unwrap();
+        assert_eq!(loaded.search("köln", "DE")[0].admin1.as_deref(), Some("North Rhine-Westphalia"));
+
+        assert!(OfflineGeocoder::install(&format!("{base_url}/missing"), &path).await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
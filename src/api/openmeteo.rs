@@ -0,0 +1,176 @@
+use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
+use crate::api::weather::*;
+use crate::error::Error;
+use crate::settings::EndpointsConfig;
+use crate::utils;
+use crate::Settings;
+
+use anyhow::{Context, Result};
+
+/// Weather provider backed by the Open-Meteo forecast API.
+///
+/// The Open-Meteo response is deserialized directly into the `Weather` model,
+/// since the model's field names follow the Open-Meteo variable names.
+pub struct OpenMeteo;
+
+impl WeatherProvider for OpenMeteo {
+    /// Fetches fresh weather data from the Open-Meteo API.
+    ///
+    /// Constructs the API URL with the appropriate parameters for current weather,
+    /// 7-day forecast, a 6-hour precipitation nowcast in 15-minute steps, and metric
+    /// units, then makes the HTTP request.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate for the weather location
+    /// * `lon` - Longitude coordinate for the weather location
+    /// * `_timezone` - Unused, Open-Meteo reports local times itself
+    /// * `s` - Settings with the endpoints
+    ///
+    /// # Returns
+    ///
+    /// Returns parsed weather data on success, or an error if the request fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The HTTP request fails
+    /// - The JSON response cannot be parsed
+    /// - The API returns an error response
+    async fn fetch(lat: f64, lon: f64, _timezone: &str, s: &Settings) -> Result<Weather> {
+        Self::fetch_batch(&[(lat, lon)], &s.endpoints)
+            .await?
+            .pop()
+            .ok_or_else(|| Error::Parse("The Open-Meteo API returned no forecast".to_string()).into())
+    }
+}
+
+impl OpenMeteo {
+    /// Fetches fresh weather data for several locations in a single request.
+    ///
+    /// Open-Meteo accepts comma-separated lists of coordinates and returns one forecast
+    /// per location, in the same order.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - Latitude and longitude of each location
+    /// * `endpoints` - Endpoint settings from the configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns the weather data of each location, in the order given.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The HTTP request fails
+    /// - The JSON response cannot be parsed
+    /// - The response has a different number of forecasts than locations
+    pub async fn fetch_batch(points: &[(f64, f64)], endpoints: &EndpointsConfig) -> Result<Vec<Weather>> {
+        let units = utils::unitstrings::UnitStrings::metric();
+
+        // https://api.open-meteo.com/v1/forecast\?latitude\=51.30011\&longitude\=-114.03528\&daily\=weather_code,temperature_2m_max,temperature_2m_min,sunset,sunrise,precipitation_hours,precipitation_probability_max\&hourly\=temperature_2m,precipitation_probability,precipitation\&current\=temperature_2m,apparent_temperature,wind_speed_10m,wind_direction_10m,wind_gusts_10m,precipitation,weather_code,pressure_msl,relative_humidity_2m\&timezone\=America%2FDenver
+        let hourly_fields =
+            ["temperature_2m", "precipitation_probability", "precipitation", "weather_code"].join(",");
+
+        let current_fields = [
+            "temperature_2m",
+            "relative_humidity_2m",
+            "apparent_temperature",
+            "wind_speed_10m",
+            "wind_direction_10m",
+            "wind_gusts_10m",
+            "precipitation",
+            "weather_code",
+            "pressure_msl",
+        ]
+        .join(",");
+
+        let daily_fields = [
+            "sunrise",
+            "sunset",
+            "weather_code",
+            "temperature_2m_max",
+            "temperature_2m_min",
+            "precipitation_sum",
+            "precipitation_hours",
+            "precipitation_probability_max",
+            "uv_index_max",
+        ]
+        .join(",");
+
+        let lat_str = points.iter().map(|(lat, _)| lat.to_string()).collect::<Vec<_>>().join(",");
+        let lon_str = points.iter().map(|(_, lon)| lon.to_string()).collect::<Vec<_>>().join(",");
+
+        let params: Vec<(&str, &str)> = vec![
+            ("latitude", lat_str.as_str()),
+            ("longitude", lon_str.as_str()),
+            ("timezone", "auto"),
+            ("forecast_days", "7"),
+            ("current", current_fields.as_str()),
+            ("daily", daily_fields.as_str()),
+            ("hourly", hourly_fields.as_str()),
+            ("minutely_15", "precipitation"),
+            ("forecast_minutely_15", "24"),
+            ("temperature_unit", units.temperature.as_str()),
+            ("wind_speed_unit", units.wind_speed.as_str()),
+            ("precipitation_unit", units.precipitation.as_str()),
+        ];
+
+        let api_url = endpoints::builder(endpoints, Endpoint::Forecast, params);
+
+        let body = client::get_with_retry(&api_url, 2)
+            .await
+            .with_context(|| "Unable to fetch weather data from the Open-Meteo API endpoint")?;
+
+        let forecasts = Self::parse_batch(&body)?;
+        if forecasts.len() != points.len() {
+            return Err(Error::Parse(format!(
+                "The Open-Meteo API returned {} forecasts for {} locations",
+                forecasts.len(),
+                points.len()
+            ))
+            .into());
+        }
+
+        Ok(forecasts)
+    }
+
+    /// Parses a forecast response, which is a single object for one location and an
+    /// array for several.
+    fn parse_batch(body: &str) -> Result<Vec<Weather>> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Response {
+            Many(Vec<Weather>),
+            One(Box<Weather>),
+        }
+
+        match serde_json::from_str(body)
+            .with_context(|| Error::Parse("Unable to parse weather response JSON".to_string()))?
+        {
+            Response::Many(forecasts) => Ok(forecasts),
+            Response::One(forecast) => Ok(vec![*forecast]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch() {
+        let forecast = |latitude| serde_json::to_string(&Weather { latitude, ..Default::default() }).unwrap();
+
+        let single = OpenMeteo::parse_batch(&forecast(51.0)).unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].latitude, 51.0);
+
+        let batch = OpenMeteo::parse_batch(&format!("[{},{}]", forecast(51.0), forecast(45.5))).unwrap();
+        assert_eq!(batch.iter().map(|w| w.latitude).collect::<Vec<_>>(), vec![51.0, 45.5]);
+
+        assert!(OpenMeteo::parse_batch("{\"error\":true}").is_err());
+    }
+}
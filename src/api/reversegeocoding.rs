@@ -0,0 +1,131 @@
+use crate::api::client;
+use crate::api::endpoints::{self, Endpoint};
+use crate::error::Error;
+use crate::settings::EndpointsConfig;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Response from the Nominatim reverse geocoding API.
+///
+/// Open-Meteo's geocoding API only searches by name, so coordinates are resolved
+/// to a place name with OpenStreetMap's Nominatim instead.
+#[derive(Deserialize, Debug, Default)]
+pub struct ReverseGeocoding {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub address: Address,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Address {
+    pub city: Option<String>,
+    pub town: Option<String>,
+    pub village: Option<String>,
+    pub hamlet: Option<String>,
+    pub municipality: Option<String>,
+    pub county: Option<String>,
+    pub state: Option<String>,
+    #[serde(default)]
+    pub country_code: String,
+}
+
+impl ReverseGeocoding {
+    /// Resolves coordinates to the name of the nearest settlement and its country.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate to resolve
+    /// * `lon` - Longitude coordinate to resolve
+    /// * `endpoints` - Endpoint settings from the configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns the place name and uppercase country code. Either may be empty when
+    /// the coordinates are far from any named place, e.g. at sea.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The API request fails
+    /// - The JSON response cannot be parsed
+    pub async fn fetch(lat: f64, lon: f64, endpoints: &EndpointsConfig) -> Result<(String, String)> {
+        let lat_str = lat.to_string();
+        let lon_str = lon.to_string();
+        let params = vec![
+            ("lat", lat_str.as_str()),
+            ("lon", lon_str.as_str()),
+            ("zoom", "10"),
+            ("accept-language", "en"),
+            ("format", "jsonv2"),
+        ];
+        let api_url = endpoints::builder(endpoints, Endpoint::ReverseGeocoding, params);
+
+        let body = client::get_with_retry(&api_url, 2)
+            .await
+            .with_context(|| format!("Unable to reverse geocode {lat},{lon}"))?;
+
+        let response: ReverseGeocoding = serde_json::from_str(&body)
+            .with_context(|| Error::Parse("Failed to parse reverse geocoding response JSON".to_string()))?;
+
+        Ok(response.place())
+    }
+
+    /// Picks the most specific place name from the response.
+    ///
+    /// # Returns
+    ///
+    /// Returns the place name and uppercase country code, both empty if the
+    /// coordinates could not be resolved.
+    pub fn place(self) -> (String, String) {
+        if self.error.is_some() {
+            return (String::new(), String::new());
+        }
+
+        let address = self.address;
+        let name = [
+            address.city,
+            address.town,
+            address.village,
+            address.hamlet,
+            address.municipality,
+            address.county,
+            address.state,
+        ]
+        .into_iter()
+        .flatten()
+        .find(|name| !name.is_empty())
+        .unwrap_or(self.name);
+
+        (name, address.country_code.to_uppercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place() {
+        let response: ReverseGeocoding = serde_json::from_str(
+            r#"{
+                "name": "Bragg Creek",
+                "display_name": "Bragg Creek, Rocky View County, Alberta, Canada",
+                "address": {
+                    "hamlet": "Bragg Creek",
+                    "county": "Rocky View County",
+                    "state": "Alberta",
+                    "country": "Canada",
+                    "country_code": "ca"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(response.place(), ("Bragg Creek".to_string(), "CA".to_string()));
+
+        let response: ReverseGeocoding = serde_json::from_str(r#"{"error": "Unable to geocode"}"#).unwrap();
+        assert_eq!(response.place(), (String::new(), String::new()));
+    }
+}
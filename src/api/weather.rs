@@ -1,27 +1,73 @@
-use crate::api::client;
+use crate::api::openmeteo::OpenMeteo;
+use crate::settings::Provider;
 use crate::utils;
+use crate::utils::cache::{self, CacheHeader};
 use crate::Settings;
 
 use anyhow::{Context, Result};
-use savefile::prelude::*;
 use savefile_derive::Savefile;
 use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+/// Trait for different weather data providers.
+///
+/// This trait abstracts the forecast lookup, allowing different weather services
+/// to be used interchangeably. Every implementation produces the same provider-neutral
+/// `Weather` model, always in metric units, which is what `Context::build` consumes.
+pub trait WeatherProvider {
+    /// Fetches current conditions, a 7-day daily forecast and an hourly forecast.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate for the weather location
+    /// * `lon` - Longitude coordinate for the weather location
+    /// * `timezone` - IANA timezone of the location, or empty if unknown
+    /// * `s` - Settings with the endpoints and cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns `Weather` data in metric units.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails or the response cannot be parsed.
+    fn fetch(
+        lat: f64,
+        lon: f64,
+        timezone: &str,
+        s: &Settings,
+    ) -> impl Future<Output = Result<Weather>> + Send;
+}
 
 #[derive(Default, Serialize, Deserialize, Debug, Savefile)]
 pub struct Weather {
     pub current: Current,
+    #[serde(default)]
     pub current_units: CurrentUnits,
+    #[serde(default)]
     pub elevation: f64,
     pub timezone: String,
     pub utc_offset_seconds: i32,
     pub daily: Daily,
+    #[serde(default)]
     pub daily_units: DailyUnits,
     pub hourly: Hourly,
+    #[serde(default)]
     pub hourly_units: HourlyUnits,
+    #[serde(default)]
+    pub minutely_15: Minutely15,
     pub latitude: f64,
     pub longitude: f64,
     #[serde(default)]
+    pub narrative: Option<String>,
+    #[serde(default)]
+    pub provider: String,
+    #[serde(default)]
     pub created_at: u64,
+    /// Why older cached data is shown instead of a fresh forecast, if it is
+    #[serde(skip)]
+    #[savefile_ignore]
+    pub stale_reason: Option<String>,
 }
 
 #[derive(Default, Serialize, Deserialize, Debug, Savefile)]
@@ -64,6 +110,8 @@ pub struct Daily {
     pub precipitation_probability_max: Vec<i32>,
     pub temperature_2m_max: Vec<f64>,
     pub temperature_2m_min: Vec<f64>,
+    #[serde(default)]
+    pub narrative: Vec<String>,
 }
 
 #[derive(Default, Serialize, Deserialize, Debug, Savefile)]
@@ -98,18 +146,34 @@ pub struct HourlyUnits {
     pub weather_code: String,
 }
 
+/// Precipitation nowcast in 15-minute steps for the next few hours.
+///
+/// Each value is the precipitation over the 15 minutes ending at the matching time.
+/// Providers without a nowcast leave this empty, and hourly data is used instead.
+#[derive(Default, Serialize, Deserialize, Debug, Savefile)]
+pub struct Minutely15 {
+    pub time: Vec<String>,
+    pub precipitation: Vec<f64>,
+}
+
 impl Weather {
     /// Retrieves weather data for the specified coordinates, using cached data if available.
     ///
-    /// Weather data is cached for 10 minutes (580 seconds) to reduce API calls.
-    /// If cached data is found for the same coordinates and is still fresh, it will be returned.
-    /// Otherwise, fresh data will be fetched from the Open-Meteo API.
+    /// Weather data is cached for the configured `cache.weather` lifetime, 10 minutes by
+    /// default, see `expires_at`. If cached data is found for the same coordinates and
+    /// provider and is still fresh, it will be returned. Otherwise, fresh data will be
+    /// fetched from the configured provider.
+    ///
+    /// When fetching fails, e.g. offline, older cached data is returned instead if it is
+    /// no older than the configured `max_stale`, 3 hours by default, or whatever its age
+    /// in offline mode. Its `stale_reason` then says why it couldn't be refreshed.
     ///
     /// # Arguments
     ///
     /// * `lat` - Latitude coordinate for the weather location
     /// * `lon` - Longitude coordinate for the weather location
-    /// * `s` - Settings containing units and location information for caching
+    /// * `timezone` - IANA timezone of the location, or empty if unknown
+    /// * `s` - Settings containing the provider and location information for caching
     ///
     /// # Returns
     ///
@@ -117,111 +181,283 @@ impl Weather {
     ///
     /// # Errors
     ///
-    /// This function will return an error if:
+    /// This function will return an error if there is no recent enough cached data and:
     /// - The API request fails
     /// - The response cannot be parsed as JSON
     /// - Network connectivity issues occur
-    pub fn get_cached(lat: f64, lon: f64, s: Settings) -> Result<Self> {
-        let filename = utils::cache::get_cached_file("weather", &s.location);
-        let now = utils::get_now();
+    pub async fn get_cached(lat: f64, lon: f64, timezone: &str, s: Settings) -> Result<Self> {
+        let cached = match Self::load(&s.location, lat, lon, &s) {
+            Some(wd) if wd.is_fresh(&s) => return Ok(wd),
+            cached => cached,
+        };
 
-        let metric_unit_strings = utils::unitstrings::UnitStrings::metric();
+        let provider = s.provider.as_str();
+        let fetched = s
+            .provider
+            .fetch(lat, lon, timezone, &s)
+            .await
+            .with_context(|| format!("Failed to fetch weather data from {provider}"));
 
-        let wd: Weather = load_file(&filename, 0).unwrap_or_default();
-
-        if wd.latitude == lat && wd.longitude == lon && wd.created_at > 0 && now - wd.created_at < 600 {
-            return Ok(wd);
+        match fetched {
+            Ok(data) => Ok(Self::store(&s.location, lat, lon, data, &s)),
+            Err(e) => Self::stale(cached, &e, &s).ok_or(e),
         }
+    }
 
-        let mut data =
-            Self::fetch(lat, lon, metric_unit_strings).with_context(|| "Failed to fetch weather data")?;
-        data.latitude = format!("{:.1}", data.latitude).parse().unwrap_or(0.0);
-        data.longitude = format!("{:.1}", data.longitude).parse().unwrap_or(0.0);
-        data.created_at = now;
+    /// Retrieves weather data for several locations, refreshing all expired ones at once.
+    ///
+    /// Each location keeps its own cache entry, exactly as with `get_cached`, including
+    /// the fallback to older cached data when refreshing fails. With Open-Meteo, the
+    /// expired locations are fetched together in a single request and the response is
+    /// split back into their entries. Other providers are asked one location at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `locations` - The location name used as cache key, with its latitude, longitude and timezone
+    /// * `s` - Settings containing the provider, endpoints and cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns the weather data of each location, in the order given.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fetching an expired location without recent
+    /// enough cached data fails.
+    pub async fn get_cached_batch(
+        locations: &[(String, f64, f64, String)],
+        s: &Settings,
+    ) -> Result<Vec<Self>> {
+        let mut cached: Vec<Option<Self>> =
+            locations.iter().map(|(location, lat, lon, _)| Self::load(location, *lat, *lon, s)).collect();
+        let mut results: Vec<Option<Self>> =
+            cached.iter_mut().map(|wd| wd.take_if(|wd| wd.is_fresh(s))).collect();
+        let expired: Vec<usize> = (0..locations.len()).filter(|&i| results[i].is_none()).collect();
+        let provider = s.provider.as_str();
 
-        match save_file(&filename, 0, &data) {
-            Ok(_) => {},
-            Err(e) => eprintln!("Unable to save weather data to disk: {e:#?}"),
+        if s.provider == Provider::OpenMeteo && !expired.is_empty() {
+            let points: Vec<(f64, f64)> = expired.iter().map(|&i| (locations[i].1, locations[i].2)).collect();
+            let fetched = OpenMeteo::fetch_batch(&points, &s.endpoints)
+                .await
+                .with_context(|| format!("Failed to fetch weather data from {provider}"));
+
+            match fetched {
+                Ok(forecasts) => {
+                    for (i, data) in expired.into_iter().zip(forecasts) {
+                        let (location, lat, lon, _) = &locations[i];
+                        results[i] = Some(Self::store(location, *lat, *lon, data, s));
+                    }
+                },
+                Err(e) => {
+                    for i in expired {
+                        match Self::stale(cached[i].take(), &e, s) {
+                            Some(wd) => results[i] = Some(wd),
+                            None => return Err(e),
+                        }
+                    }
+                },
+            }
+        } else {
+            for i in expired {
+                let (location, lat, lon, timezone) = &locations[i];
+                let fetched = s
+                    .provider
+                    .fetch(*lat, *lon, timezone, s)
+                    .await
+                    .with_context(|| format!("Failed to fetch weather data from {provider}"));
+
+                results[i] = Some(match fetched {
+                    Ok(data) => Self::store(location, *lat, *lon, data, s),
+                    Err(e) => match Self::stale(cached[i].take(), &e, s) {
+                        Some(wd) => wd,
+                        None => return Err(e),
+                    },
+                });
+            }
         }
 
-        Ok(data)
+        Ok(results.into_iter().flatten().collect())
     }
 
-    /// Fetches fresh weather data from the Open-Meteo API.
-    ///
-    /// Constructs the API URL with the appropriate parameters for current weather,
-    /// 7-day forecast, and unit preferences, then makes the HTTP request.
+    /// Returns older cached data to show instead when refreshing it failed.
     ///
     /// # Arguments
     ///
-    /// * `lat` - Latitude coordinate for the weather location
-    /// * `lon` - Longitude coordinate for the weather location
-    /// * `units` - Unit system for temperature, wind speed, and precipitation
+    /// * `cached` - The cached data, whatever its age
+    /// * `error` - Why refreshing failed, kept as the `stale_reason`
+    /// * `s` - Settings containing the cache lifetimes
     ///
     /// # Returns
     ///
-    /// Returns parsed weather data on success, or an error if the request fails.
+    /// Returns the cached data if it is no older than `max_stale`, or whatever its age in
+    /// offline mode, where there is nothing newer to show. Returns `None` otherwise.
+    fn stale(cached: Option<Self>, error: &anyhow::Error, s: &Settings) -> Option<Self> {
+        cached
+            .filter(|wd| cache::usable_when_stale(wd.created_at, s))
+            .map(|wd| Weather { stale_reason: Some(error.root_cause().to_string()), ..wd })
+    }
+
+    /// Returns when weather data fetched at `created_at` is refreshed.
+    ///
+    /// That is once the configured `cache.weather` lifetime has passed, and when the
+    /// provider's update schedule is known, not before its next update, since there is
+    /// nothing newer to fetch until then.
     ///
-    /// # Errors
+    /// # Arguments
+    ///
+    /// * `created_at` - Unix timestamp of when the data was fetched
+    /// * `s` - Settings containing the provider and cache lifetimes
+    ///
+    /// # Returns
+    ///
+    /// Returns the Unix timestamp the data expires at.
+    pub fn expires_at(created_at: u64, s: &Settings) -> u64 {
+        let expiry = created_at + s.cache.weather_seconds();
+        match s.provider.update_interval() {
+            Some(interval) => expiry.max((created_at / interval + 1) * interval),
+            None => expiry,
+        }
+    }
+
+    fn is_fresh(&self, s: &Settings) -> bool {
+        utils::get_now() < Self::expires_at(self.created_at, s)
+    }
+
+    /// Loads the cached weather data of a location, if it is for the same coordinates
+    /// and provider, whatever its age.
+    fn load(location: &str, lat: f64, lon: f64, s: &Settings) -> Option<Self> {
+        let header = Self::cache_header(lat, lon, &s.provider);
+        let wd: Weather = cache::load(&s.cache.dir(), &header, location)?;
+
+        (wd.latitude == lat && wd.longitude == lon && wd.provider == s.provider.as_str() && wd.created_at > 0)
+            .then_some(wd)
+    }
+
+    /// Describes the cache entry of weather data for coordinates from a provider.
+    fn cache_header(lat: f64, lon: f64, provider: &Provider) -> CacheHeader {
+        CacheHeader::new("weather", 0, format!("{lat},{lon}")).with_provider(provider.as_str())
+    }
+
+    /// Stamps freshly fetched weather data and saves it as the cache entry of a location.
     ///
-    /// This function will return an error if:
-    /// - The HTTP request fails
-    /// - The JSON response cannot be parsed
-    /// - The API returns an error response
-    fn fetch(lat: f64, lon: f64, units: utils::unitstrings::UnitStrings) -> Result<Self> {
-        let base_url = "https://api.open-meteo.com/v1/forecast";
-
-        // https://api.open-meteo.com/v1/forecast\?latitude\=51.30011\&longitude\=-114.03528\&daily\=weather_code,temperature_2m_max,temperature_2m_min,sunset,sunrise,precipitation_hours,precipitation_probability_max\&hourly\=temperature_2m,precipitation_probability,precipitation\&current\=temperature_2m,apparent_temperature,wind_speed_10m,wind_direction_10m,wind_gusts_10m,precipitation,weather_code,pressure_msl,relative_humidity_2m\&timezone\=America%2FDenver
-        let hourly_fields =
-            ["temperature_2m", "precipitation_probability", "precipitation", "weather_code"].join(",");
-
-        let current_fields = [
-            "temperature_2m",
-            "relative_humidity_2m",
-            "apparent_temperature",
-            "wind_speed_10m",
-            "wind_direction_10m",
-            "wind_gusts_10m",
-            "precipitation",
-            "weather_code",
-            "pressure_msl",
-        ]
-        .join(",");
-
-        let daily_fields = [
-            "sunrise",
-            "sunset",
-            "weather_code",
-            "temperature_2m_max",
-            "temperature_2m_min",
-            "precipitation_sum",
-            "precipitation_hours",
-            "precipitation_probability_max",
-            "uv_index_max",
-        ]
-        .join(",");
-
-        let lat_str = lat.to_string();
-        let lon_str = lon.to_string();
-
-        let params: Vec<(&str, &str)> = vec![
-            ("latitude", lat_str.as_str()),
-            ("longitude", lon_str.as_str()),
-            ("timezone", "auto"),
-            ("forecast_days", "7"),
-            ("current", current_fields.as_str()),
-            ("daily", daily_fields.as_str()),
-            ("hourly", hourly_fields.as_str()),
-            ("temperature_unit", units.temperature.as_str()),
-            ("wind_speed_unit", units.wind_speed.as_str()),
-            ("precipitation_unit", units.precipitation.as_str()),
-        ];
-
-        let api_url = utils::urls::builder(base_url, params);
-
-        let body = client::get_with_retry(&api_url, 2)
-            .with_context(|| "Unable to fetch weather data from the Open-Meteo API endpoint")?;
-
-        serde_json::from_str(&body).with_context(|| "Unable to parse weather response JSON")
+    /// The data is stamped with the coordinates asked for rather than the provider's grid
+    /// point, so `load` finds it again for the same coordinates.
+    fn store(location: &str, lat: f64, lon: f64, mut data: Self, s: &Settings) -> Self {
+        data.provider = s.provider.as_str().to_string();
+        data.latitude = lat;
+        data.longitude = lon;
+        data.created_at = utils::get_now();
+
+        let header = Self::cache_header(lat, lon, &s.provider);
+        match cache::save(&s.cache.dir(), &header, location, &data) {
+            Ok(_) => {},
+            Err(e) => eprintln!("Unable to save weather data to disk: {e:#?}"),
+        }
+
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{CacheConfig, EndpointsConfig};
+    use crate::utils::test_server::{self, Response};
+
+    /// Returns settings for a location whose forecasts can't be fetched.
+    fn failing_settings(location: &str) -> Settings {
+        let base_url = test_server::serve_responses(vec![("/v1/forecast", Response::status(500))]);
+
+        Settings {
+            location: location.to_string(),
+            endpoints: EndpointsConfig {
+                forecast: Some(format!("{base_url}/v1/forecast")),
+                ..Default::default()
+            },
+            cache: CacheConfig { dir: Some(test_server::cache_dir()), ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    /// Caches weather data for a location as if it had been fetched `age` seconds ago.
+    fn cache_old(s: &Settings, lat: f64, lon: f64, age: u64) {
+        let data = Weather {
+            provider: s.provider.as_str().to_string(),
+            latitude: lat,
+            longitude: lon,
+            created_at: utils::get_now() - age,
+            ..Default::default()
+        };
+        let header = Weather::cache_header(lat, lon, &s.provider);
+        cache::save(&s.cache.dir(), &header, &s.location, &data).unwrap();
+    }
+
+    #[test]
+    fn test_expires_at() {
+        let settings = |provider, weather| Settings {
+            provider,
+            cache: CacheConfig { weather, ..Default::default() },
+            ..Default::default()
+        };
+
+        // Open-Meteo data isn't refreshed before its next quarter-hour update, but longer
+        // lifetimes are kept as configured
+        assert_eq!(Weather::expires_at(1000, &settings(Provider::OpenMeteo, None)), 1800);
+        assert_eq!(Weather::expires_at(1000, &settings(Provider::OpenMeteo, Some(300))), 1800);
+        assert_eq!(Weather::expires_at(1000, &settings(Provider::OpenMeteo, Some(3600))), 4600);
+        assert_eq!(Weather::expires_at(1000, &settings(Provider::OpenMeteo, Some(1000))), 2000);
+        assert_eq!(Weather::expires_at(900, &settings(Provider::OpenMeteo, Some(900))), 1800);
+
+        assert_eq!(Weather::expires_at(1000, &settings(Provider::MetNo, Some(300))), 1300);
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let (lat, lon) = (51.0447, -114.0719);
+        let location = "51.0447,-114.0719,test_store_and_load";
+        let settings = Settings {
+            cache: CacheConfig { dir: Some(test_server::cache_dir()), ..Default::default() },
+            ..Default::default()
+        };
+        // The provider's grid point is near, but not at, the coordinates asked for
+        let fetched = Weather { latitude: 51.04, longitude: -114.08, ..Default::default() };
+
+        Weather::store(location, lat, lon, fetched, &settings);
+
+        let cached = Weather::load(location, lat, lon, &settings).unwrap();
+        assert_eq!((cached.latitude, cached.longitude), (lat, lon));
+        assert!(cached.is_fresh(&settings));
+        let met_no = Settings { provider: Provider::MetNo, ..settings };
+        assert!(Weather::load(location, lat, lon, &met_no).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stale_fallback() {
+        let (lat, lon) = (51.0447, -114.0719);
+        let settings = failing_settings("51.0447,-114.0719,test_stale_fallback");
+
+        // An hour old, so expired but within max_stale
+        cache_old(&settings, lat, lon, 3600);
+        let weather = Weather::get_cached(lat, lon, "", settings.clone()).await.unwrap();
+        assert!(weather.stale_reason.is_some());
+
+        // Older than max_stale, so the failure is reported
+        cache_old(&settings, lat, lon, settings.cache.max_stale_seconds() + 60);
+        assert!(Weather::get_cached(lat, lon, "", settings).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stale_offline() {
+        let (lat, lon) = (51.0447, -114.0719);
+        let settings = Settings { offline: true, ..failing_settings("51.0447,-114.0719,test_stale_offline") };
+
+        // Offline mode shows whatever is cached, however old
+        cache_old(&settings, lat, lon, settings.cache.max_stale_seconds() * 4);
+        let weather = Weather::get_cached(lat, lon, "", settings.clone()).await.unwrap();
+        assert!(weather.stale_reason.is_some());
+
+        let batch = [(settings.location.clone(), lat, lon, String::new())];
+        let weather = Weather::get_cached_batch(&batch, &settings).await.unwrap();
+        assert!(weather[0].stale_reason.is_some());
     }
 }
@@ -0,0 +1,275 @@
+use crate::api::airquality::AirQuality;
+use crate::api::alerts::Alerts;
+use crate::api::eccc::CitySite;
+use crate::api::location::{LocationData, LOCATION_DATA_VERSION};
+use crate::api::models::ModelComparison;
+use crate::api::nws::GridPoint;
+use crate::api::weather::Weather;
+use crate::error::Error;
+use crate::settings::CacheCommand;
+use crate::tui::location_manager::{LocationList, LOCATION_LIST_VERSION};
+use crate::utils::cache::{self, CacheEntry};
+use crate::Settings;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::path::Path;
+
+/// Runs a `cache` subcommand.
+///
+/// # Arguments
+///
+/// * `s` - Application configuration, for the named places and cache directory
+/// * `action` - The subcommand to run
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if the cache cannot be read or changed.
+pub fn run(s: &Settings, action: CacheCommand) -> Result<()> {
+    match action {
+        CacheCommand::List => list(&s.cache.dir()),
+        CacheCommand::Show { entry } => show(&s.cache.dir(), &entry),
+        CacheCommand::Purge { location, datatype, all } => {
+            let entries = cache::entries(&s.cache.dir())?;
+            remove(to_purge(&entries, location.as_deref(), datatype.as_deref(), all, s)?.into_iter())
+        },
+        CacheCommand::Prune { days } => {
+            let entries = cache::entries(&s.cache.dir())?;
+            remove(to_prune(&entries, days).into_iter())
+        },
+    }
+}
+
+/// The data type of the TUI's saved locations, which is user data rather than a cache.
+const SAVED_LOCATIONS: &str = "locations";
+
+/// Selects the entries to delete for `cache purge`.
+///
+/// The TUI's saved locations are only deleted when asked for with `--type locations`,
+/// as they can't be fetched again.
+///
+/// # Arguments
+///
+/// * `entries` - All cache entries
+/// * `location` - Only the entries of this location, see `for_location`
+/// * `datatype` - Only the entries of this data type
+/// * `all` - Every entry, whatever the other filters
+/// * `s` - Application configuration, for the named places and cache directory
+///
+/// # Returns
+///
+/// Returns the entries to delete, or an error if the location is an unknown place.
+fn to_purge<'a>(
+    entries: &'a [CacheEntry],
+    location: Option<&str>,
+    datatype: Option<&str>,
+    all: bool,
+    s: &Settings,
+) -> Result<Vec<&'a CacheEntry>> {
+    let matching = match location {
+        Some(location) if !all => for_location(entries, location, s)?,
+        _ => entries.iter().collect(),
+    };
+    let of_type = |e: &&CacheEntry| {
+        if e.datatype == SAVED_LOCATIONS {
+            datatype == Some(SAVED_LOCATIONS)
+        } else {
+            all || datatype.is_none() || datatype == Some(e.datatype.as_str())
+        }
+    };
+
+    Ok(matching.into_iter().filter(of_type).collect())
+}
+
+/// Selects the entries to delete for `cache prune`, keeping the TUI's saved locations.
+fn to_prune(entries: &[CacheEntry], days: u64) -> Vec<&CacheEntry> {
+    entries.iter().filter(|e| e.datatype != SAVED_LOCATIONS && e.age >= days * 86400).collect()
+}
+
+/// Prints a table of the cache entries.
+fn list(dir: &Path) -> Result<()> {
+    println!(
+        "{:<11} {:<11} {:<24} {:<20} {:>8} {:>9}  FILE",
+        "TYPE", "PROVIDER", "LOCATION", "LAT,LON", "AGE", "SIZE"
+    );
+
+    for entry in cache::entries(dir)? {
+        let (provider, location) = match &entry.header {
+            Some(header) if header.key.is_empty() => (header.provider.as_str(), "(detected)"),
+            Some(header) => (header.provider.as_str(), header.key.as_str()),
+            None => ("", "(old format)"),
+        };
+        let coordinates = coordinates(&entry).map(|(lat, lon)| format!("{lat},{lon}")).unwrap_or_default();
+
+        println!(
+            "{:<11} {:<11} {:<24} {:<20} {:>8} {:>9}  {}",
+            entry.datatype,
+            provider,
+            location,
+            coordinates,
+            format_age(entry.age),
+            format_size(entry.size),
+            entry.name()
+        );
+    }
+
+    Ok(())
+}
+
+/// Prints the data of a cache entry as JSON.
+fn show(dir: &Path, name: &str) -> Result<()> {
+    let name = if name.ends_with(".cache") { name.to_string() } else { format!("{name}.cache") };
+    let entry = cache::entries(dir)?
+        .into_iter()
+        .find(|e| e.name() == name)
+        .ok_or_else(|| Error::Config(format!("There is no cache entry named {name}")))?;
+
+    println!("{}", serde_json::to_string_pretty(&to_json(&entry)?)?);
+    Ok(())
+}
+
+/// Deletes cache entries and reports how many were deleted.
+fn remove<'a>(entries: impl Iterator<Item = &'a CacheEntry>) -> Result<()> {
+    let mut count = 0;
+    for entry in entries {
+        entry.remove()?;
+        count += 1;
+    }
+
+    println!("Removed {count} cache {}", if count == 1 { "entry" } else { "entries" });
+    Ok(())
+}
+
+/// Finds the cache entries of a location.
+///
+/// Entries are saved under the location as given, or as the place it names for the
+/// location itself. Data saved under coordinates, e.g. NWS gridpoints, belongs to the
+/// location when it is for the same coordinates.
+fn for_location<'a>(entries: &'a [CacheEntry], location: &str, s: &Settings) -> Result<Vec<&'a CacheEntry>> {
+    let resolved = LocationData::resolve_place(location, &s.places)?;
+    let keys = [location.trim(), resolved.trim()];
+    let is_key = |e: &CacheEntry| {
+        e.header.as_ref().is_some_and(|h| keys.iter().any(|k| h.key.trim().eq_ignore_ascii_case(k)))
+    };
+
+    let places: Vec<(f64, f64)> = entries.iter().filter(|e| is_key(e)).filter_map(coordinates).collect();
+    Ok(entries.iter().filter(|e| is_key(e) || coordinates(e).is_some_and(|c| places.contains(&c))).collect())
+}
+
+/// Returns the coordinates an entry was requested for, or else those of its data.
+fn coordinates(entry: &CacheEntry) -> Option<(f64, f64)> {
+    let header = entry.header.as_ref()?;
+    let mut request = header.request.split(',').map(|part| part.trim().parse::<f64>());
+    if let (Some(Ok(lat)), Some(Ok(lon))) = (request.next(), request.next()) {
+        return Some((lat, lon));
+    }
+
+    let data = to_json(entry).ok()?;
+    Some((data.get("latitude")?.as_f64()?, data.get("longitude")?.as_f64()?))
+}
+
+/// Loads the data of an entry as JSON, at the current version of its data type.
+fn to_json(entry: &CacheEntry) -> Result<Value> {
+    // The versions are those the data types are saved with
+    let value = match entry.datatype.as_str() {
+        "weather" | "history" => serde_json::to_value(entry.load::<Weather>(0)?),
+        "location" => serde_json::to_value(entry.load::<LocationData>(LOCATION_DATA_VERSION)?),
+        "locations" => serde_json::to_value(entry.load::<LocationList>(LOCATION_LIST_VERSION)?),
+        "airquality" => serde_json::to_value(entry.load::<AirQuality>(0)?),
+        "alerts" => serde_json::to_value(entry.load::<Alerts>(0)?),
+        "models" => serde_json::to_value(entry.load::<ModelComparison>(0)?),
+        "gridpoint" => serde_json::to_value(entry.load::<GridPoint>(0)?),
+        "citypage" => serde_json::to_value(entry.load::<CitySite>(0)?),
+        other => return Err(anyhow::anyhow!("Unknown cache data type {other}")),
+    };
+
+    value.with_context(|| format!("Unable to convert {} to JSON", entry.name()))
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{seconds} s"),
+        60..=3599 => format!("{} min", seconds / 60),
+        3600..=86399 => format!("{} h", seconds / 3600),
+        _ => format!("{} d", seconds / 86400),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1048575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1048576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::cache::CacheHeader;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_age(42), "42 s");
+        assert_eq!(format_age(600), "10 min");
+        assert_eq!(format_age(4 * 3600 + 59), "4 h");
+        assert_eq!(format_age(3 * 86400), "3 d");
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(12_800), "12.5 KB");
+        assert_eq!(format_size(3 * 1048576), "3.0 MB");
+    }
+
+    fn entry(datatype: &str, key: &str, request: &str, age: u64) -> CacheEntry {
+        CacheEntry {
+            path: format!("/nonexistent/{datatype}-{key}.cache").into(),
+            header: Some(CacheHeader { key: key.to_string(), ..CacheHeader::new(datatype, 0, request) }),
+            datatype: datatype.to_string(),
+            size: 0,
+            age,
+        }
+    }
+
+    fn types(entries: Vec<&CacheEntry>) -> Vec<&str> {
+        entries.iter().map(|e| e.datatype.as_str()).collect()
+    }
+
+    #[test]
+    fn test_for_location() {
+        let entries = vec![
+            entry("location", "Calgary, CA", "Calgary, CA", 0),
+            entry("weather", "calgary, ca", "51.05,-114.07", 0),
+            entry("gridpoint", "51.05,-114.07", "51.05,-114.07", 0),
+            entry("weather", "Edmonton, CA", "53.55,-113.47", 0),
+        ];
+        let s = Settings {
+            places: [("home".to_string(), "Calgary, CA".to_string())].into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            types(for_location(&entries, "Calgary, CA", &s).unwrap()),
+            ["location", "weather", "gridpoint"]
+        );
+        assert_eq!(types(for_location(&entries, "@home", &s).unwrap()), ["location", "weather", "gridpoint"]);
+        assert_eq!(types(for_location(&entries, "Paris, FR", &s).unwrap()), Vec::<&str>::new());
+        assert!(for_location(&entries, "@cabin", &s).is_err());
+    }
+
+    #[test]
+    fn test_purge_and_prune() {
+        let entries = vec![
+            entry("weather", "Calgary, CA", "51.05,-114.07", 0),
+            entry("alerts", "Calgary, CA", "51.05,-114.07", 40 * 86400),
+            entry("locations", "", "list", 40 * 86400),
+        ];
+        let s = Settings::default();
+        let purge = |location, datatype, all| types(to_purge(&entries, location, datatype, all, &s).unwrap());
+
+        assert_eq!(purge(None, None, true), ["weather", "alerts"]);
+        assert_eq!(purge(Some("Calgary, CA"), None, false), ["weather", "alerts"]);
+        assert_eq!(purge(None, Some("alerts"), false), ["alerts"]);
+        assert_eq!(purge(None, Some("locations"), false), ["locations"]);
+        assert_eq!(purge(None, Some("locations"), true), ["weather", "alerts", "locations"]);
+
+        assert_eq!(types(to_prune(&entries, 30)), ["alerts"]);
+    }
+}
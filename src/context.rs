@@ -1,3 +1,6 @@
+use crate::api::airquality::{AirQuality, AirQualityCurrent};
+use crate::api::alerts::{Alert, Alerts};
+use crate::api::models::ModelComparison;
 use crate::utils::conversions;
 use crate::utils::mappings;
 use crate::utils::*;
@@ -37,10 +40,23 @@ pub struct Context {
     pub precipitation_hours: f64,
     pub precipitation_start: Option<i32>,
     pub precipitation_end: Option<i32>,
+    pub precipitation_start_minutes: Option<i32>,
+    pub precipitation_end_minutes: Option<i32>,
     pub precipitation_description: Option<String>,
+    pub narrative: Option<String>,
+    #[serde(flatten)]
+    pub air_quality: ContextAirQuality,
+    pub alerts: Vec<Alert>,
+    pub models: Vec<String>,
     pub forecast: Vec<ContextDaily>,
     pub hourly: Vec<ContextHourly>,
     pub cache_age: u64,
+    pub cache_ttl: u64,
+    pub stale: bool,
+    pub stale_reason: Option<String>,
+    pub location_source: String,
+    pub location_age: u64,
+    pub history: bool,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -56,6 +72,9 @@ pub struct ContextDaily {
     pub precipitation_chance: i32,
     pub temperature_high: f64,
     pub temperature_low: f64,
+    pub narrative: Option<String>,
+    #[serde(flatten)]
+    pub model_comparison: ContextModelComparison,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -66,6 +85,82 @@ pub struct ContextHourly {
     pub precipitation: f64,
     pub weather_code: i32,
     pub weather_icon: String,
+    #[serde(flatten)]
+    pub air_quality: ContextAirQuality,
+}
+
+/// Air quality and pollen values, shared by the current conditions and each forecast hour.
+///
+/// Every value is optional: air quality is fetched on a best-effort basis, and pollen
+/// is only available in Europe during the pollen season. Pollen counts are in grains/m³.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ContextAirQuality {
+    pub us_aqi: Option<i32>,
+    pub european_aqi: Option<i32>,
+    pub aqi_description: Option<String>,
+    pub pm2_5: Option<f64>,
+    pub pm10: Option<f64>,
+    pub ozone: Option<f64>,
+    pub alder_pollen: Option<f64>,
+    pub birch_pollen: Option<f64>,
+    pub grass_pollen: Option<f64>,
+    pub mugwort_pollen: Option<f64>,
+    pub olive_pollen: Option<f64>,
+    pub ragweed_pollen: Option<f64>,
+}
+
+/// Side-by-side forecasts from several models for one day, and how closely they agree.
+///
+/// Empty unless models were requested with `--models`. The spreads are the difference
+/// between the highest and lowest value forecast by any model: the larger of the highs'
+/// and the lows' spread for temperature, and the spread of the totals for precipitation.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ContextModelComparison {
+    pub models: Vec<ContextModel>,
+    pub temperature_spread: Option<f64>,
+    pub precipitation_spread: Option<f64>,
+    pub model_agreement: Option<String>,
+}
+
+/// One model's forecast for a day. Values are `None` beyond the model's forecast range.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ContextModel {
+    pub model: String,
+    pub temperature_high: Option<f64>,
+    pub temperature_low: Option<f64>,
+    pub precipitation_sum: Option<f64>,
+}
+
+/// Minute-level precipitation timing from the 15-minute nowcast.
+///
+/// `start` and `end` are minutes from now. When it is already precipitating only
+/// `end` can be set; otherwise `end` is when the next spell of precipitation stops.
+struct Nowcast {
+    precipitating: bool,
+    start: Option<i32>,
+    end: Option<i32>,
+    weather_code: i32,
+}
+
+impl From<AirQualityCurrent> for ContextAirQuality {
+    fn from(aq: AirQualityCurrent) -> Self {
+        let us_aqi = aq.us_aqi.map(|v| v.round() as i32);
+
+        ContextAirQuality {
+            us_aqi,
+            european_aqi: aq.european_aqi.map(|v| v.round() as i32),
+            aqi_description: us_aqi.map(weather_classification::aqi_description),
+            pm2_5: aq.pm2_5,
+            pm10: aq.pm10,
+            ozone: aq.ozone,
+            alder_pollen: aq.alder_pollen,
+            birch_pollen: aq.birch_pollen,
+            grass_pollen: aq.grass_pollen,
+            mugwort_pollen: aq.mugwort_pollen,
+            olive_pollen: aq.olive_pollen,
+            ragweed_pollen: aq.ragweed_pollen,
+        }
+    }
 }
 
 impl Context {
@@ -81,10 +176,18 @@ impl Context {
     /// - Converts ISO8601 timestamps to human-readable time/date strings
     /// - Calculates cache age for freshness indication
     /// - Builds a 7-day forecast array with processed daily data
+    /// - Times precipitation to the minute from the 15-minute nowcast when available
+    /// - Matches air quality and pollen data to the current conditions and each forecast hour
+    /// - Drops expired weather alerts and formats their onset and expiry times
+    /// - Lines up the daily forecasts of several models and measures how closely they agree
+    /// - Marks the context stale when any of its data is older cached data that couldn't be refreshed
     ///
     /// # Arguments
     ///
     /// * `weather` - Weather data structure containing current conditions and forecasts (always in metric)
+    /// * `air_quality` - Air quality and pollen data, if it could be fetched
+    /// * `alerts` - Active weather alerts for the location, most severe first, if they could be fetched
+    /// * `models` - Daily forecasts from several models to compare, if requested and fetched
     /// * `location` - Location data containing city, country, and coordinates
     /// * `settings` - Settings containing units and other configuration
     ///
@@ -92,8 +195,21 @@ impl Context {
     ///
     /// Returns a `Context` struct containing all processed weather and location data
     /// ready for template rendering across different output formats.
-    pub fn build(weather: Weather, location: LocationData, settings: Settings) -> Self {
+    pub fn build(
+        weather: Weather,
+        air_quality: Option<AirQuality>,
+        alerts: Option<Alerts>,
+        models: Option<ModelComparison>,
+        location: LocationData,
+        settings: Settings,
+    ) -> Self {
         let now = get_now();
+        let stale_reason = weather
+            .stale_reason
+            .clone()
+            .or_else(|| air_quality.as_ref().and_then(|aq| aq.stale_reason.clone()))
+            .or_else(|| alerts.as_ref().and_then(|a| a.stale_reason.clone()))
+            .or_else(|| models.as_ref().and_then(|m| m.stale_reason.clone()));
 
         let current = &weather.current;
         let daily = &weather.daily;
@@ -108,6 +224,8 @@ impl Context {
         let sunset = conversions::iso8601_to_time(daily.sunset[0].clone(), settings.hour24);
 
         let cache_age = now - weather.created_at;
+        let cache_ttl = Weather::expires_at(weather.created_at, &settings) - weather.created_at;
+        let location_age = now.saturating_sub(location.created_at);
 
         // Convert values based on user settings
         let is_imperial = settings.units == Units::Imperial;
@@ -179,47 +297,52 @@ impl Context {
                 } else {
                     daily.temperature_2m_min[i]
                 },
+                narrative: daily.narrative.get(i).filter(|text| !text.is_empty()).cloned(),
+                model_comparison: models
+                    .as_ref()
+                    .map(|models| Self::compare_models(models, date, is_imperial))
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        let alerts: Vec<Alert> = alerts
+            .map(|a| a.alerts)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|alert| alert.expires_at == 0 || alert.expires_at > now)
+            .map(|alert| Alert {
+                onset: conversions::iso8601_to_datetime(alert.onset, settings.hour24),
+                expires: conversions::iso8601_to_datetime(alert.expires, settings.hour24),
+                ..alert
             })
             .collect();
 
         // Find the current hour index to start hourly forecast from current time
         let current_hour_index = Self::find_current_hour_index(&hourly.time, now, weather.utc_offset_seconds);
 
-        let hourlies: Vec<ContextHourly> = hourly
-            .time
-            .iter()
-            .enumerate()
-            .skip(current_hour_index)
-            .take(24)
-            .map(|(i, time)| ContextHourly {
-                time: conversions::iso8601_to_time(time.clone(), settings.hour24),
-                temperature: if is_imperial {
-                    conversions::celsius_to_fahrenheit(hourly.temperature_2m[i])
-                } else {
-                    hourly.temperature_2m[i]
-                },
-                precipitation_probability: hourly.precipitation_probability[i],
-                precipitation: if is_imperial {
-                    conversions::mm_to_inches(hourly.precipitation[i])
-                } else {
-                    hourly.precipitation[i]
-                },
-                weather_code: hourly.weather_code[i],
-                weather_icon: mappings::weather_code2icon(hourly.weather_code[i]),
-            })
-            .collect();
+        let hourlies = Self::build_hourly(
+            &weather,
+            air_quality.as_ref(),
+            (current_hour_index..hourly.time.len()).take(24),
+            |time| conversions::iso8601_to_time(time.to_string(), settings.hour24),
+            is_imperial,
+        );
 
         // Calculate precipitation start and end times (accounting for current hour offset)
         let (precipitation_start, precipitation_end) =
             Self::calculate_precipitation_timing(hourly, current_hour_index);
 
-        // Create precipitation description
-        let precipitation_description = Self::create_precipitation_description(
-            precipitation_start,
-            precipitation_end,
-            hourly,
-            current_hour_index,
-        );
+        // Prefer the 15-minute nowcast for the next few hours, falling back to hourly timing
+        let nowcast = Self::calculate_nowcast_timing(&weather, now);
+        let precipitation_description =
+            nowcast.as_ref().and_then(Self::create_nowcast_description).or_else(|| {
+                Self::create_precipitation_description(
+                    precipitation_start,
+                    precipitation_end,
+                    hourly,
+                    current_hour_index,
+                )
+            });
 
         Context {
             city: location.city,
@@ -251,14 +374,119 @@ impl Context {
             precipitation_hours: daily.precipitation_hours[0],
             precipitation_start,
             precipitation_end,
+            precipitation_start_minutes: nowcast.as_ref().and_then(|n| n.start),
+            precipitation_end_minutes: nowcast.as_ref().and_then(|n| n.end),
             precipitation_description,
+            narrative: weather.narrative.clone(),
+            air_quality: air_quality.map(|aq| ContextAirQuality::from(aq.current)).unwrap_or_default(),
+            alerts,
+            models: models.map(|m| m.models.into_iter().map(|f| f.model).collect()).unwrap_or_default(),
             forecast: dailies,
             hourly: hourlies,
 
             cache_age,
+            cache_ttl,
+            stale: stale_reason.is_some(),
+            stale_reason,
+            location_source: location.source,
+            location_age,
+            history: false,
         }
     }
 
+    /// Builds the context for past weather from the archive.
+    ///
+    /// Past days are shown like a forecast, but nothing about them is relative to now:
+    /// the hourly data covers every hour of the range, labelled with its date, and the
+    /// precipitation timing and cache and location ages are left out. The conditions at
+    /// noon on the first day stand in for the current conditions.
+    ///
+    /// # Arguments
+    ///
+    /// * `weather` - Past weather for the range, see `History::get_cached` (always in metric)
+    /// * `location` - Location data containing city, country, and coordinates
+    /// * `settings` - Settings containing units and other configuration
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Context` with `history` set, ready for template rendering.
+    pub fn build_history(weather: Weather, location: LocationData, settings: Settings) -> Self {
+        let hourly = Self::build_hourly(
+            &weather,
+            None,
+            0..weather.hourly.time.len(),
+            |time| conversions::iso8601_to_datetime(time.to_string(), settings.hour24),
+            settings.units == Units::Imperial,
+        );
+        let context = Self::build(weather, None, None, None, location, settings);
+
+        Context {
+            hourly,
+            precipitation_start: None,
+            precipitation_end: None,
+            precipitation_start_minutes: None,
+            precipitation_end_minutes: None,
+            precipitation_description: None,
+            cache_age: 0,
+            cache_ttl: 0,
+            stale: false,
+            stale_reason: None,
+            location_age: 0,
+            history: true,
+            ..context
+        }
+    }
+
+    /// Converts hours of the hourly data into the context's hourly entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `weather` - Weather data containing the hourly data and UTC offset (always in metric)
+    /// * `air_quality` - Air quality and pollen data to match to each hour, if any
+    /// * `hours` - Indices of the hours to include
+    /// * `label` - Formats the ISO8601 time of an hour for display
+    /// * `is_imperial` - Whether to convert values to imperial units
+    ///
+    /// # Returns
+    ///
+    /// Returns an entry for each of the hours, in the order given.
+    fn build_hourly(
+        weather: &Weather,
+        air_quality: Option<&AirQuality>,
+        hours: impl Iterator<Item = usize>,
+        label: impl Fn(&str) -> String,
+        is_imperial: bool,
+    ) -> Vec<ContextHourly> {
+        let hourly = &weather.hourly;
+
+        hours
+            .filter_map(|i| hourly.time.get(i).map(|time| (i, time)))
+            .map(|(i, time)| ContextHourly {
+                time: label(time),
+                temperature: if is_imperial {
+                    conversions::celsius_to_fahrenheit(hourly.temperature_2m[i])
+                } else {
+                    hourly.temperature_2m[i]
+                },
+                precipitation_probability: hourly.precipitation_probability[i],
+                precipitation: if is_imperial {
+                    conversions::mm_to_inches(hourly.precipitation[i])
+                } else {
+                    hourly.precipitation[i]
+                },
+                weather_code: hourly.weather_code[i],
+                weather_icon: mappings::weather_code2icon(hourly.weather_code[i]),
+                air_quality: air_quality
+                    .and_then(|aq| {
+                        Self::local_to_timestamp(time, weather.utc_offset_seconds)
+                            .and_then(|timestamp| aq.hourly.at(timestamp))
+                    })
+                    .map(ContextAirQuality::from)
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
     /// Finds the index of the current hour in the hourly time array.
     ///
     /// This function converts the current UTC timestamp to the location's timezone,
@@ -302,6 +530,119 @@ impl Context {
         0
     }
 
+    /// Lines up each model's forecast for one day and measures how far apart they are.
+    ///
+    /// Agreement is judged on metric values; the values and spreads in the result are
+    /// converted to the user's units.
+    ///
+    /// # Arguments
+    ///
+    /// * `models` - Daily forecasts from each model being compared (always in metric)
+    /// * `date` - ISO8601 date of the day to compare
+    /// * `is_imperial` - Whether to convert values to imperial units
+    ///
+    /// # Returns
+    ///
+    /// Returns each model's forecast for the day, with the spreads and agreement level
+    /// left as `None` when fewer than two models have data for the day.
+    fn compare_models(models: &ModelComparison, date: &str, is_imperial: bool) -> ContextModelComparison {
+        let day: Vec<ContextModel> = models
+            .models
+            .iter()
+            .map(|forecast| {
+                let i = forecast.time.iter().position(|time| time == date);
+                let value = |series: &[Option<f64>]| i.and_then(|i| series.get(i).copied().flatten());
+
+                ContextModel {
+                    model: forecast.model.clone(),
+                    temperature_high: value(&forecast.temperature_2m_max),
+                    temperature_low: value(&forecast.temperature_2m_min),
+                    precipitation_sum: value(&forecast.precipitation_sum),
+                }
+            })
+            .collect();
+
+        let range = |values: Vec<f64>| {
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            if values.len() >= 2 {
+                Some((min, max))
+            } else {
+                None
+            }
+        };
+
+        let highs = range(day.iter().filter_map(|m| m.temperature_high).collect());
+        let lows = range(day.iter().filter_map(|m| m.temperature_low).collect());
+        let precipitation = range(day.iter().filter_map(|m| m.precipitation_sum).collect());
+
+        let temperature_spread = match (highs, lows) {
+            (Some((high_min, high_max)), Some((low_min, low_max))) => {
+                Some((high_max - high_min).max(low_max - low_min))
+            },
+            (Some((min, max)), None) | (None, Some((min, max))) => Some(max - min),
+            (None, None) => None,
+        };
+
+        let model_agreement = temperature_spread.map(|spread| {
+            let (precipitation_min, precipitation_max) = precipitation.unwrap_or_default();
+            weather_classification::model_agreement_name(weather_classification::classify_model_agreement(
+                spread,
+                precipitation_min,
+                precipitation_max,
+            ))
+        });
+
+        let temperature = |value: f64| {
+            if is_imperial {
+                conversions::celsius_to_fahrenheit(value)
+            } else {
+                value
+            }
+        };
+        let millimetres = |value: f64| {
+            if is_imperial {
+                conversions::mm_to_inches(value)
+            } else {
+                value
+            }
+        };
+
+        ContextModelComparison {
+            models: day
+                .into_iter()
+                .map(|m| ContextModel {
+                    temperature_high: m.temperature_high.map(temperature),
+                    temperature_low: m.temperature_low.map(temperature),
+                    precipitation_sum: m.precipitation_sum.map(millimetres),
+                    ..m
+                })
+                .collect(),
+            // A temperature difference scales by 9/5 without the offset
+            temperature_spread: temperature_spread.map(|spread| {
+                let spread = if is_imperial { spread * 9.0 / 5.0 } else { spread };
+                (spread * 10.0).round() / 10.0
+            }),
+            precipitation_spread: precipitation.map(|(min, max)| millimetres(max - min)),
+            model_agreement,
+        }
+    }
+
+    /// Converts a local ISO8601 hour from the hourly forecast into a Unix timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - ISO8601 datetime string in the location's timezone
+    /// * `utc_offset_seconds` - UTC offset for the location's timezone
+    ///
+    /// # Returns
+    ///
+    /// Returns the Unix timestamp, or `None` if the time cannot be parsed.
+    fn local_to_timestamp(time: &str, utc_offset_seconds: i32) -> Option<i64> {
+        let local = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M").ok()?;
+        Some(local.and_utc().timestamp() - utc_offset_seconds as i64)
+    }
+
     /// Calculates when precipitation is expected to start or stop based on hourly data.
     ///
     /// Returns the number of hours until precipitation starts (if currently none)
@@ -401,4 +742,235 @@ impl Context {
             }
         }
     }
+
+    /// Calculates minute-level precipitation timing from the 15-minute nowcast.
+    ///
+    /// Each nowcast value covers the 15 minutes ending at its timestamp, so the interval
+    /// containing the current time is the first one ending after it.
+    ///
+    /// # Arguments
+    ///
+    /// * `weather` - Weather data containing the nowcast, hourly codes and UTC offset
+    /// * `now` - Current Unix timestamp in seconds (UTC)
+    ///
+    /// # Returns
+    ///
+    /// Returns the precipitation timing, or `None` if the provider has no nowcast
+    /// covering the current time.
+    fn calculate_nowcast_timing(weather: &Weather, now: u64) -> Option<Nowcast> {
+        let minutely = &weather.minutely_15;
+        let now = now as i64;
+
+        let interval_ends: Vec<i64> = minutely
+            .time
+            .iter()
+            .map(|time| Self::local_to_timestamp(time, weather.utc_offset_seconds))
+            .collect::<Option<_>>()?;
+        let current = interval_ends.iter().position(|&end| end > now)?;
+
+        let is_wet = |i: usize| minutely.precipitation.get(i).copied().unwrap_or(0.0) > 0.0;
+        let minutes_from_now = |i: usize| ((interval_ends[i] - 15 * 60 - now).max(0) / 60) as i32;
+        let next = |from: usize, wet: bool| (from..interval_ends.len()).find(|&i| is_wet(i) == wet);
+
+        if is_wet(current) {
+            return Some(Nowcast {
+                precipitating: true,
+                start: None,
+                end: next(current, false).map(minutes_from_now),
+                weather_code: weather.current.weather_code,
+            });
+        }
+
+        let start = next(current, true);
+        let end = start.and_then(|start| next(start, false));
+
+        // Use the hourly weather code at the start to tell rain from snow
+        let weather_code = start
+            .and_then(|start| {
+                let start_time = interval_ends[start] - 15 * 60;
+                weather
+                    .hourly
+                    .time
+                    .iter()
+                    .rposition(|time| {
+                        Self::local_to_timestamp(time, weather.utc_offset_seconds)
+                            .is_some_and(|hour| hour <= start_time)
+                    })
+                    .and_then(|i| weather.hourly.weather_code.get(i).copied())
+            })
+            .unwrap_or(weather.current.weather_code);
+
+        Some(Nowcast {
+            precipitating: false,
+            start: start.map(minutes_from_now),
+            end: end.map(minutes_from_now),
+            weather_code,
+        })
+    }
+
+    /// Creates a human-readable description of minute-level precipitation timing.
+    ///
+    /// # Arguments
+    ///
+    /// * `nowcast` - Precipitation timing from the 15-minute nowcast
+    ///
+    /// # Returns
+    ///
+    /// Returns a description like "Rain starting in ~20 min, lasting ~45 min" or
+    /// "Snow stopping in ~1 h 15 min", or `None` if nothing changes within the nowcast.
+    fn create_nowcast_description(nowcast: &Nowcast) -> Option<String> {
+        let kind = match weather_classification::classify_weather(nowcast.weather_code) {
+            weather_classification::WeatherCondition::Snow => "Snow",
+            _ => "Rain",
+        };
+
+        match (nowcast.precipitating, nowcast.start, nowcast.end) {
+            (true, _, Some(end)) => Some(format!("{kind} stopping in {}", Self::approximate_minutes(end))),
+            (false, Some(start), Some(end)) => Some(format!(
+                "{kind} starting in {}, lasting {}",
+                Self::approximate_minutes(start),
+                Self::approximate_minutes(end - start)
+            )),
+            (false, Some(start), None) => {
+                Some(format!("{kind} starting in {}", Self::approximate_minutes(start)))
+            },
+            _ => None,
+        }
+    }
+
+    /// Formats a number of minutes as an approximate duration, rounded to 5 minutes.
+    ///
+    /// # Arguments
+    ///
+    /// * `minutes` - Duration in minutes
+    ///
+    /// # Returns
+    ///
+    /// Returns a string like "~20 min", "~1 h" or "~2 h 15 min".
+    fn approximate_minutes(minutes: i32) -> String {
+        let minutes = ((minutes + 2) / 5 * 5).max(5);
+
+        match (minutes / 60, minutes % 60) {
+            (0, m) => format!("~{m} min"),
+            (h, 0) => format!("~{h} h"),
+            (h, m) => format!("~{h} h {m} min"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::models::ModelForecast;
+    use crate::api::weather::{Daily, Hourly, Minutely15};
+
+    fn weather_with_nowcast(precipitation: Vec<f64>) -> Weather {
+        // 15-minute intervals ending at 12:15, 12:30, ... in UTC
+        let time = (0..precipitation.len())
+            .map(|i| format!("2025-06-21T{:02}:{:02}", 12 + (i + 1) / 4, (i + 1) % 4 * 15))
+            .collect();
+
+        Weather { minutely_15: Minutely15 { time, precipitation }, ..Default::default() }
+    }
+
+    #[test]
+    fn test_nowcast_description() {
+        // 2025-06-21T12:05 UTC
+        let now = 1750507500;
+
+        let weather = weather_with_nowcast(vec![0.0, 0.0, 0.4, 0.8, 0.2, 0.0, 0.0]);
+        let nowcast = Context::calculate_nowcast_timing(&weather, now).unwrap();
+        assert_eq!((nowcast.start, nowcast.end), (Some(25), Some(70)));
+        assert_eq!(
+            Context::create_nowcast_description(&nowcast).as_deref(),
+            Some("Rain starting in ~25 min, lasting ~45 min")
+        );
+
+        let weather = weather_with_nowcast(vec![0.3, 0.1, 0.0]);
+        let nowcast = Context::calculate_nowcast_timing(&weather, now).unwrap();
+        assert_eq!(
+            Context::create_nowcast_description(&nowcast).as_deref(),
+            Some("Rain stopping in ~25 min")
+        );
+
+        let weather = weather_with_nowcast(vec![0.0; 24]);
+        let nowcast = Context::calculate_nowcast_timing(&weather, now).unwrap();
+        assert_eq!(Context::create_nowcast_description(&nowcast), None);
+
+        assert_eq!(Context::approximate_minutes(95), "~1 h 35 min");
+    }
+
+    #[test]
+    fn test_compare_models() {
+        let forecast = |model: &str, high: f64, low: f64, precipitation: f64| ModelForecast {
+            model: model.to_string(),
+            time: vec!["2025-06-21".to_string(), "2025-06-22".to_string()],
+            temperature_2m_max: vec![Some(high), None],
+            temperature_2m_min: vec![Some(low), None],
+            precipitation_sum: vec![Some(precipitation), None],
+        };
+        let models = ModelComparison {
+            models: vec![forecast("ecmwf", 21.0, 10.0, 0.0), forecast("gfs", 24.0, 11.0, 6.0)],
+            ..Default::default()
+        };
+
+        let day = Context::compare_models(&models, "2025-06-21", false);
+        assert_eq!(day.models.len(), 2);
+        assert_eq!(day.temperature_spread, Some(3.0));
+        assert_eq!(day.precipitation_spread, Some(6.0));
+        assert_eq!(day.model_agreement.as_deref(), Some("poor"));
+
+        let day = Context::compare_models(&models, "2025-06-21", true);
+        assert_eq!(day.models[1].temperature_high, Some(75.2));
+        assert_eq!(day.temperature_spread, Some(5.4));
+
+        let day = Context::compare_models(&models, "2025-06-22", false);
+        assert_eq!(day.models[0].temperature_high, None);
+        assert_eq!(day.model_agreement, None);
+    }
+
+    #[test]
+    fn test_build_history() {
+        let days = ["2024-07-01", "2024-07-02"];
+        let time: Vec<String> =
+            days.iter().flat_map(|day| (0..24).map(move |hour| format!("{day}T{hour:02}:00"))).collect();
+        // Rain in the afternoon of the first day
+        let precipitation: Vec<f64> =
+            (0..48).map(|i| if (15..18).contains(&i) { 1.2 } else { 0.0 }).collect();
+        let weather = Weather {
+            daily: Daily {
+                time: days.iter().map(|day| day.to_string()).collect(),
+                weather_code: vec![61, 0],
+                sunrise: days.iter().map(|day| format!("{day}T05:10")).collect(),
+                sunset: days.iter().map(|day| format!("{day}T22:05")).collect(),
+                uv_index_max: vec![0.0; 2],
+                precipitation_sum: vec![3.6, 0.0],
+                precipitation_hours: vec![3.0, 0.0],
+                precipitation_probability_max: vec![0; 2],
+                temperature_2m_max: vec![24.0, 27.0],
+                temperature_2m_min: vec![12.0, 13.0],
+                ..Default::default()
+            },
+            hourly: Hourly {
+                temperature_2m: vec![18.0; 48],
+                precipitation_probability: vec![0; 48],
+                weather_code: vec![0; 48],
+                precipitation,
+                time,
+            },
+            created_at: 1,
+            ..Default::default()
+        };
+        let location = LocationData { city: "Edmonton".to_string(), ..Default::default() };
+
+        let context =
+            Context::build_history(weather, location, Settings { hour24: true, ..Default::default() });
+        assert!(context.history);
+        assert_eq!(context.forecast.len(), 2);
+        assert_eq!(context.hourly.len(), 48);
+        assert_eq!(context.hourly[47].time, "Tue 07/02 23:00");
+        assert_eq!(context.precipitation_start, None);
+        assert_eq!(context.precipitation_description, None);
+        assert_eq!((context.cache_age, context.location_age), (0, 0));
+    }
 }
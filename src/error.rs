@@ -0,0 +1,147 @@
+use crate::api::geolocation::AmbiguousLocation;
+
+use serde::Serialize;
+
+/// Exit code for failures that aren't one of the kinds below.
+pub const EXIT_OTHER: u8 = 1;
+
+/// The kinds of failure that scripts can tell apart by their exit code.
+///
+/// Errors are raised as one of these where they occur and then travel as `anyhow::Error`,
+/// gathering context on the way. `Error::find` recovers the kind from the final error.
+#[derive(Debug)]
+pub enum Error {
+    /// The configuration file or command line is invalid
+    Config(String),
+    /// No place matches the location, or several do
+    LocationNotFound(String),
+    /// A service couldn't be reached, e.g. offline or timed out
+    Network(String),
+    /// A service answered with an unsuccessful HTTP status
+    HttpStatus { status: u16, url: String },
+    /// A service's response couldn't be parsed
+    Parse(String),
+    /// An output template is invalid or couldn't be rendered
+    Template(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Config(message)
+            | Error::LocationNotFound(message)
+            | Error::Network(message)
+            | Error::Parse(message)
+            | Error::Template(message) => write!(f, "{message}"),
+            Error::HttpStatus { status, url } => {
+                write!(f, "HTTP request failed with status: {status} for URL: {url}")
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Finds the kind of failure an error was raised as, looking through its context.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error to inspect
+    ///
+    /// # Returns
+    ///
+    /// Returns the typed error, or `None` if it wasn't raised as one.
+    pub fn find(error: &anyhow::Error) -> Option<&Error> {
+        error.downcast_ref::<Error>()
+    }
+
+    /// Returns the kind of failure as reported to scripts, e.g. "network".
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Config(_) => "config",
+            Error::LocationNotFound(_) => "location_not_found",
+            Error::Network(_) => "network",
+            Error::HttpStatus { .. } => "http_status",
+            Error::Parse(_) => "parse",
+            Error::Template(_) => "template",
+        }
+    }
+
+    /// Returns the documented exit code of the kind of failure.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Config(_) => 2,
+            Error::LocationNotFound(_) => 3,
+            Error::Network(_) => 4,
+            Error::HttpStatus { .. } => 5,
+            Error::Parse(_) => 6,
+            Error::Template(_) => 7,
+        }
+    }
+}
+
+/// An error as reported to scripts by the exit code and the JSON and Waybar outputs.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ErrorReport {
+    pub kind: &'static str,
+    pub message: String,
+    pub exit_code: u8,
+}
+
+impl ErrorReport {
+    /// Describes an error for scripts.
+    ///
+    /// A location matching several places is reported as "location_not_found", and
+    /// errors not raised as a typed `Error` as "other".
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error to report
+    ///
+    /// # Returns
+    ///
+    /// Returns the kind, the message with its causes, and the exit code.
+    pub fn new(error: &anyhow::Error) -> Self {
+        let (kind, exit_code) = match Error::find(error) {
+            Some(typed) => (typed.kind(), typed.exit_code()),
+            None if error.is::<AmbiguousLocation>() => {
+                let not_found = Error::LocationNotFound(String::new());
+                (not_found.kind(), not_found.exit_code())
+            },
+            None => ("other", EXIT_OTHER),
+        };
+
+        ErrorReport { kind, message: format!("{error:#}"), exit_code }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_report() {
+        let error = Err::<(), _>(Error::HttpStatus { status: 429, url: "http://localhost".to_string() })
+            .context("Unable to fetch weather data")
+            .unwrap_err();
+        assert_eq!(
+            ErrorReport::new(&error),
+            ErrorReport {
+                kind: "http_status",
+                message: "Unable to fetch weather data: HTTP request failed with status: 429 for URL: http://localhost"
+                    .to_string(),
+                exit_code: 5,
+            }
+        );
+
+        let error = serde_json::from_str::<u8>("{")
+            .with_context(|| Error::Parse("Unable to parse weather response JSON".to_string()))
+            .context("Failed to fetch weather data from open-meteo")
+            .unwrap_err();
+        assert_eq!(ErrorReport::new(&error).kind, "parse");
+
+        assert_eq!(ErrorReport::new(&anyhow::anyhow!("Something else")).exit_code, EXIT_OTHER);
+    }
+}
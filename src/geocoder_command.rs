@@ -0,0 +1,50 @@
+use crate::api::endpoints::{self, Endpoint};
+use crate::api::offlinegeocoder::OfflineGeocoder;
+use crate::error::Error;
+use crate::settings::GeocoderCommand;
+use crate::Settings;
+use anyhow::Result;
+
+/// Runs a `geocoder` subcommand.
+///
+/// # Arguments
+///
+/// * `s` - Application configuration, for the configured database path and endpoints
+/// * `action` - The subcommand to run
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if the database cannot be installed or read.
+pub async fn run(s: &Settings, action: GeocoderCommand) -> Result<()> {
+    match action {
+        GeocoderCommand::Install => install(s).await,
+        GeocoderCommand::Status => status(s),
+    }
+}
+
+/// Downloads the city database from GeoNames and reports where it was installed.
+async fn install(s: &Settings) -> Result<()> {
+    let path = OfflineGeocoder::install_path(&s.geocoding).ok_or_else(|| {
+        Error::Config("There is no data directory to install the city database in".to_string())
+    })?;
+
+    let base_url = endpoints::url(&s.endpoints, Endpoint::GeoNames);
+    println!("Downloading the city database from {base_url}");
+    let geocoder = OfflineGeocoder::install(&base_url, &path).await?;
+
+    println!("Installed {} cities in {}", geocoder.len(), path.display());
+    Ok(())
+}
+
+/// Prints where the city database is installed and how many cities it has.
+fn status(s: &Settings) -> Result<()> {
+    match OfflineGeocoder::database_path(&s.geocoding) {
+        Some(path) => {
+            let geocoder = OfflineGeocoder::load(&path)?;
+            println!("{} cities in {}", geocoder.len(), path.display());
+        },
+        None => println!("The city database is not installed, run `outside geocoder install`"),
+    }
+
+    Ok(())
+}
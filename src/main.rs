@@ -1,14 +1,28 @@
 pub mod api;
+pub mod cache_command;
 pub mod context;
+pub mod error;
+pub mod geocoder_command;
 pub mod output;
 pub mod settings;
 pub mod tui;
 pub mod utils;
 
+use crate::api::airquality::AirQuality;
+use crate::api::alerts::Alerts;
+use crate::api::client;
+use crate::api::endpoints;
+use crate::api::geolocation::AmbiguousLocation;
+use crate::api::history::History;
 use crate::api::location::LocationData;
+use crate::api::models::ModelComparison;
 use crate::api::weather;
-use crate::settings::{OutputFormat, Settings, Units};
-use anyhow::Result;
+use crate::error::{Error, ErrorReport};
+use crate::settings::{Command, OutputFormat, Settings, Units};
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::io::{BufRead, IsTerminal, Write};
+use std::process::ExitCode;
 use std::time::Duration;
 use tokio::signal;
 use tokio::time::interval;
@@ -18,30 +32,87 @@ use tokio::time::interval;
 /// This function orchestrates the complete weather data pipeline:
 /// 1. Builds configuration from config file and CLI arguments
 /// 2. Resolves location data (with caching)
-/// 3. Fetches weather data from Open-Meteo API (with caching)
+/// 3. Fetches weather data from the configured provider, air quality data, alerts and any
+///    model forecasts to compare (with caching)
 /// 4. Builds context for template rendering
 /// 5. Renders and outputs the weather information in the specified format
 ///
-/// Supports both single-run mode and streaming mode for continuous output.
+/// Supports both single-run mode and streaming mode for continuous output, as well
+/// as a `history` subcommand for past weather, a `cache` subcommand to manage the cache and
+/// a `geocoder` subcommand to install the offline city database.
 /// In streaming mode, weather data is fetched and output at regular intervals
 /// until the program receives a termination signal.
 ///
+/// Errors are printed to stderr, and the JSON and Waybar outputs also print them to
+/// stdout as a structured error, see `OutputFormat::render_error`.
+///
 /// # Returns
 ///
-/// Returns `Ok(())` on success, or an error if any step fails.
+/// Returns success, or the exit code of the kind of failure, see `error::Error`.
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let config_file = dirs_next::config_dir()
         .unwrap_or_else(|| dirs_next::home_dir().unwrap_or_default())
         .join(env!("CARGO_PKG_NAME"))
         .join("config.yaml");
 
-    let s = Settings::build(vec![config_file], std::env::args_os())?;
+    let (output, result) = match Settings::build(vec![config_file], std::env::args_os())
+        .context(Error::Config("Unable to read the configuration".to_string()))
+    {
+        Ok(s) => (Some(s.output.clone()), run(s).await),
+        Err(e) => (None, Err(e)),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if let Some(rendered) = output.and_then(|output| output.render_error(&e)) {
+                println!("{rendered}");
+            }
+            eprintln!("Error: {e:?}");
+            ExitCode::from(ErrorReport::new(&e).exit_code)
+        },
+    }
+}
+
+/// Runs the mode selected by the settings.
+///
+/// # Arguments
+///
+/// * `s` - Application configuration
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if any step fails.
+async fn run(s: Settings) -> Result<()> {
+    client::configure(&s.http, s.offline)?;
+    endpoints::validate(&s.endpoints)?;
 
     // TUI mode is incompatible with streaming mode
     if s.stream && matches!(s.output, OutputFormat::Tui) {
-        eprintln!("Error: TUI mode cannot be used with streaming mode.");
-        std::process::exit(1);
+        return Err(Error::Config("TUI mode cannot be used with streaming mode.".to_string()).into());
+    }
+
+    if let Some(Command::Cache { action }) = s.command.clone() {
+        return cache_command::run(&s, action);
+    }
+
+    if let Some(Command::Geocoder { action }) = s.command.clone() {
+        return geocoder_command::run(&s, action).await;
+    }
+
+    if let Some(Command::History { date, to }) = s.command.clone() {
+        if s.stream {
+            return Err(Error::Config("history cannot be used with streaming mode.".to_string()).into());
+        }
+
+        // The TUI only shows live weather, so history defaults to the detailed output
+        let mut s = s;
+        if matches!(s.output, OutputFormat::Tui) {
+            s.output = OutputFormat::Detailed;
+        }
+
+        return run_history_mode(s, date, to.unwrap_or(date)).await;
     }
 
     if s.stream {
@@ -64,11 +135,13 @@ async fn main() -> Result<()> {
 /// # Returns
 ///
 /// Returns `Ok(())` when gracefully shutdown, or an error if critical failure occurs.
-async fn run_streaming_mode(settings: Settings) -> Result<()> {
+async fn run_streaming_mode(mut settings: Settings) -> Result<()> {
+    choose_ambiguous_location(&mut settings).await?;
     let mut timer = interval(Duration::from_secs(settings.interval));
 
     // Output immediately on startup
     if let Err(e) = output_weather_data(&settings).await {
+        report_stream_error(&settings, &e);
         eprintln!("Error fetching initial weather data: {e}");
     }
 
@@ -77,25 +150,43 @@ async fn run_streaming_mode(settings: Settings) -> Result<()> {
 
     loop {
         tokio::select! {
-            _ = timer.tick() => {
-                if let Err(e) = output_weather_data(&settings).await {
-                    eprintln!("Error fetching weather data: {e}");
+            _ = timer.tick() => {}
+            _ = signal::ctrl_c() => break,
+        }
+
+        // An interrupt also cancels a fetch in progress, which drops its requests
+        tokio::select! {
+            result = output_weather_data(&settings) => {
+                if let Err(e) = result {
                     // Continue running even if one fetch fails
-                    continue;
-                }
-            }
-            _ = signal::ctrl_c() => {
-                if cfg!(debug_assertions) {
-                    eprintln!("Received interrupt signal, shutting down gracefully");
+                    report_stream_error(&settings, &e);
+                    eprintln!("Error fetching weather data: {e}");
                 }
-                break;
             }
+            _ = signal::ctrl_c() => break,
         }
     }
 
+    if cfg!(debug_assertions) {
+        eprintln!("Received interrupt signal, shutting down gracefully");
+    }
+
     Ok(())
 }
 
+/// Shows an error in the stream of an output read by another program, e.g. so
+/// Waybar shows the failure instead of stale weather.
+///
+/// # Arguments
+///
+/// * `settings` - Application configuration with the output format
+/// * `error` - The error that prevented this update
+fn report_stream_error(settings: &Settings, error: &anyhow::Error) {
+    if let Some(rendered) = settings.output.render_error(error) {
+        println!("{rendered}");
+    }
+}
+
 /// Runs the application in single-run mode.
 ///
 /// Fetches weather data once, outputs it, and exits. This is the traditional
@@ -108,14 +199,96 @@ async fn run_streaming_mode(settings: Settings) -> Result<()> {
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error if any step fails.
-async fn run_single_mode(settings: Settings) -> Result<()> {
+async fn run_single_mode(mut settings: Settings) -> Result<()> {
+    choose_ambiguous_location(&mut settings).await?;
     output_weather_data(&settings).await
 }
 
+/// Runs the application in history mode.
+///
+/// Fetches past weather for a day or range of days from the archive, outputs it
+/// through the configured output format, and exits. The days of the range are
+/// rendered as the forecast, and noon on the first day as the current conditions.
+///
+/// # Arguments
+///
+/// * `settings` - Application configuration
+/// * `start` - First day to show
+/// * `end` - Last day to show (inclusive)
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if fetching or output fails.
+async fn run_history_mode(mut settings: Settings, start: NaiveDate, end: NaiveDate) -> Result<()> {
+    History::validate_range(start, end)?;
+    choose_ambiguous_location(&mut settings).await?;
+
+    let loc = LocationData::get_cached(settings.clone()).await?;
+    let weather = History::get_cached(loc.latitude, loc.longitude, start, end, &settings).await?;
+
+    let context = context::Context::build_history(weather, loc, settings.clone());
+    let output = settings.output.render_fn()(context, settings.clone())?;
+
+    println!("{output}");
+    Ok(())
+}
+
+/// Asks the user which place they meant when their location matches several.
+///
+/// Only prompts when running in a terminal. Otherwise, e.g. under Waybar, the
+/// ambiguity is left to surface as an error listing the candidates. The chosen
+/// place replaces the configured location for the rest of the run.
+///
+/// # Arguments
+///
+/// * `settings` - Application configuration, whose location is updated with the choice
+///
+/// # Returns
+///
+/// Returns `Ok(())` once the location is unambiguous, or an error if the lookup fails
+/// or no choice is made.
+async fn choose_ambiguous_location(settings: &mut Settings) -> Result<()> {
+    if settings.location.is_empty() || !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return Ok(());
+    }
+
+    let error = match LocationData::get_cached(settings.clone()).await {
+        Ok(_) => return Ok(()),
+        Err(error) => error,
+    };
+    let Some(ambiguous) = error.downcast_ref::<AmbiguousLocation>() else {
+        return Err(error);
+    };
+
+    eprintln!("'{}' matches several places:", ambiguous.query);
+    for (i, (location, result)) in ambiguous.candidates.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, result.describe(location));
+    }
+
+    let stdin = std::io::stdin();
+    loop {
+        eprint!("Choose a location [1-{}]: ", ambiguous.candidates.len());
+        std::io::stderr().flush()?;
+
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer)? == 0 {
+            return Err(error);
+        }
+
+        let choice =
+            answer.trim().parse::<usize>().ok().and_then(|i| ambiguous.candidates.get(i.wrapping_sub(1)));
+        if let Some((location, _)) = choice {
+            settings.location = location.clone();
+            return Ok(());
+        }
+    }
+}
+
 /// Fetches weather data and outputs it according to the configured format.
 ///
 /// This function encapsulates the core weather data pipeline that can be used
-/// in both single-run and streaming modes.
+/// in both single-run and streaming modes. Once the location is known, the forecast,
+/// air quality, alerts and model comparison are fetched concurrently.
 ///
 /// # Arguments
 ///
@@ -125,11 +298,24 @@ async fn run_single_mode(settings: Settings) -> Result<()> {
 ///
 /// Returns `Ok(())` on success, or an error if fetching or output fails.
 async fn output_weather_data(settings: &Settings) -> Result<()> {
-    let loc = LocationData::get_cached(settings.clone())?;
-    let weather = weather::Weather::get_cached(loc.latitude, loc.longitude, settings.clone())?;
+    let loc = LocationData::get_cached(settings.clone()).await?;
+    let (weather, air_quality, alerts, models) = tokio::join!(
+        weather::Weather::get_cached(loc.latitude, loc.longitude, &loc.timezone, settings.clone()),
+        AirQuality::get_cached(loc.latitude, loc.longitude, settings.clone()),
+        Alerts::get_cached(&loc, settings.clone()),
+        async {
+            if settings.models.is_empty() {
+                None
+            } else {
+                ModelComparison::get_cached(loc.latitude, loc.longitude, settings.clone()).await.ok()
+            }
+        },
+    );
 
-    let context = context::Context::build(weather, loc, settings.clone());
-    let output = settings.output.render_fn()(context, settings.clone());
+    let context =
+        context::Context::build(weather?, air_quality.ok(), alerts.ok(), models, loc, settings.clone());
+    // The TUI runs its event loop while rendering, so leave the runtime's workers to its fetches
+    let output = tokio::task::block_in_place(|| settings.output.render_fn()(context, settings.clone()))?;
 
     println!("{output}");
     Ok(())
@@ -1,8 +1,19 @@
 use crate::context::Context;
 use crate::output::Output;
 use crate::Settings;
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
+/// Past weather has no current conditions, so each day is listed with its totals,
+/// followed by every hour of the range.
+const HISTORY_TEMPLATE: &str = "{city}, {country}
+    {{ for day in forecast -}}
+    {day.date}    {day.temperature_low | round}-{day.temperature_high | round}{temperature_unit} - {day.weather_description}, {day.precipitation_sum} {precipitation_unit}
+    {{ endfor }}
+    {{ for hour in hourly -}}
+    {hour.time}    {hour.temperature | round}{temperature_unit} {hour.weather_icon}  {hour.precipitation} {precipitation_unit}
+    {{ endfor }}";
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DetailedOutput {
     pub template: String,
@@ -12,8 +23,9 @@ impl Output for DetailedOutput {
     /// Creates a new DetailedOutput instance with rendered template.
     ///
     /// Processes the context data through a comprehensive template that displays
-    /// current weather conditions, atmospheric data, and a 7-day forecast.
-    /// Uses a fixed template for consistent detailed output format.
+    /// active weather alerts, current weather conditions, atmospheric data, and a 7-day forecast.
+    /// Uses a fixed template for consistent detailed output format. Past weather is shown
+    /// as a list of days and hours instead, see `HISTORY_TEMPLATE`.
     ///
     /// # Arguments
     ///
@@ -22,20 +34,32 @@ impl Output for DetailedOutput {
     ///
     /// # Returns
     ///
-    /// Returns a DetailedOutput instance with the rendered template.
-    fn new(context: Context, _: Settings) -> Self {
-        let mut tt = Self::tt();
+    /// Returns a DetailedOutput instance with the rendered template, or a template error.
+    fn new(context: Context, _: Settings) -> Result<Self> {
+        if context.history {
+            let template = Self::render_template("text", HISTORY_TEMPLATE, &context)?;
+            return Ok(DetailedOutput { template });
+        }
 
         // Build dynamic template with precipitation timing
-        let mut template_parts = vec![
-            "{city}, {country}".to_string(),
-            "    Current:     {temperature}{temperature_unit} {weather_description}".to_string(),
+        let mut template_parts = vec!["{city}, {country}".to_string()];
+        // Add active alerts, each with its headline and when it is in effect
+        if !context.alerts.is_empty() {
+            template_parts.push("{{ for alert in alerts }}{{ if not @first }}\n{{ endif }}    Alert:       {alert.headline}\n                 {alert.onset} until {alert.expires}{{ endfor }}".to_string());
+        }
+        template_parts
+            .push("    Current:     {temperature}{temperature_unit} {weather_description}".to_string());
+        // Add the provider's own forecast text, if it has one
+        if context.narrative.is_some() {
+            template_parts.push("    Forecast:    {narrative}".to_string());
+        }
+        template_parts.extend([
             "    Feels Like:  {feels_like}{temperature_unit}".to_string(),
             "    Humidity:    {humidity}{humidity_unit}".to_string(),
             "    Pressure:    {pressure}{pressure_unit}".to_string(),
             "    Wind:        {wind_speed}{wind_speed_unit} with gusts up to {wind_gusts}{wind_speed_unit} ({wind_compass})".to_string(),
             "    UV Index:    {uv_index}".to_string(),
-        ];
+        ]);
         // Add precipitation with optional timing description
         let precip_line = if let Some(description) = &context.precipitation_description {
             format!("    Precip:      {{precipitation_sum}} {{precipitation_unit}} ({{precipitation_chance}}% chance, {description})")
@@ -53,12 +77,14 @@ impl Output for DetailedOutput {
         template_parts.push("    {{ endfor }}".to_string());
 
         let text_template = template_parts.join("\n");
-        tt.add_template("text", &text_template).expect("Failed to add text template");
+        let mut template = Self::render_template("text", &text_template, &context)?;
 
-        let template =
-            tt.render("text", &context).unwrap_or_else(|_| "Error rendering text template".to_string());
+        if !context.models.is_empty() {
+            template.push('\n');
+            template.push_str(&Self::format_model_comparison(&context));
+        }
 
-        DetailedOutput { template }
+        Ok(DetailedOutput { template })
     }
 
     /// Returns the rendered detailed weather output.
@@ -71,3 +97,77 @@ impl Output for DetailedOutput {
         self.template.clone()
     }
 }
+
+impl DetailedOutput {
+    /// Formats the daily forecasts of the compared models as a table.
+    ///
+    /// Each row is a day, with one column per model showing its low, high and
+    /// precipitation, followed by how closely the models agree. The table is built
+    /// in code rather than a template so the columns line up.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - Weather context containing the compared models
+    ///
+    /// # Returns
+    ///
+    /// Returns the comparison table as a multi-line string.
+    pub fn format_model_comparison(context: &Context) -> String {
+        let cells: Vec<Vec<String>> = context
+            .forecast
+            .iter()
+            .map(|day| {
+                day.model_comparison
+                    .models
+                    .iter()
+                    .map(|model| match (model.temperature_low, model.temperature_high) {
+                        (Some(low), Some(high)) => format!(
+                            "{:.0}-{:.0}{} {:.1}{}",
+                            low.round(),
+                            high.round(),
+                            context.temperature_unit,
+                            model.precipitation_sum.unwrap_or_default(),
+                            context.precipitation_unit
+                        ),
+                        _ => "-".to_string(),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let widths: Vec<usize> = context
+            .models
+            .iter()
+            .enumerate()
+            .map(|(i, model)| {
+                cells
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| cell.chars().count())
+                    .chain(std::iter::once(model.chars().count()))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let date_width =
+            context.forecast.iter().map(|day| day.date.chars().count()).max().unwrap_or_default();
+
+        let mut header = format!("    {:date_width$}", "Models");
+        for (model, width) in context.models.iter().zip(&widths) {
+            header.push_str(&format!("    {model:width$}"));
+        }
+        header.push_str("    Agreement");
+
+        let mut lines = vec![header];
+        for (day, row) in context.forecast.iter().zip(&cells) {
+            let mut line = format!("    {:date_width$}", day.date);
+            for (cell, width) in row.iter().zip(&widths) {
+                line.push_str(&format!("    {cell:width$}"));
+            }
+            line.push_str(&format!("    {}", day.model_comparison.model_agreement.as_deref().unwrap_or("-")));
+            lines.push(line);
+        }
+
+        lines.join("\n")
+    }
+}
@@ -1,6 +1,8 @@
 use crate::context::Context;
+use crate::error::{Error, ErrorReport};
 use crate::output::Output;
 use crate::Settings;
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -21,11 +23,12 @@ impl Output for JsonOutput {
     ///
     /// # Returns
     ///
-    /// Returns a JsonOutput instance with the serialized context data.
-    fn new(context: Context, _: Settings) -> Self {
+    /// Returns a JsonOutput instance with the serialized context data, or an error if
+    /// it cannot be serialized.
+    fn new(context: Context, _: Settings) -> Result<Self> {
         let template = serde_json::to_string(&context)
-            .unwrap_or_else(|_| "{{\"error\": \"Unable to serialize Context\"}}".to_string());
-        JsonOutput { template }
+            .map_err(|e| Error::Template(format!("Unable to serialize the context: {e}")))?;
+        Ok(JsonOutput { template })
     }
 
     /// Returns the JSON-formatted weather output.
@@ -37,3 +40,18 @@ impl Output for JsonOutput {
         self.template.clone()
     }
 }
+
+impl JsonOutput {
+    /// Renders an error as a JSON object, so scripts can tell failures apart.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error that prevented showing the weather
+    ///
+    /// # Returns
+    ///
+    /// Returns e.g. `{"error":{"kind":"network","message":"...","exit_code":4}}`.
+    pub fn error(error: &anyhow::Error) -> String {
+        serde_json::json!({ "error": ErrorReport::new(error) }).to_string()
+    }
+}
@@ -4,8 +4,10 @@ pub mod simple;
 pub mod waybar;
 
 use crate::context::Context;
+use crate::error::Error;
 use crate::Settings;
 
+use anyhow::Result;
 use std::fmt::Write;
 use tinytemplate::TinyTemplate;
 
@@ -25,7 +27,13 @@ pub trait Output {
     /// # Returns
     ///
     /// Returns a new instance of the output formatter configured with the provided data.
-    fn new(context: Context, settings: Settings) -> Self;
+    ///
+    /// # Errors
+    ///
+    /// Returns a template error if a template is invalid or cannot be rendered.
+    fn new(context: Context, settings: Settings) -> Result<Self>
+    where
+        Self: Sized;
 
     /// Renders the output as a formatted string.
     ///
@@ -50,6 +58,30 @@ pub trait Output {
         });
         tt
     }
+
+    /// Renders a template with the context, e.g. the user's Waybar text template.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - What the template is for, used in error messages
+    /// * `template` - The template text
+    /// * `context` - Weather and location data to fill in
+    ///
+    /// # Returns
+    ///
+    /// Returns the rendered text.
+    ///
+    /// # Errors
+    ///
+    /// Returns a template error if the template is invalid or refers to unknown values.
+    fn render_template(name: &str, template: &str, context: &Context) -> Result<String> {
+        let mut tt = Self::tt();
+        tt.add_template(name, template)
+            .map_err(|e| Error::Template(format!("Invalid {name} template: {e}")))?;
+        Ok(tt
+            .render(name, context)
+            .map_err(|e| Error::Template(format!("Unable to render the {name} template: {e}")))?)
+    }
 }
 
 /// Generic function to render weather data using any output format.
@@ -70,7 +102,11 @@ pub trait Output {
 /// # Returns
 ///
 /// Returns the formatted weather information as a string.
-pub fn render_output<O: Output>(context: Context, settings: Settings) -> String {
-    let output = O::new(context, settings);
-    output.render()
+///
+/// # Errors
+///
+/// Returns a template error if a template is invalid or cannot be rendered.
+pub fn render_output<O: Output>(context: Context, settings: Settings) -> Result<String> {
+    let output = O::new(context, settings)?;
+    Ok(output.render())
 }
@@ -1,6 +1,8 @@
 use crate::context::Context;
 use crate::output::Output;
 use crate::Settings;
+
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 const DEFAULT_TEMPLATE: &str =
@@ -25,17 +27,12 @@ impl Output for SimpleOutput {
     ///
     /// # Returns
     ///
-    /// Returns a SimpleOutput instance with the rendered template.
-    fn new(context: Context, settings: Settings) -> Self {
-        let mut tt = Self::tt();
+    /// Returns a SimpleOutput instance with the rendered template, or a template error.
+    fn new(context: Context, settings: Settings) -> Result<Self> {
         let text_template = settings.simple.template.unwrap_or(DEFAULT_TEMPLATE.to_string());
+        let template = Self::render_template("text", &text_template, &context)?;
 
-        tt.add_template("text", text_template.as_str()).expect("Failed to add text template");
-
-        let template =
-            tt.render("text", &context).unwrap_or_else(|_| "Error rendering text template".to_string());
-
-        SimpleOutput { template }
+        Ok(SimpleOutput { template })
     }
 
     /// Returns the rendered simple weather output.
@@ -1,12 +1,14 @@
 use crate::context::Context;
+use crate::error::ErrorReport;
 use crate::output::Output;
 use crate::utils::weather_classification;
 use crate::Settings;
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 const DEFAULT_TEXT_TEMPLATE: &str =
     "{weather_icon} {temperature | round}{temperature_unit}{{if precipitation_sum}} 󰖗 {precipitation_chance}%{{endif}}";
-const DEFAULT_TOOLTIP_TEMPLATE: &str = "{city}, {country}\n{weather_description}\nFeels Like  {feels_like} {temperature_unit}\nForecast    {temperature_low | round}-{temperature_high| round} {temperature_unit}\nHumidity    {humidity}{humidity_unit}\nPressure    {pressure} {pressure_unit}\nWind        {wind_speed}{wind_gusts} {wind_speed_unit} ({wind_compass})\nPrecip      {precipitation_sum} {precipitation_unit} ({precipitation_chance}% chance)\n{{if precipitation_description}}            {precipitation_description}{{endif}}\n {sunrise}    {sunset}";
+const DEFAULT_TOOLTIP_TEMPLATE: &str = "{{for alert in alerts}}⚠ {alert.headline}\n{{endfor}}{city}, {country}\n{weather_description}\nFeels Like  {feels_like} {temperature_unit}\nForecast    {temperature_low | round}-{temperature_high| round} {temperature_unit}\nHumidity    {humidity}{humidity_unit}\nPressure    {pressure} {pressure_unit}\nWind        {wind_speed}{wind_gusts} {wind_speed_unit} ({wind_compass})\nPrecip      {precipitation_sum} {precipitation_unit} ({precipitation_chance}% chance)\n{{if precipitation_description}}            {precipitation_description}{{endif}}\n {sunrise}    {sunset}";
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct WaybarOutput {
@@ -28,6 +30,10 @@ impl Output for WaybarOutput {
     /// - "cold" - when temperature is below configured cold threshold
     /// - Weather condition classes ("fog", "snow", "rain") based on weather codes
     ///   (see utils::weather_classification for specific ranges)
+    /// - "alert" - when there are active weather alerts for the location
+    /// - "stale" - when older cached data is shown because it couldn't be refreshed
+    /// - Air quality classes ("aqi-good", "aqi-moderate", "aqi-unhealthy", etc.)
+    ///   based on the US AQI, when air quality data is available
     ///
     /// # Arguments
     ///
@@ -36,29 +42,36 @@ impl Output for WaybarOutput {
     ///
     /// # Returns
     ///
-    /// Returns a WaybarOutput instance with formatted text, tooltip, and classes.
-    fn new(context: Context, settings: Settings) -> Self {
-        let mut tt = Self::tt();
+    /// Returns a WaybarOutput instance with formatted text, tooltip, and classes, or a
+    /// template error.
+    fn new(context: Context, settings: Settings) -> Result<Self> {
         let text_template = settings.waybar.text.unwrap_or(DEFAULT_TEXT_TEMPLATE.to_string());
         let tooltip_template = settings.waybar.tooltip.unwrap_or(DEFAULT_TOOLTIP_TEMPLATE.to_string());
 
-        tt.add_template("text", text_template.as_str()).expect("Unable to add text template");
-        tt.add_template("tooltip", tooltip_template.as_str()).expect("Unable to add tooltip template");
-
-        let text =
-            tt.render("text", &context).unwrap_or_else(|_| "Error rendering text template".to_string());
-        let tooltip =
-            tt.render("tooltip", &context).unwrap_or_else(|_| "Error rendering tooltip template".to_string());
+        let text = Self::render_template("text", &text_template, &context)?;
+        let tooltip = Self::render_template("tooltip", &tooltip_template, &context)?;
 
         // Generate all CSS classes using the centralized utility
-        let classes = weather_classification::get_all_weather_css_classes(
+        let mut classes = weather_classification::get_all_weather_css_classes(
             context.weather_code,
             context.temperature,
             settings.waybar.hot_temperature,
             settings.waybar.cold_temperature,
         );
 
-        WaybarOutput { text, tooltip, class: classes, percentage: 100 }
+        if !context.alerts.is_empty() {
+            classes.push("alert".to_string());
+        }
+
+        if context.stale {
+            classes.push("stale".to_string());
+        }
+
+        if let Some(us_aqi) = context.air_quality.us_aqi {
+            classes.push(weather_classification::get_aqi_css_class(us_aqi));
+        }
+
+        Ok(WaybarOutput { text, tooltip, class: classes, percentage: 100 })
     }
 
     /// Returns the Waybar-compatible JSON output.
@@ -71,3 +84,28 @@ impl Output for WaybarOutput {
         serde_json::to_string(self).unwrap()
     }
 }
+
+impl WaybarOutput {
+    /// Renders an error for Waybar, so the module shows it rather than disappearing.
+    ///
+    /// The text is a warning sign and the tooltip the error message. The classes are
+    /// "error" and "error-" followed by the kind, e.g. "error-network", to style them.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error that prevented showing the weather
+    ///
+    /// # Returns
+    ///
+    /// Returns the error formatted as JSON for Waybar consumption.
+    pub fn error(error: &anyhow::Error) -> String {
+        let report = ErrorReport::new(error);
+        let output = WaybarOutput {
+            text: "⚠".to_string(),
+            tooltip: report.message,
+            class: vec!["error".to_string(), format!("error-{}", report.kind.replace('_', "-"))],
+            percentage: 0,
+        };
+        output.render()
+    }
+}
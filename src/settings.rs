@@ -1,11 +1,20 @@
+use crate::api::eccc::Eccc;
+use crate::api::metno::MetNo;
+use crate::api::nws::Nws;
+use crate::api::openmeteo::OpenMeteo;
+use crate::api::weather::{Weather, WeatherProvider};
 use crate::context::Context;
 use crate::output::*;
 use crate::utils::unitstrings::UnitStrings;
 use crate::Settings as OutsideSettings;
 
-use clap::ValueEnum;
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::{Subcommand, ValueEnum};
 use cli_settings_derive::cli_settings;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
 
 #[derive(ValueEnum, Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
 pub enum Units {
@@ -61,7 +70,7 @@ impl OutputFormat {
     ///
     /// Returns a function pointer that takes a `Context` and `OutsideSettings`
     /// and returns a formatted string for the selected output format.
-    pub fn render_fn(&self) -> fn(Context, OutsideSettings) -> String {
+    pub fn render_fn(&self) -> fn(Context, OutsideSettings) -> Result<String> {
         match self {
             OutputFormat::Simple => render_output::<simple::SimpleOutput>,
             OutputFormat::Detailed => render_output::<detailed::DetailedOutput>,
@@ -70,6 +79,217 @@ impl OutputFormat {
             OutputFormat::Tui => render_output::<crate::tui::TuiOutput>,
         }
     }
+
+    /// Renders an error for output formats read by other programs.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error that prevented showing the weather
+    ///
+    /// # Returns
+    ///
+    /// Returns a structured error for the JSON and Waybar outputs, or `None` for the
+    /// formats meant for people, which report errors on stderr only.
+    pub fn render_error(&self, error: &anyhow::Error) -> Option<String> {
+        match self {
+            OutputFormat::Json => Some(json::JsonOutput::error(error)),
+            OutputFormat::Waybar => Some(waybar::WaybarOutput::error(error)),
+            OutputFormat::Simple | OutputFormat::Detailed | OutputFormat::Tui => None,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub enum Provider {
+    #[default]
+    OpenMeteo,
+    MetNo,
+    Nws,
+    /// Environment and Climate Change Canada citypage forecasts (Canada only)
+    Eccc,
+}
+
+impl Provider {
+    /// Returns the string representation of the provider for cache keys and messages.
+    ///
+    /// # Returns
+    ///
+    /// Returns the provider name, e.g. "open-meteo".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Provider::OpenMeteo => "open-meteo",
+            Provider::MetNo => "met-no",
+            Provider::Nws => "nws",
+            Provider::Eccc => "eccc",
+        }
+    }
+
+    /// Returns how often the provider publishes new data, when it is known.
+    ///
+    /// Open-Meteo updates its current conditions every 15 minutes, at the quarter hour.
+    ///
+    /// # Returns
+    ///
+    /// Returns the update interval in seconds, or `None` if the provider doesn't
+    /// publish on a fixed schedule.
+    pub fn update_interval(&self) -> Option<u64> {
+        match self {
+            Provider::OpenMeteo => Some(900),
+            Provider::MetNo | Provider::Nws | Provider::Eccc => None,
+        }
+    }
+
+    /// Fetches weather data from the selected weather provider.
+    ///
+    /// Each provider has its own implementation of the `WeatherProvider` trait,
+    /// and this method dispatches to the correct one.
+    ///
+    /// # Arguments
+    ///
+    /// * `lat` - Latitude coordinate for the weather location
+    /// * `lon` - Longitude coordinate for the weather location
+    /// * `timezone` - IANA timezone of the location, or empty if unknown
+    /// * `s` - Settings with the endpoints and cache directory
+    ///
+    /// # Returns
+    ///
+    /// Returns provider-neutral `Weather` data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider's API request fails or the response cannot be parsed.
+    pub async fn fetch(&self, lat: f64, lon: f64, timezone: &str, s: &OutsideSettings) -> Result<Weather> {
+        match self {
+            Provider::OpenMeteo => OpenMeteo::fetch(lat, lon, timezone, s).await,
+            Provider::MetNo => MetNo::fetch(lat, lon, timezone, s).await,
+            Provider::Nws => Nws::fetch(lat, lon, timezone, s).await,
+            Provider::Eccc => Eccc::fetch(lat, lon, timezone, s).await,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Model {
+    /// ECMWF IFS (European Centre)
+    Ecmwf,
+    /// NOAA GFS (United States)
+    Gfs,
+    /// DWD ICON (Germany)
+    Icon,
+    /// ECCC GEM (Canada)
+    Gem,
+    /// Météo-France ARPEGE/AROME
+    Meteofrance,
+    /// UK Met Office
+    Ukmo,
+    /// JMA (Japan)
+    Jma,
+    /// MET Norway Nordic
+    Metno,
+}
+
+impl Model {
+    /// Returns the short model name for display and cache keys.
+    ///
+    /// # Returns
+    ///
+    /// Returns the model name, e.g. "ecmwf".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Model::Ecmwf => "ecmwf",
+            Model::Gfs => "gfs",
+            Model::Icon => "icon",
+            Model::Gem => "gem",
+            Model::Meteofrance => "meteofrance",
+            Model::Ukmo => "ukmo",
+            Model::Jma => "jma",
+            Model::Metno => "metno",
+        }
+    }
+
+    /// Returns the Open-Meteo model identifier used in API calls.
+    ///
+    /// The "seamless" variants blend each provider's global and regional models.
+    ///
+    /// # Returns
+    ///
+    /// Returns the Open-Meteo model name, e.g. "ecmwf_ifs025".
+    pub fn api_name(&self) -> &'static str {
+        match self {
+            Model::Ecmwf => "ecmwf_ifs025",
+            Model::Gfs => "gfs_seamless",
+            Model::Icon => "icon_seamless",
+            Model::Gem => "gem_seamless",
+            Model::Meteofrance => "meteofrance_seamless",
+            Model::Ukmo => "ukmo_seamless",
+            Model::Jma => "jma_seamless",
+            Model::Metno => "metno_seamless",
+        }
+    }
+}
+
+#[derive(Subcommand, Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Show past weather for a day or range of days
+    History {
+        /// Day to show, e.g. 2024-07-01
+        #[arg(long)]
+        date: NaiveDate,
+
+        /// Last day of a range starting at --date
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
+    /// Inspect and clean up cached data
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+    /// Install the city database for offline geocoding
+    Geocoder {
+        #[command(subcommand)]
+        action: GeocoderCommand,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug, PartialEq)]
+pub enum GeocoderCommand {
+    /// Download the GeoNames city database and install it
+    Install,
+    /// Show where the city database is installed and how many cities it has
+    Status,
+}
+
+#[derive(Subcommand, Clone, Debug, PartialEq)]
+pub enum CacheCommand {
+    /// List the cached entries
+    List,
+    /// Print a cached entry as JSON
+    Show {
+        /// File name of the entry, as shown by `cache list`
+        entry: String,
+    },
+    /// Delete the cached entries of a location or data type
+    #[command(group(clap::ArgGroup::new("filter").required(true).multiple(true)))]
+    Purge {
+        /// Location the entries were fetched for, as given to outside --location
+        #[arg(long, group = "filter")]
+        location: Option<String>,
+
+        /// Type of data, e.g. weather or location
+        #[arg(long = "type", group = "filter")]
+        datatype: Option<String>,
+
+        /// Delete every entry, except the TUI's saved locations unless --type locations is given
+        #[arg(long, group = "filter")]
+        all: bool,
+    },
+    /// Delete entries older than a number of days, except the TUI's saved locations
+    Prune {
+        /// Minimum age in days of the entries to delete
+        #[arg(long)]
+        days: u64,
+    },
 }
 
 #[serde_with::skip_serializing_none]
@@ -87,14 +307,151 @@ pub struct SimpleConfig {
     pub template: Option<String>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub enum GeocodingMode {
+    /// Use the offline city database when it is installed, and the network otherwise
+    #[default]
+    Auto,
+    /// Always use the Open-Meteo geocoding API
+    Online,
+    /// Only use the offline city database
+    Offline,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct GeocodingConfig {
+    pub mode: Option<GeocodingMode>,
+    pub database: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum IpLocationProvider {
+    /// ipinfo.io over HTTPS, optionally with an access token
+    IpInfo,
+    /// ip-api.com, which is HTTP only on the free plan
+    IpApi,
+    /// A self-hosted endpoint returning JSON, set with `custom_url`
+    Custom,
+    /// A GPS receiver through a local gpsd, see the `gps` settings
+    Gps,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct IpLocationConfig {
+    pub providers: Option<Vec<IpLocationProvider>>,
+    pub ipinfo_token: Option<String>,
+    pub custom_url: Option<String>,
+    pub fallback: Option<String>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct GpsConfig {
+    pub address: Option<String>,
+    pub timeout: Option<u64>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct EndpointsConfig {
+    pub forecast: Option<String>,
+    pub archive: Option<String>,
+    pub air_quality: Option<String>,
+    pub geocoding: Option<String>,
+    pub reverse_geocoding: Option<String>,
+    pub met_no: Option<String>,
+    pub nws: Option<String>,
+    pub eccc: Option<String>,
+    pub meteoalarm: Option<String>,
+    pub ipinfo: Option<String>,
+    pub ip_api: Option<String>,
+    pub geonames: Option<String>,
+    pub api_key: Option<String>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct HttpConfig {
+    pub user_agent: Option<String>,
+    pub proxy: Option<String>,
+    pub ca_bundle: Option<String>,
+    pub connect_timeout: Option<u64>,
+    pub timeout: Option<u64>,
+    pub retries: Option<usize>,
+    pub max_backoff: Option<u64>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct CacheConfig {
+    pub weather: Option<u64>,
+    pub location: Option<u64>,
+    pub gps: Option<u64>,
+    pub air_quality: Option<u64>,
+    pub alerts: Option<u64>,
+    pub models: Option<u64>,
+    pub max_stale: Option<u64>,
+    pub dir: Option<String>,
+}
+
+impl CacheConfig {
+    /// Returns the directory cached data is kept in, by default `outside` in the user's cache directory.
+    pub fn dir(&self) -> PathBuf {
+        match &self.dir {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs_next::cache_dir()
+                .unwrap_or_else(|| dirs_next::home_dir().unwrap_or_default())
+                .join(env!("CARGO_PKG_NAME")),
+        }
+    }
+
+    /// Returns how long weather forecasts are used before they are refreshed, in seconds.
+    pub fn weather_seconds(&self) -> u64 {
+        self.weather.unwrap_or(600)
+    }
+
+    /// Returns how long looked up locations are cached, in seconds.
+    pub fn location_seconds(&self) -> u64 {
+        self.location.unwrap_or(14400)
+    }
+
+    /// Returns how long GPS positions are cached, in seconds, short as the receiver may be moving.
+    pub fn gps_seconds(&self) -> u64 {
+        self.gps.unwrap_or(60)
+    }
+
+    /// Returns how long air quality data is cached, in seconds.
+    pub fn air_quality_seconds(&self) -> u64 {
+        self.air_quality.unwrap_or(3600)
+    }
+
+    /// Returns how long weather alerts are cached, in seconds.
+    pub fn alerts_seconds(&self) -> u64 {
+        self.alerts.unwrap_or(300)
+    }
+
+    /// Returns how long model forecasts are cached, in seconds.
+    pub fn models_seconds(&self) -> u64 {
+        self.models.unwrap_or(600)
+    }
+
+    /// Returns how old cached weather may be and still be shown when it can't be refreshed,
+    /// in seconds.
+    pub fn max_stale_seconds(&self) -> u64 {
+        self.max_stale.unwrap_or(10800)
+    }
+}
+
 #[derive(Debug, Clone)]
 #[cli_settings]
 #[cli_settings_file = "#[serde_with::serde_as]#[derive(serde::Deserialize)]"]
 /// A multi-purpose weather client for your terminal
 #[cli_settings_clap = "#[derive(clap::Parser)]#[command(name = \"outside\", version, verbatim_doc_comment)]"]
 pub struct Settings {
-    /// Location to fetch weather data for,
-    /// leave blank to auto-detect using your IP address
+    /// Location to fetch weather data for, as "City, [Region,] CountryCode" or "latitude,longitude",
+    /// "gps" to read a GPS receiver through gpsd, or leave blank to auto-detect using your IP address
     #[cli_settings_file]
     #[cli_settings_clap = "#[arg(short, long, verbatim_doc_comment)]"]
     pub location: String,
@@ -104,6 +461,19 @@ pub struct Settings {
     #[cli_settings_clap = "#[arg(short, long, verbatim_doc_comment)]"]
     pub units: Units,
 
+    /// Weather data provider
+    #[cli_settings_file]
+    #[cli_settings_clap = "#[arg(short, long, verbatim_doc_comment)]"]
+    pub provider: Provider,
+
+    #[cli_settings_file]
+    pub places: HashMap<String, String>,
+
+    /// Forecast models to compare side by side, e.g. ecmwf,gfs,icon
+    #[cli_settings_file]
+    #[cli_settings_clap = "#[arg(short, long, value_delimiter = ',', verbatim_doc_comment)]"]
+    pub models: Vec<Model>,
+
     /// Display format
     #[cli_settings_clap = "#[arg(short, long, verbatim_doc_comment)]"]
     pub output: OutputFormat,
@@ -121,9 +491,35 @@ pub struct Settings {
     #[cli_settings_clap = "#[arg(long = \"24\", action = clap::ArgAction::SetTrue, verbatim_doc_comment)]"]
     pub hour24: bool,
 
+    /// Only show cached data, without touching the network
+    #[cli_settings_clap = "#[arg(long, action = clap::ArgAction::SetTrue, verbatim_doc_comment)]"]
+    pub offline: bool,
+
+    #[cli_settings_mandatory]
+    #[cli_settings_clap = "#[command(subcommand)]"]
+    pub command: Option<Command>,
+
     #[cli_settings_file]
     pub simple: SimpleConfig,
 
     #[cli_settings_file]
     pub waybar: WaybarConfig,
+
+    #[cli_settings_file]
+    pub geocoding: GeocodingConfig,
+
+    #[cli_settings_file]
+    pub ip_location: IpLocationConfig,
+
+    #[cli_settings_file]
+    pub gps: GpsConfig,
+
+    #[cli_settings_file]
+    pub http: HttpConfig,
+
+    #[cli_settings_file]
+    pub endpoints: EndpointsConfig,
+
+    #[cli_settings_file]
+    pub cache: CacheConfig,
 }
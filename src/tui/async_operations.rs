@@ -1,13 +1,17 @@
+use crate::api::airquality::AirQuality;
+use crate::api::alerts::Alerts;
 use crate::api::location::LocationData;
+use crate::api::models::ModelComparison;
 use crate::api::weather::Weather;
 use crate::context::Context;
+use crate::settings::Settings;
 use crate::tui::constants::*;
+use crate::tui::location_manager::LocationManager;
 use crate::tui::state_manager::TuiStateManager;
 use crate::tui::ui_components::UiComponents;
 use crate::tui::weather_display::WeatherDisplay;
 use cursive::views::{ProgressBar, TextView};
 use cursive::Cursive;
-use std::thread;
 
 pub struct WeatherFetcher {
     state_manager: TuiStateManager,
@@ -39,9 +43,10 @@ impl WeatherFetcher {
         let location_clone = location.clone();
         let state_manager_clone = self.state_manager.clone();
 
-        // Spawn background thread to fetch weather data
-        thread::spawn(move || {
-            if let Ok(result) = Self::fetch_weather_for_location(&location_clone, &state_manager_clone) {
+        // Spawn a background task to fetch weather data
+        tokio::spawn(async move {
+            if let Ok(result) = Self::fetch_weather_for_location(&location_clone, &state_manager_clone).await
+            {
                 // Send callback to update UI on main thread
                 cb_sink
                     .send(Box::new(move |s| {
@@ -65,6 +70,7 @@ impl WeatherFetcher {
 
     pub fn switch_location(&self, siv: &mut Cursive, location: String) {
         let location_clone = location.clone();
+        let refreshed_location = location.clone();
         self.fetch_and_update(
             location,
             siv,
@@ -73,8 +79,14 @@ impl WeatherFetcher {
                 state_manager.update_context_with_location(context, location_clone.clone());
                 UiComponents::update_weather_display_components(s, state_manager);
             },
-            |s, _state_manager, error_message| {
-                Self::show_error_dialog(s, &error_message);
+            move |s, state_manager, error_message| {
+                if state_manager.get_current_location() == refreshed_location {
+                    // Keep showing the weather we have when refreshing it fails, e.g. offline
+                    state_manager.mark_stale("Unable to refresh the forecast".to_string());
+                    UiComponents::update_weather_display_components(s, state_manager);
+                } else {
+                    Self::show_error_dialog(s, &error_message);
+                }
             },
         );
     }
@@ -98,8 +110,10 @@ impl WeatherFetcher {
         let cb_sink = siv.cb_sink().clone();
         let state_manager_clone = self.state_manager.clone();
 
-        thread::spawn(move || {
-            if let Ok(result) = Self::fetch_weather_for_location(&current_location, &state_manager_clone) {
+        tokio::spawn(async move {
+            if let Ok(result) =
+                Self::fetch_weather_for_location(&current_location, &state_manager_clone).await
+            {
                 let location_for_update = current_location.clone();
                 cb_sink
                     .send(Box::new(move |s| {
@@ -120,30 +134,48 @@ impl WeatherFetcher {
         });
     }
 
-    pub fn setup_auto_refresh(&self, siv: &mut Cursive) {
+    pub fn setup_auto_refresh(&self, siv: &mut Cursive, location_manager: LocationManager) {
         let cb_sink = siv.cb_sink().clone();
         let state_manager_clone = self.state_manager.clone();
+        let saved_locations =
+            move || location_manager.get_location_list().lock().unwrap().get_sorted_locations().0;
 
-        thread::spawn(move || loop {
-            thread::sleep(std::time::Duration::from_secs(AUTO_REFRESH_INTERVAL));
+        tokio::spawn(async move {
+            // Warm the cache of every saved location, so switching between them is instant
+            Self::refresh_saved_locations(saved_locations(), &state_manager_clone).await;
 
-            if state_manager_clone.needs_refresh() {
-                // Fetch new data when cache expires
-                let current_location = state_manager_clone.get_current_location();
-                let state_for_refresh = state_manager_clone.clone();
+            let mut timer = tokio::time::interval(std::time::Duration::from_secs(AUTO_REFRESH_INTERVAL));
+            // Skip the first tick since interval.tick() fires immediately
+            timer.tick().await;
 
-                let _ = cb_sink.send(Box::new(move |s| {
-                    let fetcher = WeatherFetcher::new(state_for_refresh);
-                    fetcher.switch_location(s, current_location);
-                }));
-            } else {
-                // Update display to show current cache age without fetching new data
-                let state_for_display = state_manager_clone.clone();
-                let _ = cb_sink.send(Box::new(move |s| {
-                    // Update cache_age in context to current time difference
-                    state_for_display.update_cache_age();
-                    UiComponents::update_weather_display_components(s, &state_for_display);
-                }));
+            loop {
+                timer.tick().await;
+
+                let sent = if state_manager_clone.needs_refresh() {
+                    // Refresh all saved locations at once when the cache expires, then
+                    // show the current one from the fresh cache
+                    Self::refresh_saved_locations(saved_locations(), &state_manager_clone).await;
+                    let current_location = state_manager_clone.get_current_location();
+                    let state_for_refresh = state_manager_clone.clone();
+
+                    cb_sink.send(Box::new(move |s| {
+                        let fetcher = WeatherFetcher::new(state_for_refresh);
+                        fetcher.switch_location(s, current_location);
+                    }))
+                } else {
+                    // Update display to show current cache age without fetching new data
+                    let state_for_display = state_manager_clone.clone();
+                    cb_sink.send(Box::new(move |s| {
+                        // Update cache_age in context to current time difference
+                        state_for_display.update_cache_age();
+                        UiComponents::update_weather_display_components(s, &state_for_display);
+                    }))
+                };
+
+                // Stop once the TUI has exited
+                if sent.is_err() {
+                    break;
+                }
             }
         });
     }
@@ -169,33 +201,81 @@ impl WeatherFetcher {
         }));
     }
 
-    fn fetch_weather_for_location(
+    /// Refreshes the weather of every saved location whose cache expired, in as few
+    /// requests as the provider allows.
+    ///
+    /// Failures are ignored: each location is fetched again on its own when shown.
+    async fn refresh_saved_locations(locations: Vec<String>, state_manager: &TuiStateManager) {
+        let mut batch = Vec::new();
+        for location in locations {
+            let Ok(settings) = Self::settings_for_location(&location, state_manager) else {
+                continue;
+            };
+            if let Ok(location_data) = LocationData::get_cached(settings.clone()).await {
+                batch.push((
+                    settings.location,
+                    location_data.latitude,
+                    location_data.longitude,
+                    location_data.timezone,
+                ));
+            }
+        }
+
+        let _ = Weather::get_cached_batch(&batch, &state_manager.get_settings()).await;
+    }
+
+    fn settings_for_location(
         location: &str,
         state_manager: &TuiStateManager,
-    ) -> Result<Context, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<Settings, Box<dyn std::error::Error + Send + Sync>> {
         let mut settings = state_manager.get_settings();
 
         // Handle special "Automatic" case for IP-based lookup
         if location == "Automatic" {
             settings.location = String::new(); // Empty string triggers IP lookup
         } else {
-            // Parse location for geocoding API
+            // Accept "City, [Region,] Country" for the geocoding API, coordinates, or "gps"
             let parts: Vec<&str> = location.split(',').collect();
-            if parts.len() != 2 {
+            if !(2..=3).contains(&parts.len())
+                && LocationData::parse_coordinates(location).is_none()
+                && !LocationData::is_gps(location)
+            {
                 return Err("Invalid location format".into());
             }
             settings.location = location.to_string();
         }
 
+        Ok(settings)
+    }
+
+    async fn fetch_weather_for_location(
+        location: &str,
+        state_manager: &TuiStateManager,
+    ) -> Result<Context, Box<dyn std::error::Error + Send + Sync>> {
+        let settings = Self::settings_for_location(location, state_manager)?;
+
         // Fetch location data
-        let location_data = LocationData::get_cached(settings.clone())?;
+        let location_data = LocationData::get_cached(settings.clone()).await?;
+        let (lat, lon) = (location_data.latitude, location_data.longitude);
 
-        // Fetch weather data
-        let weather_data =
-            Weather::get_cached(location_data.latitude, location_data.longitude, settings.clone())?;
+        // Fetch weather data along with the optional air quality, alerts and, only when a
+        // comparison was requested, forecasts from other models
+        let (weather_data, air_quality, alerts, models) = tokio::join!(
+            Weather::get_cached(lat, lon, &location_data.timezone, settings.clone()),
+            AirQuality::get_cached(lat, lon, settings.clone()),
+            Alerts::get_cached(&location_data, settings.clone()),
+            async {
+                if settings.models.is_empty() {
+                    None
+                } else {
+                    ModelComparison::get_cached(lat, lon, settings.clone()).await.ok()
+                }
+            },
+        );
 
         // Build context
-        let context = Context::build(weather_data, location_data, settings);
+        let context =
+            Context::build(weather_data?, air_quality.ok(), alerts.ok(), models, location_data, settings);
 
         Ok(context)
     }
@@ -1,10 +1,13 @@
+use crate::api::geolocation::AmbiguousLocation;
 use crate::api::location::LocationData;
 use crate::tui::async_operations::WeatherFetcher;
 use crate::tui::constants::*;
 use crate::tui::location_manager::LocationManager;
 use crate::tui::state_manager::TuiStateManager;
 use crate::tui::ui_components::UiComponents;
-use cursive::views::{Dialog, EditView, SelectView};
+use crate::tui::weather_display::WeatherDisplay;
+use cursive::traits::Scrollable;
+use cursive::views::{Dialog, EditView, SelectView, TextView};
 use cursive::Cursive;
 
 pub struct KeyboardHandlers;
@@ -76,10 +79,15 @@ impl KeyboardHandlers {
                         .call_on_name(NEW_LOCATION_NAME, |view: &mut EditView| view.get_content())
                         .unwrap()
                         .to_string();
+                    let alias = s
+                        .call_on_name(NEW_LOCATION_ALIAS_NAME, |view: &mut EditView| view.get_content())
+                        .map(|alias| alias.trim().to_string())
+                        .filter(|alias| !alias.is_empty());
 
                     if !location.is_empty() {
+                        // Named places from the configuration are resolved to the location they stand for
                         let normalized_location = if location != "Automatic" {
-                            LocationData::normalize_location_string(&location)
+                            location_manager_for_add.get_current_location_string(&location)
                         } else {
                             location.clone()
                         };
@@ -109,6 +117,7 @@ impl KeyboardHandlers {
                             location_manager_for_add.clone(),
                             &weather_fetcher_for_add,
                             normalized_location,
+                            alias,
                         );
                     }
                     s.pop_layer();
@@ -188,10 +197,87 @@ impl KeyboardHandlers {
 
     fn add_and_switch_location(
         siv: &mut Cursive,
-        _state_manager: &TuiStateManager,
+        state_manager: &TuiStateManager,
         location_manager: LocationManager,
         weather_fetcher: &WeatherFetcher,
         location: String,
+        alias: Option<String>,
+    ) {
+        if location == "Automatic" {
+            Self::switch_to_new_location(siv, location_manager, weather_fetcher, location, alias);
+            return;
+        }
+
+        siv.call_on_name(WEATHER_HEADER_NAME, |view: &mut TextView| {
+            view.set_content(WeatherDisplay::format_loading_message());
+        });
+
+        // Look the location up first, so a name shared by several places can be picked from a list
+        let cb_sink = siv.cb_sink().clone();
+        let mut settings = state_manager.get_settings();
+        let state_manager = state_manager.clone();
+        let weather_fetcher = weather_fetcher.clone();
+        settings.location = location.clone();
+
+        tokio::spawn(async move {
+            let ambiguous = LocationData::get_cached(settings)
+                .await
+                .err()
+                .and_then(|error| error.downcast_ref::<AmbiguousLocation>().cloned());
+
+            cb_sink
+                .send(Box::new(move |s| match ambiguous {
+                    Some(ambiguous) => {
+                        UiComponents::update_weather_display_components(s, &state_manager);
+                        Self::show_candidates_dialog(s, ambiguous, location_manager, weather_fetcher, alias);
+                    },
+                    None => {
+                        Self::switch_to_new_location(s, location_manager, &weather_fetcher, location, alias)
+                    },
+                }))
+                .unwrap();
+        });
+    }
+
+    fn show_candidates_dialog(
+        siv: &mut Cursive,
+        ambiguous: AmbiguousLocation,
+        location_manager: LocationManager,
+        weather_fetcher: WeatherFetcher,
+        alias: Option<String>,
+    ) {
+        let mut select = SelectView::<String>::new();
+        for (location, result) in &ambiguous.candidates {
+            select.add_item(result.describe(location), location.clone());
+        }
+
+        select.set_on_submit(move |s, location: &String| {
+            s.pop_layer();
+            Self::switch_to_new_location(
+                s,
+                location_manager.clone(),
+                &weather_fetcher,
+                location.clone(),
+                alias.clone(),
+            );
+        });
+
+        siv.add_layer(
+            Dialog::around(select.scrollable()).title(format!("Which {}?", ambiguous.query)).button(
+                "Cancel",
+                |s| {
+                    s.pop_layer();
+                },
+            ),
+        );
+    }
+
+    fn switch_to_new_location(
+        siv: &mut Cursive,
+        location_manager: LocationManager,
+        weather_fetcher: &WeatherFetcher,
+        location: String,
+        alias: Option<String>,
     ) {
         let location_clone = location.clone();
         let location_manager_clone = location_manager.clone();
@@ -203,8 +289,11 @@ impl KeyboardHandlers {
                 // Update both context and currently selected location
                 state_manager.update_context_with_location(result, location_clone.clone());
 
-                // Add to location list
+                // Add to location list, with the name given to it if any
                 location_manager_clone.add_location(location_clone.clone());
+                if let Some(alias) = alias.clone() {
+                    location_manager_clone.set_alias(&location_clone, alias);
+                }
 
                 // Update the select view and select the new location
                 s.call_on_name(LOCATION_LIST_NAME, |view: &mut SelectView<String>| {
@@ -1,43 +1,54 @@
 use crate::api::location::LocationData;
-use crate::utils::cache;
+use crate::utils::cache::{self, CacheHeader};
 use cursive::views::SelectView;
-use savefile::prelude::*;
 use savefile_derive::Savefile;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+/// Version 1 added the names given to locations in the TUI
+pub const LOCATION_LIST_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize, Debug, Default, Savefile)]
 pub struct LocationList {
     pub locations: Vec<String>,
+    #[savefile_versions = "1.."]
+    pub aliases: HashMap<String, String>,
 }
 
 impl LocationList {
-    pub fn load() -> Self {
-        let filename = cache::get_cached_file("locations", "list");
-        load_file(&filename, 0).unwrap_or_default()
+    pub fn load(dir: &Path) -> Self {
+        cache::load(dir, &Self::cache_header(), "list").unwrap_or_default()
     }
 
-    pub fn save(&self) {
-        let filename = cache::get_cached_file("locations", "list");
-        if let Err(e) = save_file(&filename, 0, self) {
+    pub fn save(&self, dir: &Path) {
+        if let Err(e) = cache::save(dir, &Self::cache_header(), "list", self) {
             eprintln!("Unable to save location list: {e:#?}");
         }
     }
 
+    fn cache_header() -> CacheHeader {
+        CacheHeader::new("locations", LOCATION_LIST_VERSION, "list")
+    }
+
     pub fn add_location(&mut self, location: String) {
         if !self.locations.contains(&location) {
             self.locations.push(location);
-            self.save();
         }
     }
 
     pub fn remove_location_by_name(&mut self, location: &str) {
         if let Some(index) = self.locations.iter().position(|loc| loc == location) {
             self.locations.remove(index);
-            self.save();
+            self.aliases.remove(location);
         }
     }
 
+    pub fn set_alias(&mut self, location: &str, alias: String) {
+        self.aliases.insert(location.to_string(), alias);
+    }
+
     /// Returns a sorted list of locations and the index of the specified location
     pub fn get_sorted_locations_with_index(&self, target_location: &str) -> (Vec<String>, Option<usize>) {
         let (sorted_locations, _) = self.get_sorted_locations();
@@ -89,18 +100,16 @@ impl LocationList {
 #[derive(Clone)]
 pub struct LocationManager {
     location_list: Arc<Mutex<LocationList>>,
-}
-
-impl Default for LocationManager {
-    fn default() -> Self {
-        Self::new()
-    }
+    places: HashMap<String, String>,
+    cache_dir: PathBuf,
 }
 
 impl LocationManager {
-    pub fn new() -> Self {
-        let location_list = Arc::new(Mutex::new(LocationList::load()));
-        Self { location_list }
+    /// Creates a location manager for the bookmarks saved in the cache directory and the named
+    /// places from the configuration.
+    pub fn new(places: HashMap<String, String>, cache_dir: PathBuf) -> Self {
+        let location_list = Arc::new(Mutex::new(LocationList::load(&cache_dir)));
+        Self { location_list, places, cache_dir }
     }
 
     pub fn get_location_list(&self) -> Arc<Mutex<LocationList>> {
@@ -110,21 +119,50 @@ impl LocationManager {
     pub fn add_location(&self, location: String) {
         let mut list = self.location_list.lock().unwrap();
         list.add_location(location);
+        list.save(&self.cache_dir);
     }
 
     pub fn remove_location_by_name(&self, location: &str) {
         let mut list = self.location_list.lock().unwrap();
         list.remove_location_by_name(location);
+        list.save(&self.cache_dir);
+    }
+
+    pub fn set_alias(&self, location: &str, alias: String) {
+        let mut list = self.location_list.lock().unwrap();
+        list.set_alias(location, alias);
+        list.save(&self.cache_dir);
+    }
+
+    /// Returns the name to show for a location: a name given in the TUI, then a named
+    /// place from the configuration, and otherwise the location itself.
+    pub fn display_name(&self, location: &str) -> String {
+        let list = self.location_list.lock().unwrap();
+        if let Some(alias) = list.aliases.get(location) {
+            return alias.clone();
+        }
+
+        let mut names: Vec<&String> = self
+            .places
+            .iter()
+            .filter(|(_, place)| LocationData::normalize_location_string(place) == location)
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+
+        names.first().map_or_else(|| location.to_string(), |name| name.to_string())
     }
 
     pub fn rebuild_select_view(&self, view: &mut SelectView<String>, target_location: &str) -> Option<usize> {
         let list = self.location_list.lock().unwrap();
         let (sorted_locations, target_index) = list.get_sorted_locations_with_index(target_location);
 
+        drop(list);
+
         // Clear and rebuild the SelectView with sorted locations
         view.clear();
         for location in &sorted_locations {
-            view.add_item(location.clone(), location.clone());
+            view.add_item(self.display_name(location), location.clone());
         }
 
         target_index
@@ -134,7 +172,9 @@ impl LocationManager {
         if settings_location.is_empty() {
             "Automatic".to_string()
         } else {
-            LocationData::normalize_location_string(settings_location)
+            let location = LocationData::resolve_place(settings_location, &self.places)
+                .unwrap_or_else(|_| settings_location.to_string());
+            LocationData::normalize_location_string(&location)
         }
     }
 
@@ -142,6 +182,7 @@ impl LocationManager {
         let mut list = self.location_list.lock().unwrap();
         if !list.locations.contains(&location) {
             list.add_location(location);
+            list.save(&self.cache_dir);
         }
     }
 }
@@ -9,6 +9,7 @@ pub mod weather_display;
 use crate::context::Context;
 use crate::output::Output;
 use crate::Settings;
+use anyhow::Result;
 use async_operations::WeatherFetcher;
 use cursive::views::ResizedView;
 use cursive::{Cursive, CursiveExt};
@@ -37,8 +38,8 @@ impl Output for TuiOutput {
     /// # Returns
     ///
     /// Returns a TuiOutput instance with the provided context.
-    fn new(context: Context, settings: Settings) -> Self {
-        TuiOutput { context, settings }
+    fn new(context: Context, settings: Settings) -> Result<Self> {
+        Ok(TuiOutput { context, settings })
     }
 
     /// Renders the TUI interface and returns empty string.
@@ -70,7 +71,7 @@ impl TuiOutput {
 
         // Initialize managers
         let state_manager = TuiStateManager::new(self.context.clone(), self.settings.clone());
-        let location_manager = LocationManager::new();
+        let location_manager = LocationManager::new(self.settings.places.clone(), self.settings.cache.dir());
         let weather_fetcher = WeatherFetcher::new(state_manager.clone());
 
         // Add current location to list if not present
@@ -82,9 +83,7 @@ impl TuiOutput {
         siv.add_fullscreen_layer(ResizedView::with_full_screen(main_layout));
 
         // Set initial progress bar value based on current cache age
-        let context = state_manager.get_context();
-        let initial_progress = ((context.cache_age as f64 / constants::WEATHER_CACHE_DURATION as f64) * 100.0)
-            .min(100.0) as usize;
+        let initial_progress = state_manager.refresh_progress();
         siv.call_on_name(constants::DATA_AGE_PROGRESS_NAME, |view: &mut cursive::views::ProgressBar| {
             view.set_value(initial_progress);
         });
@@ -93,12 +92,12 @@ impl TuiOutput {
         KeyboardHandlers::setup_all_handlers(
             &mut siv,
             state_manager.clone(),
-            location_manager,
+            location_manager.clone(),
             weather_fetcher.clone(),
         );
 
         // Set up automatic refresh when cache expires
-        weather_fetcher.setup_auto_refresh(&mut siv);
+        weather_fetcher.setup_auto_refresh(&mut siv, location_manager);
 
         // Run the TUI
         siv.run();
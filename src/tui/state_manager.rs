@@ -1,3 +1,4 @@
+use crate::api::location::LocationData;
 use crate::context::Context;
 use crate::settings::Units;
 use crate::Settings;
@@ -7,6 +8,7 @@ use std::sync::{Arc, Mutex};
 pub enum ForecastMode {
     Daily,  // 7-day forecast
     Hourly, // 24-hour forecast
+    Models, // Per-day comparison of the requested models
 }
 
 #[derive(Debug, Clone)]
@@ -20,6 +22,22 @@ pub struct TuiState {
     pub forecast_mode: ForecastMode,
 }
 
+impl TuiState {
+    /// Returns when the shown weather is refreshed.
+    ///
+    /// That is once it expires, counted from when it was fetched rather than loaded from
+    /// the cache, see `Weather::expires_at`. After a failed refresh, the next attempt
+    /// waits as long again.
+    fn refresh_at(&self) -> u64 {
+        let expires_at = self.weather_created_at + self.context.cache_ttl;
+        if self.context.stale {
+            expires_at.max(self.last_fetch_time + self.context.cache_ttl)
+        } else {
+            expires_at
+        }
+    }
+}
+
 pub struct TuiStateManager {
     pub state: Arc<Mutex<TuiState>>,
 }
@@ -33,7 +51,9 @@ impl TuiStateManager {
         let currently_selected_location = if settings.location.is_empty() {
             "Automatic".to_string()
         } else {
-            format!("{}, {}", context.city, context.country)
+            let location = LocationData::resolve_place(&settings.location, &settings.places)
+                .unwrap_or_else(|_| settings.location.clone());
+            LocationData::normalize_location_string(&location)
         };
 
         let initial_state = TuiState {
@@ -81,6 +101,16 @@ impl TuiStateManager {
         state_guard.currently_selected_location = location;
     }
 
+    /// Keeps showing the current weather, marked as stale, when refreshing it failed.
+    /// Another refresh is only attempted once the cache would have expired again.
+    pub fn mark_stale(&self, reason: String) {
+        let mut state_guard = self.state.lock().unwrap();
+        state_guard.context.stale = true;
+        state_guard.context.stale_reason = Some(reason);
+        state_guard.loading = false;
+        state_guard.last_fetch_time = crate::utils::get_now();
+    }
+
     pub fn get_current_location(&self) -> String {
         let state_guard = self.state.lock().unwrap();
         state_guard.currently_selected_location.clone()
@@ -100,10 +130,19 @@ impl TuiStateManager {
         state_guard.settings.clone()
     }
 
+    /// Returns whether the shown weather has expired, see `refresh_at`.
     pub fn needs_refresh(&self) -> bool {
+        let state_guard = self.state.lock().unwrap();
+        crate::utils::get_now() >= state_guard.refresh_at()
+    }
+
+    /// Returns how far along the shown weather is to its refresh, in percent.
+    pub fn refresh_progress(&self) -> usize {
         let state_guard = self.state.lock().unwrap();
         let now = crate::utils::get_now();
-        now - state_guard.last_fetch_time > super::constants::WEATHER_CACHE_DURATION
+        let lifetime = state_guard.refresh_at() - state_guard.weather_created_at;
+        let age = now - state_guard.weather_created_at;
+        ((age as f64 / lifetime.max(1) as f64) * 100.0).min(100.0) as usize
     }
 
     pub fn is_loading(&self) -> bool {
@@ -126,7 +165,9 @@ impl TuiStateManager {
         let mut state_guard = self.state.lock().unwrap();
         state_guard.forecast_mode = match state_guard.forecast_mode {
             ForecastMode::Daily => ForecastMode::Hourly,
-            ForecastMode::Hourly => ForecastMode::Daily,
+            // The model table is only offered when models were requested
+            ForecastMode::Hourly if !state_guard.context.models.is_empty() => ForecastMode::Models,
+            ForecastMode::Hourly | ForecastMode::Models => ForecastMode::Daily,
         };
         state_guard.forecast_mode.clone()
     }
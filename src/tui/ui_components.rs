@@ -1,13 +1,14 @@
-use crate::api::location::LocationData;
 use crate::tui::constants::*;
 use crate::tui::location_manager::LocationManager;
 use crate::tui::state_manager::{ForecastMode, TuiStateManager};
 use crate::tui::weather_display::WeatherDisplay;
 use crate::Settings;
 use cursive::align::HAlign;
-use cursive::theme::{Color, ColorType, PaletteColor, Theme};
+use cursive::theme::{BaseColor, Color, ColorType, Effect, PaletteColor, Style, Theme};
 use cursive::view::{Nameable, Resizable};
-use cursive::views::{DummyView, LinearLayout, Panel, ProgressBar, ResizedView, SelectView, TextView};
+use cursive::views::{
+    DummyView, HideableView, LinearLayout, Panel, ProgressBar, ResizedView, SelectView, TextView,
+};
 use cursive::Cursive;
 
 pub struct UiComponents;
@@ -59,8 +60,18 @@ impl UiComponents {
         let header_text = WeatherDisplay::format_header_text(&context);
         let current_info = WeatherDisplay::format_current_info(&context);
         let forecast_text = WeatherDisplay::format_forecast_text(&context);
+        let alert_banner = WeatherDisplay::format_alert_banner(&context);
 
         LinearLayout::vertical()
+            .child(
+                HideableView::new(
+                    TextView::new(alert_banner)
+                        .h_align(HAlign::Center)
+                        .style(Style::from(Color::Light(BaseColor::Red)).combine(Effect::Bold)),
+                )
+                .visible(!context.alerts.is_empty())
+                .with_name(ALERT_BANNER_NAME),
+            )
             .child(
                 Panel::new(TextView::new(header_text).h_align(HAlign::Center).with_name(WEATHER_HEADER_NAME))
                     .title(format!("{} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")))
@@ -94,7 +105,7 @@ impl UiComponents {
 
         // Ensure CLI location is in the location list for selection
         if !settings.location.is_empty() {
-            let normalized_cli_location = LocationData::normalize_location_string(&settings.location);
+            let normalized_cli_location = location_manager.get_current_location_string(&settings.location);
             location_manager.ensure_location_in_list(normalized_cli_location);
         }
 
@@ -106,7 +117,7 @@ impl UiComponents {
 
         // Add items to select view
         for location in &all_ordered_locations {
-            select.add_item(location.clone(), location.clone());
+            select.add_item(location_manager.display_name(location), location.clone());
         }
 
         // Set current location as selected
@@ -128,13 +139,28 @@ impl UiComponents {
     where
         F: Fn(&mut cursive::Cursive, &str) + 'static + Send + Sync,
     {
+        // Enter submits from either field
+        let on_submit = std::sync::Arc::new(on_submit);
+        let on_alias_submit = on_submit.clone();
+
         let edit_view = cursive::views::EditView::new()
             .content("")
-            .on_submit(on_submit)
+            .on_submit(move |s, content| on_submit(s, content))
             .with_name(NEW_LOCATION_NAME)
             .fixed_width(30);
+        let alias_view = cursive::views::EditView::new()
+            .content("")
+            .on_submit(move |s, content| on_alias_submit(s, content))
+            .with_name(NEW_LOCATION_ALIAS_NAME)
+            .fixed_width(30);
 
-        cursive::views::Dialog::around(edit_view).title("Add Location (City, Country)")
+        let layout = LinearLayout::vertical()
+            .child(edit_view)
+            .child(DummyView)
+            .child(TextView::new("Name (optional)"))
+            .child(alias_view);
+
+        cursive::views::Dialog::around(layout).title("Add Location (City, Country or Lat,Lon)")
     }
 
     pub fn create_delete_confirmation_dialog(location: &str) -> cursive::views::Dialog {
@@ -151,13 +177,19 @@ impl UiComponents {
         let forecast_mode = state_manager.get_forecast_mode();
         let header_text = WeatherDisplay::format_header_text(&context);
         let current_info = WeatherDisplay::format_current_info(&context);
+        let alert_banner = WeatherDisplay::format_alert_banner(&context);
 
         // Choose forecast content based on mode
         let forecast_text = match forecast_mode {
             ForecastMode::Daily => WeatherDisplay::format_forecast_text(&context),
             ForecastMode::Hourly => WeatherDisplay::format_hourly_forecast(&context),
+            ForecastMode::Models => WeatherDisplay::format_model_forecast(&context),
         };
 
+        siv.call_on_name(ALERT_BANNER_NAME, |view: &mut HideableView<TextView>| {
+            view.set_visible(!context.alerts.is_empty());
+            view.get_inner_mut().set_content(alert_banner);
+        });
         siv.call_on_name(WEATHER_HEADER_NAME, |view: &mut TextView| {
             view.set_content(header_text);
         });
@@ -169,9 +201,7 @@ impl UiComponents {
         });
 
         // Update data age progress bar
-        let cache_duration = crate::tui::constants::WEATHER_CACHE_DURATION;
-        let progress_percentage =
-            ((context.cache_age as f64 / cache_duration as f64) * 100.0).min(100.0) as usize;
+        let progress_percentage = state_manager.refresh_progress();
         siv.call_on_name(DATA_AGE_PROGRESS_NAME, |view: &mut ProgressBar| {
             view.set_value(progress_percentage);
         });
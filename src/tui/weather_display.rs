@@ -1,11 +1,12 @@
 use crate::context::Context;
+use crate::output::detailed::DetailedOutput;
 use crate::utils::weather_classification;
 
 pub struct WeatherDisplay;
 
 impl WeatherDisplay {
     pub fn format_header_text(context: &Context) -> String {
-        format!(
+        let header = format!(
             "{}, {}\n\
             {} {}{} • {} • Feels like {}{}",
             context.city,
@@ -16,7 +17,18 @@ impl WeatherDisplay {
             context.weather_description,
             context.feels_like.round(),
             context.temperature_unit
-        )
+        );
+
+        match &context.stale_reason {
+            Some(reason) => {
+                format!("{header}\n⚠ Offline, data from {} min ago: {reason}", context.cache_age / 60)
+            },
+            None => header,
+        }
+    }
+
+    pub fn format_alert_banner(context: &Context) -> String {
+        context.alerts.iter().map(|alert| format!("⚠ {}", alert.headline)).collect::<Vec<_>>().join("\n")
     }
 
     pub fn format_current_info(context: &Context) -> String {
@@ -48,11 +60,24 @@ impl WeatherDisplay {
             info.push_str(&format!("                 {description}"));
         }
 
+        if let (Some(us_aqi), Some(description)) =
+            (context.air_quality.us_aqi, &context.air_quality.aqi_description)
+        {
+            info.push_str(&format!("\nAir Quality:     {us_aqi} AQI ({description})"));
+        }
+
         info.push_str(&format!("\nSun:             {} • {}", context.sunrise, context.sunset));
 
         info
     }
 
+    pub fn format_model_forecast(context: &Context) -> String {
+        if context.models.is_empty() {
+            return "No models to compare".to_string();
+        }
+        DetailedOutput::format_model_comparison(context)
+    }
+
     pub fn format_hourly_forecast(context: &Context) -> String {
         // Calculate available width: assume 80 chars wide terminal minus location panel
         let available_width = Self::calculate_available_forecast_width();
@@ -1,14 +1,262 @@
+use crate::utils;
+use crate::Settings;
+
+use anyhow::{Context, Result};
+use savefile::prelude::*;
+use savefile_derive::Savefile;
 use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
 use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Version of the `CacheHeader` layout itself. Version 1 added the cache key.
+const HEADER_VERSION: u32 = 1;
+
+/// Units cached data is stored in. Every provider is fetched in metric units, and
+/// converted to the user's units only for output.
+const CACHE_UNITS: &str = "metric";
+
+/// Describes the data in a cache file, and is written at its start.
+///
+/// An entry is only used when its header matches the one asked for, so a cache file
+/// written for another provider, units or request, or by a newer version of the data
+/// type, is discarded rather than misread.
+#[derive(Savefile, Debug, Default, Clone, PartialEq)]
+pub struct CacheHeader {
+    /// The type of data, e.g. "weather"
+    pub datatype: String,
+    /// The savefile version of the data type, bumped with every change to its fields
+    pub schema: u32,
+    /// The provider the data was fetched from, if the data type has several
+    pub provider: String,
+    /// The units the data is stored in
+    pub units: String,
+    /// The parameters the data was requested with, e.g. "51.1,-114.1"
+    pub request: String,
+    /// The key the entry was saved under, e.g. the location string, set when saving
+    #[savefile_versions = "1.."]
+    pub key: String,
+}
+
+impl CacheHeader {
+    /// Creates a header for data of a type, requested with the given parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `datatype` - The type of data, e.g. "weather"
+    /// * `schema` - The savefile version of the data type
+    /// * `request` - The parameters the data is requested with, e.g. "51.1,-114.1"
+    ///
+    /// # Returns
+    ///
+    /// Returns a header without a provider, for data in metric units.
+    pub fn new(datatype: &str, schema: u32, request: impl Into<String>) -> Self {
+        CacheHeader {
+            datatype: datatype.to_string(),
+            schema,
+            provider: String::new(),
+            units: CACHE_UNITS.to_string(),
+            request: request.into(),
+            key: String::new(),
+        }
+    }
+
+    /// Sets the provider the data is fetched from.
+    pub fn with_provider(self, provider: &str) -> Self {
+        CacheHeader { provider: provider.to_string(), ..self }
+    }
+}
+
+/// Loads a cache entry, if there is one matching the header.
+///
+/// Entries written at an older schema version are migrated by savefile, using the
+/// `savefile_versions` of the data type's fields. Cache files from before headers were
+/// added are read from their previous location until the entry is saved again.
+///
+/// # Arguments
+///
+/// * `dir` - The cache directory, see `CacheConfig::dir`
+/// * `header` - What the entry must hold
+/// * `key` - The cache key, e.g. the location string
+///
+/// # Returns
+///
+/// Returns the cached data, or `None` if there is no entry, it doesn't match the header,
+/// or it can't be read.
+pub fn load<T: WithSchema + Deserialize>(dir: &Path, header: &CacheHeader, key: &str) -> Option<T> {
+    let path = cache_path(dir, &header.datatype, key);
+    if path.exists() {
+        return read(&path, header).ok();
+    }
+
+    load_file(legacy_cache_path(dir, &header.datatype, key), header.schema).ok()
+}
+
+/// Saves a cache entry with its header, replacing any previous entry.
+///
+/// # Arguments
+///
+/// * `dir` - The cache directory, see `CacheConfig::dir`
+/// * `header` - What the entry holds
+/// * `key` - The cache key, e.g. the location string
+/// * `data` - The data to cache
+///
+/// # Errors
+///
+/// This function will return an error if the cache file cannot be written.
+pub fn save<T: WithSchema + Serialize>(dir: &Path, header: &CacheHeader, key: &str, data: &T) -> Result<()> {
+    let header = CacheHeader { key: key.to_string(), ..header.clone() };
+    write(&cache_path(dir, &header.datatype, key), &header, data)?;
+
+    // Cache files from before headers were added are replaced by this one
+    let _ = std::fs::remove_file(legacy_cache_path(dir, &header.datatype, key));
+    Ok(())
+}
+
+fn read<T: WithSchema + Deserialize>(path: &Path, header: &CacheHeader) -> Result<T> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let stored: CacheHeader = savefile::load(&mut reader, HEADER_VERSION)?;
+    // Older schemas are migrated when loading the data, newer ones can't be read. The
+    // key is already part of the file name.
+    let same_data =
+        stored == CacheHeader { schema: stored.schema, key: stored.key.clone(), ..header.clone() };
+    if !same_data || stored.schema > header.schema {
+        return Err(anyhow::anyhow!("The cache file {} holds other data", path.display()));
+    }
+
+    Ok(savefile::load(&mut reader, header.schema)?)
+}
+
+fn write<T: WithSchema + Serialize>(path: &Path, header: &CacheHeader, data: &T) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Unable to create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    savefile::save(&mut writer, HEADER_VERSION, header)?;
+    savefile::save(&mut writer, header.schema, data)?;
+    Ok(())
+}
+
+/// A file in the cache directory.
+#[derive(Debug)]
+pub struct CacheEntry {
+    /// The path of the cache file
+    pub path: PathBuf,
+    /// The header of the file, or `None` for files from before headers were added
+    pub header: Option<CacheHeader>,
+    /// The type of data, from the header or else the file name
+    pub datatype: String,
+    /// The size of the file in bytes
+    pub size: u64,
+    /// Seconds since the file was written
+    pub age: u64,
+}
+
+impl CacheEntry {
+    /// Returns the file name of the entry, e.g. "weather-a1b2c3d4e5f60718.cache".
+    pub fn name(&self) -> String {
+        self.path.file_name().unwrap_or_default().to_string_lossy().to_string()
+    }
+
+    /// Loads the data of the entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - The current savefile version of the data type, older entries are migrated to it
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the entry has no header, or its data cannot be read
+    /// at the given version.
+    pub fn load<T: WithSchema + Deserialize>(&self, schema: u32) -> Result<T> {
+        let header = self
+            .header
+            .as_ref()
+            .with_context(|| format!("The cache file {} predates the current cache format", self.name()))?;
+
+        read(&self.path, &CacheHeader { schema, ..header.clone() })
+    }
+
+    /// Deletes the cache file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be removed.
+    pub fn remove(&self) -> Result<()> {
+        std::fs::remove_file(&self.path).with_context(|| format!("Unable to remove {}", self.path.display()))
+    }
+}
+
+/// Checks whether data cached at `created_at` may still be shown when refreshing it failed.
+///
+/// # Arguments
+///
+/// * `created_at` - Unix timestamp of when the data was fetched
+/// * `s` - Settings containing the cache lifetimes
+///
+/// # Returns
+///
+/// Returns `true` if the data is no older than `max_stale`, or whatever its age in offline
+/// mode, where there is nothing newer to show.
+pub fn usable_when_stale(created_at: u64, s: &Settings) -> bool {
+    s.offline || utils::get_now() - created_at < s.cache.max_stale_seconds()
+}
+
+/// Lists the files in the cache directory.
+///
+/// # Arguments
+///
+/// * `dir` - The cache directory, see `CacheConfig::dir`
+///
+/// # Returns
+///
+/// Returns the cache entries sorted by data type and file name.
+///
+/// # Errors
+///
+/// This function will return an error if the cache directory cannot be read.
+pub fn entries(dir: &Path) -> Result<Vec<CacheEntry>> {
+    create_dir(dir);
+    let mut entries = Vec::new();
+
+    for file in std::fs::read_dir(dir).with_context(|| format!("Unable to read {}", dir.display()))? {
+        let path = file?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(".cache"))
+        else {
+            continue;
+        };
+
+        let metadata = std::fs::metadata(&path)?;
+        let age = metadata.modified().ok().and_then(|m| m.elapsed().ok()).map_or(0, |d| d.as_secs());
+        let header = read_header(&path).ok();
+        let datatype = match &header {
+            Some(header) => header.datatype.clone(),
+            None => name.rsplit_once('-').map_or(name, |(datatype, _)| datatype).to_string(),
+        };
+
+        entries.push(CacheEntry { path, header, datatype, size: metadata.len(), age });
+    }
+
+    entries.sort_by(|a, b| (&a.datatype, a.name()).cmp(&(&b.datatype, b.name())));
+    Ok(entries)
+}
+
+fn read_header(path: &Path) -> Result<CacheHeader> {
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(savefile::load(&mut reader, HEADER_VERSION)?)
+}
 
 /// Generates a cache file path for the given data type and content.
 ///
 /// Creates a hashed filename to ensure unique cache files for different
 /// locations. The cache directory is created if it doesn't exist, and the
-/// filename includes a hash of the content to prevent cache conflicts.
+/// filename includes a hash of the content to prevent cache conflicts. The
+/// hash is stable, so the same content maps to the same file across releases.
 ///
 /// # Arguments
 ///
+/// * `dir` - The cache directory, see `CacheConfig::dir`
 /// * `datatype` - The type of data being cached (e.g., "weather", "location")
 /// * `content` - The content identifier (e.g., location string)
 ///
@@ -19,23 +267,67 @@ use std::hash::{Hash, Hasher};
 /// # Panics
 ///
 /// Panics if the cache directory cannot be created.
-pub fn get_cached_file(datatype: &str, content: &str) -> String {
+pub fn get_cached_file(dir: &Path, datatype: &str, content: &str) -> String {
+    cache_path(dir, datatype, content).display().to_string()
+}
+
+fn cache_path(dir: &Path, datatype: &str, content: &str) -> PathBuf {
+    create_dir(dir);
+    dir.join(format!("{datatype}-{:016x}.cache", stable_hash(content)))
+}
+
+/// Path of a cache file from before headers were added, named with the standard
+/// library's hasher, which is not guaranteed to be stable across Rust releases.
+fn legacy_cache_path(dir: &Path, datatype: &str, content: &str) -> PathBuf {
     let mut hasher = DefaultHasher::new();
     content.hash(&mut hasher);
 
-    let hash = format!("{:x}", hasher.finish());
-
-    std::fs::create_dir_all(
-        dirs_next::cache_dir()
-            .unwrap_or_else(|| dirs_next::home_dir().unwrap_or_default())
-            .join(env!("CARGO_PKG_NAME")),
-    )
-    .unwrap_or_else(|_| panic!("Unable to create the cache directory for {}", env!("CARGO_PKG_NAME")));
-
-    dirs_next::cache_dir()
-        .unwrap_or_else(|| dirs_next::home_dir().unwrap_or_default())
-        .join(env!("CARGO_PKG_NAME"))
-        .join(format!("{datatype}-{hash}.cache"))
-        .display()
-        .to_string()
+    dir.join(format!("{datatype}-{:x}.cache", hasher.finish()))
+}
+
+fn create_dir(dir: &Path) {
+    std::fs::create_dir_all(dir)
+        .unwrap_or_else(|_| panic!("Unable to create the cache directory {}", dir.display()));
+}
+
+/// Hashes a cache key with 64-bit FNV-1a, which gives the same result on every
+/// platform and release.
+fn stable_hash(content: &str) -> u64 {
+    content
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stable_hash() {
+        assert_eq!(stable_hash(""), 0xcbf29ce484222325);
+        assert_eq!(stable_hash("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(stable_hash("Calgary, CA"), stable_hash("Calgary, CA"));
+        assert_ne!(stable_hash("Calgary, CA"), stable_hash("Calgary, US"));
+    }
+
+    #[test]
+    fn test_read_write() {
+        let path = std::env::temp_dir().join(format!(
+            "{}-test-{}.cache",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        let header = CacheHeader::new("test", 1, "51.1,-114.1").with_provider("open-meteo");
+
+        write(&path, &header, &String::from("cached")).unwrap();
+        assert_eq!(read::<String>(&path, &header).unwrap(), "cached");
+
+        // Entries for other requests, providers or newer schemas are discarded
+        assert!(read::<String>(&path, &CacheHeader::new("test", 1, "45.5,-73.6")).is_err());
+        assert!(read::<String>(&path, &CacheHeader { schema: 0, ..header.clone() }).is_err());
+        // Entries from older schemas are migrated
+        assert_eq!(read::<String>(&path, &CacheHeader { schema: 2, ..header }).unwrap(), "cached");
+
+        std::fs::remove_file(path).unwrap();
+    }
 }
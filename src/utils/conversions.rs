@@ -48,6 +48,27 @@ pub fn iso8601_to_date(iso8601: String) -> String {
     dt.format("%a %m/%d").to_string()
 }
 
+/// Converts an ISO8601 datetime string to a human-readable date and time format.
+///
+/// Combines the formats of `iso8601_to_date` and `iso8601_to_time`, for example
+/// "Mon 03/15 08:30pm" or "Mon 03/15 20:30".
+///
+/// # Arguments
+///
+/// * `iso8601` - A datetime string in ISO8601 format
+/// * `hour24` - If true, uses 24-hour format; if false, uses 12-hour format
+///
+/// # Returns
+///
+/// Returns the formatted date and time, or an empty string if the input cannot be parsed.
+pub fn iso8601_to_datetime(iso8601: String, hour24: bool) -> String {
+    match NaiveDateTime::parse_from_str(&iso8601, "%Y-%m-%dT%H:%M") {
+        Ok(dt) if hour24 => dt.format("%a %m/%d %H:%M").to_string(),
+        Ok(dt) => dt.format("%a %m/%d %I:%M%P").to_string(),
+        Err(_) => String::new(),
+    }
+}
+
 /// Converts temperature from Celsius to Fahrenheit.
 ///
 /// # Arguments
@@ -86,3 +107,24 @@ pub fn kmh_to_mph(kmh: f64) -> f64 {
 pub fn mm_to_inches(mm: f64) -> f64 {
     (mm * 0.0393701 * 10.0).round() / 10.0
 }
+
+/// Calculates the apparent ("feels like") temperature.
+///
+/// Uses the Australian Bureau of Meteorology formula, which accounts for
+/// humidity and wind. This is the same model Open-Meteo uses, so providers
+/// that don't report a feels-like value stay consistent with it.
+///
+/// # Arguments
+///
+/// * `celsius` - Air temperature in Celsius
+/// * `relative_humidity` - Relative humidity in percent
+/// * `wind_kmh` - Wind speed in kilometers per hour
+///
+/// # Returns
+///
+/// Returns the apparent temperature in Celsius, rounded to 1 decimal place.
+pub fn apparent_temperature(celsius: f64, relative_humidity: f64, wind_kmh: f64) -> f64 {
+    let vapour_pressure = relative_humidity / 100.0 * 6.105 * ((17.27 * celsius) / (237.7 + celsius)).exp();
+    let wind_ms = wind_kmh / 3.6;
+    ((celsius + 0.33 * vapour_pressure - 0.70 * wind_ms - 4.00) * 10.0).round() / 10.0
+}
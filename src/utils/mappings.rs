@@ -164,3 +164,148 @@ pub fn weather_description(code: i32) -> String {
     }
     .to_string()
 }
+
+/// Converts a MET Norway symbol code to the equivalent Open-Meteo (WMO) weather code.
+///
+/// MET Norway describes conditions with symbol codes such as "lightrainshowers_day"
+/// or "heavysnow". The day/night/polartwilight suffix is ignored, and the remaining
+/// condition is mapped to the closest WMO code so that icons and descriptions
+/// keep working. Sleet has no WMO equivalent and is mapped to freezing rain.
+///
+/// # Arguments
+///
+/// * `symbol` - MET Norway symbol code
+///
+/// # Returns
+///
+/// Returns the equivalent WMO weather code, or -1 if unmapped.
+pub fn metno_symbol2meteo_code(symbol: &str) -> i32 {
+    let condition = symbol.split('_').next().unwrap_or_default();
+
+    if condition.contains("thunder") {
+        return 95;
+    }
+
+    match condition {
+        "clearsky" => 0,
+        "fair" => 1,
+        "partlycloudy" => 2,
+        "cloudy" => 3,
+        "fog" => 45,
+        "lightrain" => 61,
+        "rain" => 63,
+        "heavyrain" => 65,
+        "lightsleet" | "lightsleetshowers" => 66,
+        "sleet" | "heavysleet" | "sleetshowers" | "heavysleetshowers" => 67,
+        "lightsnow" => 71,
+        "snow" => 73,
+        "heavysnow" => 75,
+        "lightrainshowers" => 80,
+        "rainshowers" => 81,
+        "heavyrainshowers" => 82,
+        "lightsnowshowers" | "snowshowers" => 85,
+        "heavysnowshowers" => 86,
+        _ => -1,
+    }
+}
+
+/// Converts a 16-point compass direction to degrees.
+///
+/// This is the inverse of `degrees2compass`, used for providers that report
+/// wind direction as text (e.g. "NNW") rather than degrees.
+///
+/// # Arguments
+///
+/// * `compass` - Compass direction string (N, NNE, NE, ... NNW)
+///
+/// # Returns
+///
+/// Returns the direction in degrees, or 0 if the direction is unknown.
+pub fn compass2degrees(compass: &str) -> i32 {
+    const POINTS: [&str; 16] =
+        ["N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW", "NNW"];
+
+    POINTS
+        .iter()
+        .position(|&point| point.eq_ignore_ascii_case(compass.trim()))
+        .map(|index| (index as f64 * 22.5).round() as i32)
+        .unwrap_or(0)
+}
+
+/// Converts a US National Weather Service icon URL to the equivalent Open-Meteo (WMO) weather code.
+///
+/// NWS icon URLs encode one or two conditions in their path, for example
+/// `https://api.weather.gov/icons/land/day/tsra_sct,40/rain_showers,30?size=medium`.
+/// Each condition is mapped to the closest WMO code and the most severe one wins.
+///
+/// # Arguments
+///
+/// * `icon` - NWS icon URL
+///
+/// # Returns
+///
+/// Returns the equivalent WMO weather code, or -1 if unmapped.
+pub fn nws_icon2meteo_code(icon: &str) -> i32 {
+    let path = icon.split('?').next().unwrap_or_default();
+
+    path.split('/')
+        .skip_while(|segment| *segment != "day" && *segment != "night")
+        .skip(1)
+        .map(|segment| match segment.split(',').next().unwrap_or_default() {
+            "skc" | "wind_skc" | "hot" | "cold" => 0,
+            "few" | "wind_few" => 1,
+            "sct" | "wind_sct" | "bkn" | "wind_bkn" => 2,
+            "ovc" | "wind_ovc" => 3,
+            "fog" | "haze" | "smoke" | "dust" => 45,
+            "rain" => 63,
+            "rain_showers_hi" => 80,
+            "rain_showers" => 81,
+            "fzra" | "rain_fzra" | "rain_sleet" => 66,
+            "sleet" | "snow_fzra" | "snow_sleet" => 67,
+            "snow" | "rain_snow" => 73,
+            "blizzard" => 75,
+            "tsra" | "tsra_sct" | "tsra_hi" | "tornado" | "hurricane" | "tropical_storm" => 95,
+            _ => -1,
+        })
+        .max()
+        .unwrap_or(-1)
+}
+
+/// Converts an Environment and Climate Change Canada icon code to the equivalent Open-Meteo (WMO) weather code.
+///
+/// ECCC citypage forecasts use numeric icon codes (00-48), with codes 30-39
+/// being the nighttime variants of codes 00-09.
+///
+/// # Arguments
+///
+/// * `code` - ECCC icon code
+///
+/// # Returns
+///
+/// Returns the equivalent WMO weather code, or -1 if unmapped.
+pub fn eccc_icon2meteo_code(code: i32) -> i32 {
+    let code = if (30..=39).contains(&code) { code - 30 } else { code };
+
+    match code {
+        0 => 0,                  // Sunny / clear
+        1 | 5 => 1,              // Mainly sunny, decreasing cloud
+        2 | 4 | 43 => 2,         // Partly cloudy, increasing cloud, windy
+        3 | 10 | 22 => 3,        // Mostly cloudy, cloudy
+        6 => 80,                 // Chance of showers
+        7 | 15 => 67,            // Rain and snow
+        8 => 85,                 // Chance of flurries
+        9 | 19 | 41 | 42 => 95,  // Thunderstorms, funnel cloud, tornado
+        11 | 12 => 63,           // Precipitation, rain
+        13 => 65,                // Heavy rain
+        14 => 66,                // Freezing rain
+        16 => 71,                // Light snow
+        17 | 25 | 40 => 73,      // Snow, drifting or blowing snow
+        18 => 75,                // Heavy snow
+        23 | 24 | 44 | 45 => 45, // Haze, fog, smoke, dust
+        26 => 77,                // Ice crystals
+        27 | 46 | 47 => 96,      // Hail, thunderstorm with hail or dust
+        28 => 51,                // Drizzle
+        48 => 99,                // Waterspout
+        _ => -1,
+    }
+}
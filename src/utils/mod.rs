@@ -1,6 +1,10 @@
 pub mod cache;
 pub mod conversions;
 pub mod mappings;
+pub mod network;
+pub mod solar;
+#[cfg(test)]
+pub mod test_server;
 pub mod unitstrings;
 pub mod urls;
 pub mod weather_classification;
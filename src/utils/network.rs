@@ -0,0 +1,82 @@
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+
+/// Addresses used to find the local address of the default route. Connecting a UDP
+/// socket only selects a route, no packets are sent.
+const ROUTE_PROBES: [&str; 2] = ["192.0.2.1:53", "[2001:db8::1]:53"];
+
+/// Identifies the network this machine is connected to, to notice when it moves.
+///
+/// Combines the default route's interface and gateway (on Linux) with the local
+/// address used to reach the internet, all of which change when joining a different
+/// network. Nothing is sent over the network.
+///
+/// # Returns
+///
+/// Returns a fingerprint of the current network, or an empty string if it can't be
+/// determined, e.g. when offline.
+pub fn fingerprint() -> String {
+    let route = std::fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|table| parse_default_route(&table))
+        .map(|(interface, gateway)| format!("{interface} via {gateway}"));
+    let local_address = local_address().map(|address| address.to_string());
+
+    [route, local_address].into_iter().flatten().collect::<Vec<_>>().join(", ")
+}
+
+/// Finds the local address that traffic to the internet is sent from.
+fn local_address() -> Option<IpAddr> {
+    ROUTE_PROBES.iter().find_map(|probe| {
+        let bind = if probe.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" };
+        let socket = UdpSocket::bind(bind).ok()?;
+        socket.connect(probe).ok()?;
+        socket.local_addr().ok().map(|address| address.ip())
+    })
+}
+
+/// Reads the default route's interface and gateway from the Linux routing table.
+///
+/// # Arguments
+///
+/// * `table` - The contents of `/proc/net/route`
+///
+/// # Returns
+///
+/// Returns the interface name and gateway address of the default route with the
+/// lowest metric, or `None` if there is no default route.
+pub fn parse_default_route(table: &str) -> Option<(String, Ipv4Addr)> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 7 || fields[1] != "00000000" {
+                return None;
+            }
+
+            // The gateway is a hexadecimal address in host byte order
+            let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+            let metric: u32 = fields[6].parse().ok()?;
+            Some((metric, fields[0].to_string(), Ipv4Addr::from(gateway.to_ne_bytes())))
+        })
+        .min_by_key(|(metric, _, _)| *metric)
+        .map(|(_, interface, gateway)| (interface, gateway))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_default_route() {
+        let table = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                     wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0\n\
+                     eth0\t00000000\t010200C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+                     eth0\t000200C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n";
+        assert_eq!(parse_default_route(table), Some(("eth0".to_string(), Ipv4Addr::new(192, 0, 2, 1))));
+
+        let table = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                     eth0\t000200C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n";
+        assert_eq!(parse_default_route(table), None);
+    }
+}
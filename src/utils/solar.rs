@@ -0,0 +1,68 @@
+use chrono::{DateTime, NaiveDate};
+
+const J2000: f64 = 2451545.0;
+const UNIX_EPOCH_JULIAN: f64 = 2440587.5;
+const EARTH_TILT: f64 = 23.4397;
+const SUN_ALTITUDE: f64 = -0.833;
+
+/// Calculates local sunrise and sunset times for a date and location.
+///
+/// Uses the sunrise equation (NOAA simplified algorithm), which is accurate
+/// to within a minute or two. This is used by weather providers whose forecast
+/// responses do not include sunrise and sunset times.
+///
+/// During polar night both times are solar noon, and during polar day the
+/// sun is reported as rising at 00:00 and setting at 23:59.
+///
+/// # Arguments
+///
+/// * `lat` - Latitude in degrees (north positive)
+/// * `lon` - Longitude in degrees (east positive)
+/// * `date` - The local calendar date
+/// * `utc_offset_seconds` - UTC offset of the location's timezone
+///
+/// # Returns
+///
+/// Returns a tuple of (sunrise, sunset) as ISO8601 local datetime strings ("YYYY-MM-DDTHH:MM").
+pub fn sunrise_sunset(lat: f64, lon: f64, date: NaiveDate, utc_offset_seconds: i32) -> (String, String) {
+    let epoch = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+    let days = (date - epoch).num_days() as f64;
+
+    let mean_solar_time = days - lon / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_solar_time).rem_euclid(360.0).to_radians();
+    let center = 1.9148 * anomaly.sin() + 0.0200 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic_longitude =
+        (anomaly.to_degrees() + center + 180.0 + 102.9372).rem_euclid(360.0).to_radians();
+    let transit =
+        J2000 + mean_solar_time + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+
+    let declination = (ecliptic_longitude.sin() * EARTH_TILT.to_radians().sin()).asin();
+    let latitude = lat.to_radians();
+    let cos_hour_angle = (SUN_ALTITUDE.to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+
+    if cos_hour_angle > 1.0 {
+        let noon = julian_to_local(transit, utc_offset_seconds);
+        return (noon.clone(), noon);
+    }
+    if cos_hour_angle < -1.0 {
+        let day = date.format("%Y-%m-%d");
+        return (format!("{day}T00:00"), format!("{day}T23:59"));
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    let sunrise = julian_to_local(transit - hour_angle / 360.0, utc_offset_seconds);
+    let sunset = julian_to_local(transit + hour_angle / 360.0, utc_offset_seconds);
+
+    (sunrise, sunset)
+}
+
+/// Converts a Julian date to a local ISO8601 datetime string.
+fn julian_to_local(julian: f64, utc_offset_seconds: i32) -> String {
+    let timestamp = ((julian - UNIX_EPOCH_JULIAN) * 86400.0).round() as i64 + utc_offset_seconds as i64;
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .naive_utc()
+        .format("%Y-%m-%dT%H:%M")
+        .to_string()
+}
@@ -0,0 +1,114 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// A fixed response served by the test server.
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Creates a successful response with the given body.
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Response { status: 200, headers: Vec::new(), body: body.into() }
+    }
+
+    /// Creates an empty response with the given status, e.g. 500.
+    pub fn status(status: u16) -> Self {
+        Response { status, headers: Vec::new(), body: Vec::new() }
+    }
+
+    /// Adds a header to the response, e.g. `Retry-After`.
+    pub fn with_header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+}
+
+/// Starts a local HTTP server that serves fixed responses for offline tests.
+///
+/// Each route is matched against the request path (without the query string).
+/// Unknown paths return a 404. The server runs on a background thread for the
+/// remainder of the test process.
+///
+/// # Arguments
+///
+/// * `routes` - List of (path, body) pairs to serve
+///
+/// # Returns
+///
+/// Returns the base URL of the server, e.g. "http://127.0.0.1:40123".
+pub fn serve(routes: Vec<(&'static str, String)>) -> String {
+    serve_responses(routes.into_iter().map(|(path, body)| (path, Response::ok(body))).collect())
+}
+
+/// Starts a local HTTP server like `serve`, with full control over each response.
+///
+/// # Arguments
+///
+/// * `routes` - List of (path, response) pairs to serve
+///
+/// # Returns
+///
+/// Returns the base URL of the server, e.g. "http://127.0.0.1:40123".
+pub fn serve_responses(routes: Vec<(&'static str, Response)>) -> String {
+    serve_linked(|_| routes)
+}
+
+/// Starts a local HTTP server like `serve_responses`, for APIs whose responses link to
+/// further requests, e.g. the NWS forecast URLs of a gridpoint.
+///
+/// # Arguments
+///
+/// * `routes` - Builds the (path, response) pairs to serve from the base URL of the server
+///
+/// # Returns
+///
+/// Returns the base URL of the server, e.g. "http://127.0.0.1:40123".
+pub fn serve_linked(routes: impl FnOnce(&str) -> Vec<(&'static str, Response)>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Unable to bind test server");
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let routes = routes(&base_url);
+
+    thread::spawn(move || {
+        let not_found = Response::status(404);
+
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).is_err() {
+                continue;
+            }
+
+            // Drain the request headers
+            let mut header = String::new();
+            while reader.read_line(&mut header).is_ok() && header.trim() != "" {
+                header.clear();
+            }
+
+            let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+            let path = target.split('?').next().unwrap_or(target);
+
+            let response =
+                routes.iter().find(|(route, _)| *route == path).map_or(&not_found, |(_, response)| response);
+
+            let mut head = format!("HTTP/1.1 {} Test\r\n", response.status);
+            for (name, value) in &response.headers {
+                head.push_str(&format!("{name}: {value}\r\n"));
+            }
+            head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", response.body.len()));
+
+            let _ = (&stream).write_all(head.as_bytes()).and_then(|_| (&stream).write_all(&response.body));
+        }
+    });
+
+    base_url
+}
+
+/// Returns a cache directory for tests, which keeps their entries out of the user's cache.
+pub fn cache_dir() -> String {
+    let dir = std::env::temp_dir().join(format!("{}-test-{}", env!("CARGO_PKG_NAME"), std::process::id()));
+    dir.display().to_string()
+}
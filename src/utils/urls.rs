@@ -28,3 +28,32 @@ pub fn builder(base_url: &str, params: Vec<(&str, &str)>) -> String {
 
     url.to_string()
 }
+
+/// Hides the API key of a request URL, for URLs shown in errors and logs.
+///
+/// # Arguments
+///
+/// * `url` - The request URL, possibly with an `apikey` query parameter
+///
+/// # Returns
+///
+/// Returns the URL with the `apikey` value replaced by `***`, or unchanged if it has none.
+pub fn redact(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    if !parsed.query_pairs().any(|(key, _)| key == "apikey") {
+        return url.to_string();
+    }
+
+    let params: Vec<(String, String)> = parsed
+        .query_pairs()
+        .map(|(key, value)| {
+            let value = if key == "apikey" { "***".into() } else { value };
+            (key.into_owned(), value.into_owned())
+        })
+        .collect();
+    parsed.query_pairs_mut().clear().extend_pairs(params);
+
+    parsed.to_string()
+}
@@ -111,6 +111,150 @@ pub fn get_all_weather_css_classes(
     classes
 }
 
+/// Air quality categories, following the US EPA Air Quality Index bands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AirQualityCategory {
+    Good,
+    Moderate,
+    UnhealthySensitive,
+    Unhealthy,
+    VeryUnhealthy,
+    Hazardous,
+}
+
+/// Classifies a US Air Quality Index value into its EPA category.
+///
+/// # Arguments
+///
+/// * `us_aqi` - The US AQI value
+///
+/// # Returns
+///
+/// Returns an `AirQualityCategory` enum representing the AQI band.
+///
+/// # AQI Ranges
+///
+/// - **Good**: 0-50
+/// - **Moderate**: 51-100
+/// - **Unhealthy for Sensitive Groups**: 101-150
+/// - **Unhealthy**: 151-200
+/// - **Very Unhealthy**: 201-300
+/// - **Hazardous**: 301 and above
+pub fn classify_aqi(us_aqi: i32) -> AirQualityCategory {
+    match us_aqi {
+        ..=50 => AirQualityCategory::Good,
+        51..=100 => AirQualityCategory::Moderate,
+        101..=150 => AirQualityCategory::UnhealthySensitive,
+        151..=200 => AirQualityCategory::Unhealthy,
+        201..=300 => AirQualityCategory::VeryUnhealthy,
+        _ => AirQualityCategory::Hazardous,
+    }
+}
+
+/// Gets the human-readable description of a US AQI value.
+///
+/// # Arguments
+///
+/// * `us_aqi` - The US AQI value
+///
+/// # Returns
+///
+/// Returns the EPA category name, e.g. "Unhealthy for Sensitive Groups".
+pub fn aqi_description(us_aqi: i32) -> String {
+    match classify_aqi(us_aqi) {
+        AirQualityCategory::Good => "Good",
+        AirQualityCategory::Moderate => "Moderate",
+        AirQualityCategory::UnhealthySensitive => "Unhealthy for Sensitive Groups",
+        AirQualityCategory::Unhealthy => "Unhealthy",
+        AirQualityCategory::VeryUnhealthy => "Very Unhealthy",
+        AirQualityCategory::Hazardous => "Hazardous",
+    }
+    .to_string()
+}
+
+/// Gets the CSS class name for a US AQI value (used in Waybar output).
+///
+/// # Arguments
+///
+/// * `us_aqi` - The US AQI value
+///
+/// # Returns
+///
+/// Returns the CSS class name, e.g. "aqi-good" or "aqi-unhealthy".
+pub fn get_aqi_css_class(us_aqi: i32) -> String {
+    match classify_aqi(us_aqi) {
+        AirQualityCategory::Good => "aqi-good",
+        AirQualityCategory::Moderate => "aqi-moderate",
+        AirQualityCategory::UnhealthySensitive => "aqi-unhealthy-sensitive",
+        AirQualityCategory::Unhealthy => "aqi-unhealthy",
+        AirQualityCategory::VeryUnhealthy => "aqi-very-unhealthy",
+        AirQualityCategory::Hazardous => "aqi-hazardous",
+    }
+    .to_string()
+}
+
+/// How closely several forecast models agree on a day's weather.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelAgreement {
+    Good,
+    Fair,
+    Poor,
+}
+
+/// Classifies how closely forecast models agree, from the spread of their forecasts.
+///
+/// Precipitation counts as a disagreement when some models forecast a dry day
+/// (under 1 mm) and others a wet one.
+///
+/// # Arguments
+///
+/// * `temperature_spread` - Largest difference between the models' highs or lows, in °C
+/// * `precipitation_min` - Smallest precipitation total forecast by any model, in mm
+/// * `precipitation_max` - Largest precipitation total forecast by any model, in mm
+///
+/// # Returns
+///
+/// Returns a `ModelAgreement` enum representing how far the forecasts can be trusted.
+///
+/// # Agreement Levels
+///
+/// - **Good**: temperatures within 2°C, and all models agree on dry or wet
+/// - **Poor**: temperatures more than 5°C apart, or models disagree on at least 5 mm of precipitation
+/// - **Fair**: Everything in between
+pub fn classify_model_agreement(
+    temperature_spread: f64,
+    precipitation_min: f64,
+    precipitation_max: f64,
+) -> ModelAgreement {
+    let precipitation_disagrees = precipitation_min < 1.0 && precipitation_max >= 1.0;
+
+    if temperature_spread > 5.0 || (precipitation_disagrees && precipitation_max >= 5.0) {
+        ModelAgreement::Poor
+    } else if temperature_spread <= 2.0 && !precipitation_disagrees {
+        ModelAgreement::Good
+    } else {
+        ModelAgreement::Fair
+    }
+}
+
+/// Gets the lowercase name of a model agreement level, for display and templates.
+///
+/// # Arguments
+///
+/// * `agreement` - The model agreement level
+///
+/// # Returns
+///
+/// Returns "good", "fair" or "poor".
+pub fn model_agreement_name(agreement: ModelAgreement) -> String {
+    match agreement {
+        ModelAgreement::Good => "good",
+        ModelAgreement::Fair => "fair",
+        ModelAgreement::Poor => "poor",
+    }
+    .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -164,4 +308,26 @@ mod tests {
         let classes = get_all_weather_css_classes(60, 30.0, Some(30.0), Some(0.0));
         assert_eq!(classes, vec!["rain"]); // Should not include "hot" for exactly at threshold
     }
+
+    #[test]
+    fn test_get_aqi_css_class() {
+        assert_eq!(get_aqi_css_class(0), "aqi-good");
+        assert_eq!(get_aqi_css_class(50), "aqi-good");
+        assert_eq!(get_aqi_css_class(51), "aqi-moderate");
+        assert_eq!(get_aqi_css_class(120), "aqi-unhealthy-sensitive");
+        assert_eq!(get_aqi_css_class(180), "aqi-unhealthy");
+        assert_eq!(get_aqi_css_class(250), "aqi-very-unhealthy");
+        assert_eq!(get_aqi_css_class(420), "aqi-hazardous");
+        assert_eq!(aqi_description(120), "Unhealthy for Sensitive Groups");
+    }
+
+    #[test]
+    fn test_classify_model_agreement() {
+        assert_eq!(classify_model_agreement(1.5, 0.0, 0.4), ModelAgreement::Good);
+        assert_eq!(classify_model_agreement(1.5, 2.0, 6.0), ModelAgreement::Good);
+        assert_eq!(classify_model_agreement(3.0, 0.0, 0.0), ModelAgreement::Fair);
+        assert_eq!(classify_model_agreement(1.0, 0.0, 2.0), ModelAgreement::Fair);
+        assert_eq!(classify_model_agreement(6.0, 0.0, 0.0), ModelAgreement::Poor);
+        assert_eq!(classify_model_agreement(1.0, 0.2, 8.0), ModelAgreement::Poor);
+    }
 }